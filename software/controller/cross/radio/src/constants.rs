@@ -2,15 +2,40 @@
 
 //use static_assertions::{self, const_assert};
 
+use embassy_time::Duration;
+use http::{CertificateVerification, Proxy};
+
 // URL where the list of stations are for rusty-radio
 pub const STATIONS_URL: &str = "http://andrew-doble.hier-im-netz.de/ir/rr-stations.txt";
 
+// How often the list of stations is checked for changes. The list is only downloaded
+// again if it has changed.
+pub const STATIONS_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// How the certificates of HTTPS servers (stations and the station list) are verified.
+// To verify them, give the certificate of the certificate authority in DER format, e.g.:
+//   CertificateVerification::CertificateAuthority(include_bytes!("../certs/isrg-root-x1.der"))
+// Note that only servers with certificates from this authority can then be used.
+// A user name and password in an HTTPS URL are only sent if the certificates are verified.
+pub const CERTIFICATE_VERIFICATION: CertificateVerification<'static> =
+    CertificateVerification::None;
+
+// The HTTP proxy to connect through, for networks that do not allow direct connections
+// to the internet, e.g. Some(Proxy::new("192.168.1.10", 3128)). This is used for the
+// stations and the station list.
+pub const PROXY: Option<Proxy<'static>> = None;
+
+// The highest bandwidth (in bits per second) of the variant of an HLS stream that is played.
+// If a station has no variant with this or less, its lowest bandwidth variant is played.
+pub const MAX_HLS_BANDWIDTH: u32 = 128_000;
+
 //pub const NUMBER_SOCKETS_STACK_RESOURCES: usize = 3;
-// Need double the number of reseources (from the usual 3) as we are setting up two sockets:
+// Need three times the number of reseources (from the usual 3) as we are setting up three sockets:
 //  - one for the audio streaming
 //  - one to read the station list
-pub const NUMBER_SOCKETS_STACK_RESOURCES: usize = 6;
-//pub const NUMBER_SOCKETS_STACK_RESOURCES: usize = 9;
+//  - one for the web server
+//pub const NUMBER_SOCKETS_STACK_RESOURCES: usize = 6;
+pub const NUMBER_SOCKETS_STACK_RESOURCES: usize = 9;
 //pub const NUMBER_SOCKETS_STACK_RESOURCES: usize = 12;  // Used to work
 //pub const NUMBER_SOCKETS_TCP_CLIENT_STATE: usize = 6;
 
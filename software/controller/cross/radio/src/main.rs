@@ -32,6 +32,7 @@ use task::{
     sync::CODEC_DRIVER,
     sync::MULTIPLEXER_DRIVER,
     sync::STATION_CHANGE_WATCH,
+    web::web,
 
     //access_radio_stations::access_radio_stations,
     //tuner::tuner,
@@ -49,6 +50,9 @@ use front_panel::FrontPanel;
 mod sendable_multiplexer_driver;
 use sendable_multiplexer_driver::SendableMultiplexerDriver;
 
+mod tls_buffers;
+mod tls_rng;
+
 //use stations::{Station, Stations};
 
 // External crates
@@ -116,6 +120,11 @@ esp_bootloader_esp_idf::esp_app_desc!();
 #[esp_rtos::main]
 async fn main(spawner: Spawner) {
     esp_println::println!("INFO: Rusty Radio started");
+    if !constants::CERTIFICATE_VERIFICATION.is_verified() {
+        esp_println::println!(
+            "WARNING: The certificates of HTTPS servers are not verified and no credentials are sent to them (see CERTIFICATE_VERIFICATION)"
+        );
+    }
 
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
     let peripherals = esp_hal::init(config);
@@ -298,6 +307,9 @@ async fn main(spawner: Spawner) {
 
     spawner.spawn(play_music()).ok();
 
+    // Controlling the radio from a browser
+    spawner.spawn(web(wifi_hardware.sta_stack)).ok();
+
     // Showing on the panel LED when a station has been tuned in
     // TODO This is a temporary solution until the display is ready.
     //spawner.spawn(station_indicator(front_panel)).ok();
@@ -18,5 +18,7 @@ pub mod receive_radio_control_commands;
 
 pub mod station_indicator;
 
+pub mod web;
+
 // TEST
 pub mod test_button_board;
@@ -2,31 +2,30 @@ use crate::STATION_CHANGE_WATCH;
 
 use embassy_executor::Spawner;
 use embassy_net::{
-    dns::DnsSocket,
-    tcp::{
-        self,
-        client::{TcpClient, TcpClientState},
-        TcpSocket,
-    },
-    Stack,
-    // IpAddress
+    tcp::{self, TcpSocket},
+    IpAddress, Stack,
 };
-//use embedded_io_async::Write;
+use embedded_io_async::{Read, Write};
 
-// use core::net::Ipv4Addr;
+use core::net::Ipv4Addr;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
 use embassy_time::{Duration, Timer};
-// use embassy_net::tcp::client::{TcpClient, TcpClientState};
-// use embassy_net::Stack;
-use core::sync::atomic::{AtomicBool, Ordering};
-// use nourl::Url;
-use reqwless::{client::HttpClient, request::Method};
-use static_cell::StaticCell;
+use nourl::{Url, UrlScheme};
 
-//
-//use http::{Method, Request, RequestError, Response, ResponseStatusCode, MAX_URL_LEN};
-use stations::{Station, StationError, Stations};
+use http::{
+    connect_tunnel, read_headers, split_credentials, CacheValidators, ChunkedDecoder, Connection,
+    ConnectionError, Method, ProxyError, ReadHeadersError, Request, Response, ResponseStatusCode,
+    TlsError, TransferEncoding,
+};
+use stations::{LoadError, LoadWarnings, Station, StationError, Stations, StationsLoader};
 
-use crate::{front_panel::FrontPanel, task::tuner::tuner};
+use crate::{
+    constants::{CERTIFICATE_VERIFICATION, PROXY, STATIONS_REFRESH_INTERVAL},
+    front_panel::FrontPanel,
+    task::tuner::tuner,
+    tls_buffers::StationsTlsBuffers,
+    tls_rng::TlsRng,
+};
 
 pub const MAX_STATION_NAME_LEN: usize = 40;
 pub const MAX_STATION_URL_LEN: usize = 256;
@@ -34,98 +33,272 @@ pub const NUMBER_PRESETS: usize = 4;
 
 pub type RadioStation = Station<MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN>;
 pub type RadioStations = Stations<MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN, NUMBER_PRESETS>;
+pub type RadioStationsLoader =
+    StationsLoader<MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN, NUMBER_PRESETS>;
+
+// The stations from the last station list that was read, or None before the first has been
+pub static RADIO_STATIONS: Mutex<CriticalSectionRawMutex, Option<RadioStations>> = Mutex::new(None);
 
-static RADIO_STATIONS: StaticCell<RadioStations> = StaticCell::new();
-static RADIO_STATIONS_INITIALIZED: AtomicBool = AtomicBool::new(false);
+// Enough space to store all the HTTP header information
+const HEADER_SIZE: usize = 2048;
 
 /// Read the internet stations from the web.
-// Development note: This version uses reqwless. A  previous version used TCP sockets directly.
-// This can be found at https://gist.github.com/adoble/6ae04bff12d76949743be39f9222f06d
+///
+/// Once read, the list is checked for changes every `STATIONS_REFRESH_INTERVAL`. The request
+/// is conditional on the `ETag`/`Last-Modified` of the current list, so the list is only
+/// downloaded and parsed again if it has changed.
+// Development note: This version uses TCP sockets directly with the http crate. A previous
+// version used reqwless.
 #[embassy_executor::task]
 pub async fn radio_stations(spawner: Spawner, stack: Stack<'static>, stations_url: &'static str) {
-    let mut rx_buffer = [0; 16000];
-    let client_state = TcpClientState::<1, 1024, 1024>::new();
-    let tcp_client = TcpClient::new(stack, &client_state);
-    let dns_client = DnsSocket::new(stack);
+    // Identify the current station list, empty until one has been read
+    let mut validators = CacheValidators::default();
 
-    let mut http_client = HttpClient::new(&tcp_client, &dns_client);
+    // Empty unless the station list uses HTTPS
+    let tls_buffers = StationsTlsBuffers::stations();
 
-    // Get initial stations and spawn tuner
     loop {
-        // Only load the stations if they are not initialised
-        if !RADIO_STATIONS_INITIALIZED.load(Ordering::Acquire) {
-            if let Ok(mut request) = http_client.request(Method::GET, stations_url).await {
-                if let Ok(response) = request.send(&mut rx_buffer).await {
-                    if let Ok(body) = response.body().read_to_end().await {
-                        if let Ok(stations) = Stations::<
-                            MAX_STATION_NAME_LEN,
-                            MAX_STATION_URL_LEN,
-                            NUMBER_PRESETS,
-                        >::load(body)
-                        {
-                            let stations = RADIO_STATIONS.init(stations);
-                            RADIO_STATIONS_INITIALIZED.store(true, Ordering::Release);
-
-                            // Signal the initial station
-                            let station_change_sender = STATION_CHANGE_WATCH.sender();
-
-                            // 1. The last set station - TODO
-                            // 2. The first preset stations if set
-                            // 3. The first station in the station list
-
-                            let initial_station = stations
-                                .preset(0)
-                                .map(|s| s.1) // Get the preset station from the tuple
-                                .or_else(|| stations.get_station(0)); //.expect("No initial station found");
-
-                            // Send the inital station
-                            station_change_sender.send(initial_station);
-
-                            //spawner.must_spawn(tuner(stations, front_panel));
-                        }
+        match read_stations_file(stack, stations_url, &validators, tls_buffers).await {
+            Ok(StationsFile::NotModified) => {
+                // The current stations are still up to date
+            }
+            Ok(StationsFile::Modified(stations, warnings, new_validators)) => {
+                for warning in warnings.iter() {
+                    esp_println::println!(
+                        "WARNING: Skipped the station on line {} of the stations [field {}: {:?}]",
+                        warning.line,
+                        warning.field,
+                        warning.error
+                    );
+                }
+                let number_not_shown = warnings.number_skipped() - warnings.iter().count();
+                if number_not_shown > 0 {
+                    esp_println::println!("WARNING: Skipped {} more stations", number_not_shown);
+                }
+
+                if stations.number_stations() == 0 && !warnings.is_empty() {
+                    esp_println::println!("ERROR: None of the stations could be loaded");
+                } else {
+                    let mut radio_stations = RADIO_STATIONS.lock().await;
+
+                    if radio_stations.is_none() {
+                        // Signal the initial station
+                        let station_change_sender = STATION_CHANGE_WATCH.sender();
+
+                        // 1. The last set station - TODO
+                        // 2. The first preset stations if set
+                        // 3. The first station in the station list
+
+                        let initial_station = stations
+                            .preset(0)
+                            .map(|s| s.1) // Get the preset station from the tuple
+                            .or_else(|| stations.get_station(0)); //.expect("No initial station found");
+
+                        // Send the inital station
+                        station_change_sender.send(initial_station);
+
+                        //spawner.must_spawn(tuner(stations, front_panel));
                     }
+
+                    *radio_stations = Some(stations);
+                    // Only the validators of a list that could be loaded are kept
+                    validators = new_validators;
                 }
             }
+            Err(err) => {
+                esp_println::println!("ERROR: Cannot read the stations [{:?}]", err);
+            }
         }
 
-        Timer::after(Duration::from_secs(1)).await;
+        // Retry soon until there are stations, after that only check for changes occasionally
+        let wait = if RADIO_STATIONS.lock().await.is_some() {
+            STATIONS_REFRESH_INTERVAL
+        } else {
+            Duration::from_secs(1)
+        };
+        Timer::after(wait).await;
     }
 }
 
-// TODO This is a duplicate from stream. Maybe put it  http::response??
-/// Read the headers into the header buffer
-#[deprecated]
-async fn read_headers(
-    socket: &mut TcpSocket<'_>,
-    header_buffer: &mut [u8],
-    //) -> Result<Option<String<MAX_URL_LEN>>, embassy_net::tcp::Error> {
-) -> Result<(), RadioStationsError> {
-    let mut header_pos = 0;
-    let mut found_end = false;
-
-    while header_pos < header_buffer.len() && !found_end {
-        match socket
-            .read(&mut header_buffer[header_pos..header_pos + 1])
-            .await
-            .map_err(RadioStationsError::Tcp)?
-        {
-            0 => break,
-            n => {
-                header_pos += n;
-                if header_pos >= 4
-                    && header_buffer[header_pos - 4..header_pos] == [b'\r', b'\n', b'\r', b'\n']
-                {
-                    found_end = true;
-                }
-            }
-        }
+/// The result of reading the stations file.
+enum StationsFile {
+    /// The file has changed (or is read for the first time). Contains the stations, the
+    /// warnings for the stations that were skipped and the validators to use for the next
+    /// request.
+    Modified(RadioStations, LoadWarnings, CacheValidators),
+    /// The file has not changed since the validators were taken
+    NotModified,
+}
+
+/// Reads and loads the stations file, unless it has not changed since the validators were
+/// taken.
+async fn read_stations_file(
+    stack: Stack<'static>,
+    stations_url: &str,
+    validators: &CacheValidators,
+    tls_buffers: &mut StationsTlsBuffers,
+) -> Result<StationsFile, RadioStationsError> {
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 1024];
+
+    // A user name and password in the URL are sent in the Authorization header
+    let (stations_url, credentials) =
+        split_credentials(stations_url).map_err(|_| RadioStationsError::MalformedUrl)?;
+    let url = Url::parse(&stations_url).map_err(|_| RadioStationsError::MalformedUrl)?;
+    let host = url.host();
+
+    // With a proxy, the connection is made to the proxy instead of the server
+    let (connect_host, connect_port) = match PROXY {
+        Some(proxy) => (proxy.host, proxy.port),
+        None => (host, url.port_or_default()),
+    };
+
+    let remote_ip_addresses = stack
+        .dns_query(connect_host, embassy_net::dns::DnsQueryType::A)
+        .await
+        .map_err(|_| RadioStationsError::Dns)?;
+
+    let remote_endpoint = match remote_ip_addresses.first() {
+        Some(IpAddress::Ipv4(ipv4_addr)) => (Ipv4Addr::from(ipv4_addr.octets()), connect_port),
+        None => return Err(RadioStationsError::Dns),
+    };
+
+    let is_https = matches!(url.scheme(), UrlScheme::HTTPS);
+    let (tls_read_buffer, tls_write_buffer) = tls_buffers.split();
+    if is_https && tls_read_buffer.is_empty() {
+        return Err(RadioStationsError::NoTlsBuffers);
+    }
+    // Without verification, the credentials could go to anyone posing as the server
+    if is_https && credentials.is_some() && !CERTIFICATE_VERIFICATION.is_verified() {
+        return Err(RadioStationsError::UnverifiedCertificate);
     }
 
-    if !found_end {
-        Err(RadioStationsError::HeadersEndNotFound)
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(Duration::from_secs(15)));
+
+    socket
+        .connect(remote_endpoint)
+        .await
+        .map_err(|_| RadioStationsError::IpConnection)?;
+
+    let mut header_buffer = [0u8; HEADER_SIZE];
+
+    // The proxy has to pass on the encrypted connection, for HTTP it handles the request
+    if PROXY.is_some() && is_https {
+        connect_tunnel(&mut socket, host, url.port_or_default(), &mut header_buffer).await?;
+    }
+
+    let mut connection = if is_https {
+        Connection::tls(
+            &mut socket,
+            host,
+            tls_read_buffer,
+            tls_write_buffer,
+            CERTIFICATE_VERIFICATION,
+            TlsRng::new(),
+        )
+        .await
+        .map_err(RadioStationsError::Tls)?
+    } else {
+        Connection::plain(&mut socket)
+    };
+
+    // A proxy is sent the whole URL so that it knows where to pass the request on to
+    let request_target = if PROXY.is_some() && !is_https {
+        stations_url.as_str()
     } else {
-        Ok(())
+        url.path()
+    };
+
+    let mut request =
+        Request::new(Method::GET, request_target).map_err(|_| RadioStationsError::HttpRequest)?;
+    request
+        .host(host)
+        .map_err(|_| RadioStationsError::HttpRequest)?;
+    request
+        .header("Connection", "close")
+        .map_err(|_| RadioStationsError::HttpRequest)?;
+    if let Some(credentials) = &credentials {
+        request
+            .basic_auth(&credentials.user, &credentials.password)
+            .map_err(|_| RadioStationsError::HttpRequest)?;
     }
+    request
+        .conditional(validators)
+        .map_err(|_| RadioStationsError::HttpRequest)?;
+
+    request.write_to(&mut connection).await?;
+    connection.flush().await?;
+    let headers_read = read_headers(&mut connection, &mut header_buffer).await?;
+
+    let response = Response::new(headers_read.headers(&header_buffer))
+        .map_err(|_| RadioStationsError::HttpResponse)?;
+
+    if response.is_not_modified() {
+        drop(connection);
+        socket.close();
+        return Ok(StationsFile::NotModified);
+    }
+
+    let status_code = response.status_code();
+    if !matches!(status_code, ResponseStatusCode::Successful(_)) {
+        drop(connection);
+        socket.abort();
+        return Err(RadioStationsError::HttpStatus(status_code));
+    }
+
+    let new_validators = CacheValidators::from_response(&response);
+    let mut chunked_decoder = match response.transfer_encoding() {
+        TransferEncoding::Chunked => Some(ChunkedDecoder::new()),
+        TransferEncoding::Identity => None,
+    };
+
+    // The stations are loaded as the body is read, so the body does not have to fit in
+    // memory. A bad record is skipped, rather than leaving the radio without stations.
+    let mut loader = RadioStationsLoader::lenient();
+
+    // The start of the body has been read in with the headers
+    let body_start = headers_read.headers_len..headers_read.headers_len + headers_read.body_len;
+    load_body_part(
+        &mut loader,
+        chunked_decoder.as_mut(),
+        &mut header_buffer[body_start],
+    )?;
+
+    // Read to the end of the body, i.e. until the server closes the connection. The rest of
+    // the body is read into the header buffer, which is no longer needed.
+    loop {
+        match connection.read(&mut header_buffer).await? {
+            0 => break,
+            n => load_body_part(
+                &mut loader,
+                chunked_decoder.as_mut(),
+                &mut header_buffer[..n],
+            )?,
+        }
+    }
+    drop(connection);
+    socket.close();
+
+    let (stations, warnings) = loader.finish()?;
+    Ok(StationsFile::Modified(stations, warnings, new_validators))
+}
+
+/// Loads a part of the body read from the connection, removing the chunk sizes if the body
+/// is chunked.
+fn load_body_part(
+    loader: &mut RadioStationsLoader,
+    chunked_decoder: Option<&mut ChunkedDecoder>,
+    part: &mut [u8],
+) -> Result<(), RadioStationsError> {
+    let body_len = match chunked_decoder {
+        Some(decoder) => decoder
+            .decode(part)
+            .map_err(|_| RadioStationsError::HttpResponse)?,
+        None => part.len(),
+    };
+    loader.feed(&part[..body_len])?;
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -133,11 +306,56 @@ pub enum RadioStationsError {
     StationConstruction(StationError),
     Dns,
     Tcp(tcp::Error),
+    Tls(TlsError),
+    // The station list uses HTTPS, but STATIONS_URL does not, so it has no TLS buffers
+    NoTlsBuffers,
+    // Credentials are not sent over HTTPS unless the certificate is verified
+    UnverifiedCertificate,
     IpConnection,
     MalformedUrl,
     HttpRequest,
+    HttpResponse,
+    HttpStatus(ResponseStatusCode),
+    ProxyRefused(ResponseStatusCode),
     HeadersEndNotFound,
 }
+
+impl From<LoadError> for RadioStationsError {
+    fn from(error: LoadError) -> Self {
+        Self::StationConstruction(error.error)
+    }
+}
+
+impl From<ConnectionError<tcp::Error>> for RadioStationsError {
+    fn from(error: ConnectionError<tcp::Error>) -> Self {
+        match error {
+            ConnectionError::Io(error) => Self::Tcp(error),
+            ConnectionError::Tls(error) => Self::Tls(error),
+        }
+    }
+}
+
+impl From<ProxyError<tcp::Error>> for RadioStationsError {
+    fn from(error: ProxyError<tcp::Error>) -> Self {
+        match error {
+            ProxyError::Io(error) => Self::Tcp(error),
+            ProxyError::Request(_) => Self::HttpRequest,
+            ProxyError::Response(_) => Self::HttpResponse,
+            ProxyError::Refused(status_code) => Self::ProxyRefused(status_code),
+        }
+    }
+}
+
+impl From<ReadHeadersError<ConnectionError<tcp::Error>>> for RadioStationsError {
+    fn from(error: ReadHeadersError<ConnectionError<tcp::Error>>) -> Self {
+        match error {
+            ReadHeadersError::Io(error) => error.into(),
+            ReadHeadersError::UnexpectedEof | ReadHeadersError::BufferOverflow => {
+                Self::HeadersEndNotFound
+            }
+        }
+    }
+}
 // impl From<nourl::Error> for RadioStationsError {
 //     fn from(_error: nourl::Error) -> Self {
 //         Self::MalformedUrl
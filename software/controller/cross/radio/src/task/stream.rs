@@ -6,26 +6,39 @@ use crate::task::radio_stations::RadioStation;
 use embassy_net::{tcp::TcpSocket, IpAddress, Stack};
 #[cfg(feature = "stats")]
 use embassy_time::Instant;
-use embassy_time::{Duration, Timer};
+use embassy_time::{with_timeout, Duration, Timer};
 
-use embedded_io_async::Write;
+use embedded_io_async::{Read, Write};
 
 //use esp_println::dbg;
 
-use m3u::{M3UError, M3U};
-use static_assertions::const_assert;
+use m3u::{
+    Asx, ExtendedM3U, HlsItem, HlsPlaylist, M3UError, PlaylistEntry as _, PlaylistParser,
+    VariantSelection, Xspf,
+};
+use pls::{Pls, PlsError};
 
 use core::net::Ipv4Addr;
 
-use nourl::Url;
+use nourl::{Url, UrlScheme};
+
+use heapless::{Deque, String};
 
-use heapless::String;
+use crate::constants::{CERTIFICATE_VERIFICATION, MAX_HLS_BANDWIDTH, PROXY};
+use crate::tls_buffers::TlsBuffers;
+use crate::tls_rng::TlsRng;
 
 use crate::task::sync::{
-    StationChangeReceiver, AUDIO_BUFFER_SIZE, MUSIC_PIPE, START_PLAYING, STATION_CHANGE_WATCH,
+    StationChangeReceiver, StreamMetadata, AUDIO_BUFFER_SIZE, MAX_STREAM_METADATA_LEN, MUSIC_PIPE,
+    START_PLAYING, STATION_CHANGE_WATCH, STREAM_METADATA_WATCH,
 };
 
-use http::{Method, Request, Response, ResponseStatusCode, MAX_URL_LEN};
+use http::{
+    connect_tunnel, join_url, read_headers, split_credentials, ChunkedDecoder, Connection,
+    ConnectionError, ContentType, HeadersRead, IcyDemux, Method, Prepend, ProxyError,
+    ReadHeadersError, RedactedUrl, Request, Response, ResponseStatusCode, TlsError,
+    TransferEncoding, TsDemux, UrlError, MAX_URL_LEN, SNIFF_LEN,
+};
 
 // Empirically determined value. This value  has to be used in
 // conjunction with the wifi tuning parameters in .cargo/config.toml
@@ -35,20 +48,50 @@ const TCP_BUFFER_SIZE: usize = 6000;
 // Enough space to store all the HTTP header information
 const HEADER_SIZE: usize = 2048;
 
+// The number of redirects (including playlists) followed before giving up on a station
+const MAX_REDIRECTS: usize = 5;
+
+// The number of times an audio file is resumed without any more of it being read before
+// giving up on it
+const MAX_RESUMES: usize = 3;
+
+// The number of entries of a playlist that are kept, to fall back on if a stream does not work
+const MAX_PLAYLIST_ENTRIES: usize = 4;
+
+// The number of segments of an HLS stream that are queued to be played. A live stream is
+// started this many segments before the end of its playlist (see RFC 8216, section 6.3.3).
+const MAX_HLS_SEGMENTS: usize = 3;
+
+// The target duration (in seconds) of the segments of an HLS stream, if the playlist does not
+// give it
+const DEFAULT_HLS_TARGET_DURATION: u32 = 10;
+
+// An entry of the playlist of a station
+struct PlaylistEntry {
+    url: String<MAX_URL_LEN>,
+    // The title of the entry, shown until the station sends its own
+    metadata: StreamMetadata,
+}
+
+impl PlaylistEntry {
+    fn new(url: String<MAX_URL_LEN>, title: Option<&str>) -> PlaylistEntry {
+        PlaylistEntry {
+            url,
+            metadata: title.map(StreamMetadata::with_title).unwrap_or_default(),
+        }
+    }
+}
+
+// The entries of the playlist of the current station that have not been tried yet
+type Playlist = Deque<PlaylistEntry, MAX_PLAYLIST_ENTRIES>;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum StreamingState {
     FillingPipe,
     Playing,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum ContentType {
-    Audio,
-    SimpleM3U(String<MAX_URL_LEN>),
-    ExtendedM3U(String<MAX_URL_LEN>),
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 enum StreamError {
     // Recoverabe errors. These are problems with the station.
     // The user is given is ability to change the station if it
@@ -58,36 +101,116 @@ enum StreamError {
     ConnectionError(embassy_net::tcp::ConnectError),
     ConnectionPrematurelyClosed,
     Tcp(embassy_net::tcp::Error),
+    Tls(TlsError),
 
     StationUrlTooLong,
     MalformedUrl,
 
     HttpRequest(http::RequestError),
     HttpResponse(http::ResponseError),
+    ChunkedEncoding(http::ChunkedError),
     HeadersEndNotFound,
-    UnableToReadContentType(embassy_net::tcp::Error),
     InvalidHttpCode(ResponseStatusCode),
+    InvalidContentRange,
+    ProxyRefused(ResponseStatusCode),
+    // Credentials are not sent over HTTPS unless the certificate is verified
+    UnverifiedCertificate,
+    EmptyBody,
     InvalidContent,
-    TooManyBytesReadIn(usize),
+    UnsupportedContent(ContentType),
 
     NoRedirectionLocationFound,
     RedirectionUrlTooLong,
+    RedirectLoop,
 
-    EmptyM3U,
     InvalidM3U(M3UError),
-    M3uUrlTooLong,
-    UrlNotFoundInM3U,
+    InvalidPls(PlsError),
+    EmptyPlaylist,
+    // All the segments of an HLS stream have been played
+    EndOfStream,
     // Non recoverable errors. These are due to program errors and
     // should not happen
     //StringAllocationTooSmall,
 }
 
+impl StreamError {
+    // The connection to the server has been lost, though the station itself could be fine
+    fn is_connection_lost(&self) -> bool {
+        matches!(
+            self,
+            StreamError::ConnectionPrematurelyClosed | StreamError::Tcp(_) | StreamError::Tls(_)
+        )
+    }
+}
+
+// So that a body can be read through the readers of the http crate
+impl embedded_io_async::Error for StreamError {
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        embedded_io_async::ErrorKind::Other
+    }
+}
+
 impl From<http::ResponseError> for StreamError {
     fn from(error: http::ResponseError) -> Self {
         StreamError::HttpResponse(error)
     }
 }
 
+impl<E> From<ReadHeadersError<E>> for StreamError
+where
+    StreamError: From<E>,
+{
+    fn from(error: ReadHeadersError<E>) -> Self {
+        match error {
+            ReadHeadersError::Io(error) => error.into(),
+            ReadHeadersError::UnexpectedEof => StreamError::ConnectionPrematurelyClosed,
+            ReadHeadersError::BufferOverflow => StreamError::HeadersEndNotFound,
+        }
+    }
+}
+
+impl From<ConnectionError<embassy_net::tcp::Error>> for StreamError {
+    fn from(error: ConnectionError<embassy_net::tcp::Error>) -> Self {
+        match error {
+            ConnectionError::Io(error) => StreamError::Tcp(error),
+            ConnectionError::Tls(error) => StreamError::Tls(error),
+        }
+    }
+}
+
+impl From<ProxyError<embassy_net::tcp::Error>> for StreamError {
+    fn from(error: ProxyError<embassy_net::tcp::Error>) -> Self {
+        match error {
+            ProxyError::Io(error) => StreamError::Tcp(error),
+            ProxyError::Request(error) => StreamError::HttpRequest(error),
+            ProxyError::Response(error) => StreamError::HttpResponse(error),
+            ProxyError::Refused(status_code) => StreamError::ProxyRefused(status_code),
+        }
+    }
+}
+
+impl From<TlsError> for StreamError {
+    fn from(error: TlsError) -> Self {
+        StreamError::Tls(error)
+    }
+}
+
+impl From<UrlError> for StreamError {
+    fn from(error: UrlError) -> Self {
+        match error {
+            UrlError::InvalidBase => StreamError::MalformedUrl,
+            UrlError::TooLong => StreamError::RedirectionUrlTooLong,
+            UrlError::InvalidCredentials => StreamError::MalformedUrl,
+        }
+    }
+}
+
+impl From<http::ChunkedError> for StreamError {
+    fn from(error: http::ChunkedError) -> Self {
+        StreamError::ChunkedEncoding(error)
+    }
+}
+
 impl From<http::RequestError> for StreamError {
     fn from(error: http::RequestError) -> Self {
         StreamError::HttpRequest(error)
@@ -120,16 +243,17 @@ impl From<M3UError> for StreamError {
         Self::InvalidM3U(error)
     }
 }
+impl From<PlsError> for StreamError {
+    fn from(error: PlsError) -> Self {
+        Self::InvalidPls(error)
+    }
+}
 impl From<nourl::Error> for StreamError {
     fn from(_error: nourl::Error) -> Self {
         Self::MalformedUrl
     }
 }
 
-// This is the number of characters that have to be read in the determine the content type
-// The assumption is that each content type contains at least this number of characters.
-const TOKEN_LEN: usize = 7;
-
 /// This task is the core of the rusty-radio project.
 /// It accesses an internet radio station and sends the data to MUSIC_CHANNEL.
 ///
@@ -143,12 +267,37 @@ pub async fn stream(stack: Stack<'static>) {
 
     let station_change_sender = STATION_CHANGE_WATCH.sender();
 
+    let mut playlist = Playlist::new();
+
+    // Only used for HTTPS stations
+    let tls_buffers = TlsBuffers::stream();
+
     loop {
-        match stream_station(stack, &mut station_change_receiver).await {
+        match stream_station(
+            stack,
+            &mut station_change_receiver,
+            &mut playlist,
+            tls_buffers,
+        )
+        .await
+        {
             Ok(_) => (), //  stream_station will only return if there is an error
 
             Err(e) => {
                 esp_println::println!("ERROR: {:?}", e);
+
+                // Try the next entry of the station's playlist, unless the station has changed
+                if !playlist.is_empty() {
+                    match station_change_receiver.try_changed() {
+                        None => esp_println::println!("Trying the next entry of the playlist"),
+                        Some(station) => {
+                            playlist.clear();
+                            station_change_sender.send(station);
+                        }
+                    }
+                    continue;
+                }
+
                 // Wait until the station changes
 
                 let station = station_change_receiver.changed().await;
@@ -167,6 +316,8 @@ pub async fn stream(stack: Stack<'static>) {
 async fn stream_station(
     stack: Stack<'static>,
     station_change_receiver: &mut StationChangeReceiver,
+    playlist: &mut Playlist,
+    tls_buffers: &mut TlsBuffers,
 ) -> Result<(), StreamError> {
     let mut rx_buffer = [0; TCP_BUFFER_SIZE];
     let mut tx_buffer = [0; TCP_BUFFER_SIZE];
@@ -202,27 +353,39 @@ async fn stream_station(
     // TODO assuming that this is always Some(station)
     let initial_station = station_change_receiver.get().await.unwrap();
 
-    let initial_url = initial_station.url();
+    // The title to show until the station sends its own
+    let mut metadata = StreamMetadata::default();
+
     let mut url_str = String::<MAX_URL_LEN>::new();
-    url_str
-        .push_str(&initial_url)
-        .map_err(|_| StreamError::StationUrlTooLong)?;
+    match playlist.pop_front() {
+        // The last entry of the station's playlist did not work, so try the next one
+        Some(entry) => {
+            url_str = entry.url;
+            metadata = entry.metadata;
+        }
+        None => url_str
+            .push_str(&initial_station.url())
+            .map_err(|_| StreamError::StationUrlTooLong)?,
+    }
+
+    // The number of redirects followed for the current station
+    let mut redirects = 0;
+
+    // Where to continue an audio file (rather than a live stream) after the connection
+    // to it was lost, and how often this has been tried
+    let mut resume_position: u64 = 0;
+    let mut resumes = 0;
 
     'redirect: loop {
-        let url = if !url_str.is_empty() {
-            Url::parse(&url_str)?
-        } else {
+        if url_str.is_empty() {
             //The URL is empty meaning no station selected so wait until one has been
             let new_station = station_change_receiver.get().await;
             match new_station {
                 Some(station) => {
                     // A new station has been selected
-                    url_str.clear();
                     url_str
                         .push_str(&station.url())
                         .map_err(|_| StreamError::StationUrlTooLong)?;
-
-                    Url::parse(&url_str)?
                 }
                 None => {
                     // No station selected so wait a bit and then check again
@@ -230,94 +393,168 @@ async fn stream_station(
                     continue 'redirect;
                 }
             }
-        };
-
-        let host = url.host();
-        let port = url.port_or_default();
-        let path = url.path();
-
-        let remote_ip_addresses = stack
-            .dns_query(host, embassy_net::dns::DnsQueryType::A)
-            .await?;
-
-        let remote_ip_addr = if !remote_ip_addresses.is_empty() {
-            remote_ip_addresses[0]
-        } else {
-            return Err(StreamError::IpAddressNotFound);
-        };
+        }
 
-        let remote_endpoint = match remote_ip_addr {
-            IpAddress::Ipv4(ipv4_addr) => {
-                let octets = ipv4_addr.octets();
-                (Ipv4Addr::from(octets), port)
-            }
-        };
+        let mut header_buffer = [0u8; HEADER_SIZE];
 
-        // Connect to the socket using the IP address from the DNS
-        socket.connect(remote_endpoint).await?;
+        let (mut connection, headers_read) = send_request(
+            stack,
+            &mut socket,
+            &url_str,
+            tls_buffers,
+            &mut header_buffer,
+            resume_position,
+        )
+        .await?;
 
-        // Request the data
-        let mut request = Request::new(Method::GET, path)?;
-        request.host(host)?;
+        let response = Response::new(headers_read.headers(&header_buffer))?;
 
-        // Set the user agent. Note this does not have to be a spoof of
-        // a "normal" browser agent such as
-        // "Mozilla/5.0 (X11; Linux x86_64; rv:138.0) Gecko/20100101 Firefox/138.0"
-        // Note that this is based on the data in cross/app/Cargo.toml
-        let user_agent = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
-        request.header("User-Agent", user_agent)?;
+        match response.status_code() {
+            ResponseStatusCode::Successful(_) => (),
 
-        request.header("Connection", "keep-alive")?;
+            ResponseStatusCode::Redirection(_) => {
+                let location = response
+                    .location()
+                    .ok_or(StreamError::NoRedirectionLocationFound)?;
 
-        socket.write_all(request.to_string().as_bytes()).await?;
-        socket.flush().await?;
+                // The location can be relative to the URL just requested
+                let next_url = join_url(&url_str, location)?;
 
-        let mut header_buffer = [0u8; HEADER_SIZE];
+                redirects += 1;
+                if redirects > MAX_REDIRECTS || next_url == url_str {
+                    return Err(StreamError::RedirectLoop);
+                }
 
-        read_headers(&mut socket, &mut header_buffer).await?;
+                esp_println::println!("Redirected to {}", RedactedUrl(&next_url));
 
-        let response = Response::new(&header_buffer)?;
+                url_str = next_url;
+                drop(connection);
+                socket.abort();
+                socket.flush().await?;
+                continue 'redirect;
+            }
 
-        match response.status_code() {
-            ResponseStatusCode::Successful(_) => {
-                let content_type = determine_content_type(&mut socket).await?;
-                match content_type {
-                    ContentType::Audio => (),
-                    ContentType::SimpleM3U(location) => {
-                        url_str.clear();
-                        url_str
-                            .push_str(&location)
-                            .map_err(|_| StreamError::M3uUrlTooLong)?;
+            other => return Err(StreamError::InvalidHttpCode(other)),
+        };
 
-                        socket.abort();
-                        socket.flush().await?;
-                        continue 'redirect;
+        // The length of an audio file that can be resumed if the connection is lost.
+        // Live streams have no length.
+        let file_length = if response.is_partial_content() {
+            let content_range = response
+                .content_range()
+                .filter(|range| range.start == resume_position)
+                .ok_or(StreamError::InvalidContentRange)?;
+            content_range.complete_length
+        } else {
+            // Either not resuming or the server has ignored the range and sends the whole file
+            resume_position = 0;
+            response
+                .content_length()
+                .filter(|_| response.accepts_ranges())
+                .map(|length| length as u64)
+        }
+        .filter(|_| response.icy_metaint().is_none());
+
+        let mut body = BodyReader::new(
+            &mut connection,
+            &response,
+            headers_read.body(&header_buffer),
+            resume_position,
+        );
+
+        // Read in the start of the body. This is used to determine the content type if the
+        // Content-Type header is missing or too generic, and is then handled as part of the body.
+        let mut body_start = [0u8; SNIFF_LEN];
+        let body_start_len = body.read_up_to(&mut body_start).await?;
+        if body_start_len == 0 {
+            return Err(StreamError::EmptyBody);
+        }
+        let body_start = &body_start[..body_start_len];
+
+        let content_type = ContentType::detect(response.content_type(), body_start);
+        match content_type {
+            // If the content type cannot be determined, assume that it is audio and leave
+            // it to the codec.
+            ContentType::Audio(_) | ContentType::Unknown => (),
+            ContentType::M3U | ContentType::Pls | ContentType::Asx | ContentType::Xspf => {
+                let entry = match content_type {
+                    ContentType::Pls => {
+                        let mut pls = Pls::<MAX_URL_LEN>::new();
+                        read_playlist(&mut body, body_start, &url_str, &mut pls, playlist).await?
                     }
-                    ContentType::ExtendedM3U(location) => {
-                        url_str.clear();
-                        url_str
-                            .push_str(&location)
-                            .map_err(|_| StreamError::M3uUrlTooLong)?;
-
-                        socket.abort();
-                        socket.flush().await?;
-                        continue 'redirect;
+                    ContentType::Asx => {
+                        let mut asx = Asx::<MAX_URL_LEN>::new();
+                        read_playlist(&mut body, body_start, &url_str, &mut asx, playlist).await?
+                    }
+                    ContentType::Xspf => {
+                        let mut xspf = Xspf::<MAX_URL_LEN>::new();
+                        read_playlist(&mut body, body_start, &url_str, &mut xspf, playlist).await?
                     }
+                    _ => {
+                        let mut m3u = ExtendedM3U::<MAX_URL_LEN>::new();
+                        read_playlist(&mut body, body_start, &url_str, &mut m3u, playlist).await?
+                    }
+                };
+                url_str = entry.url;
+                metadata = entry.metadata;
+
+                // A playlist can refer to another playlist
+                redirects += 1;
+                if redirects > MAX_REDIRECTS {
+                    return Err(StreamError::RedirectLoop);
                 }
-            }
 
-            ResponseStatusCode::Redirection(_) => {
-                url_str = response
-                    .location
-                    .ok_or(StreamError::NoRedirectionLocationFound)?;
+                drop(connection);
                 socket.abort();
                 socket.flush().await?;
                 continue 'redirect;
             }
+            ContentType::Hls => {
+                let mut hls = HlsStream::new(url_str.clone());
+                let variant_url = hls.read_playlist(&mut body, body_start).await?;
 
-            other => return Err(StreamError::InvalidHttpCode(other)),
+                drop(connection);
+                socket.abort();
+                socket.flush().await?;
+
+                // A master playlist refers to the media playlist of the selected variant
+                if let Some(variant_url) = variant_url {
+                    url_str = variant_url;
+                    redirects += 1;
+                    if redirects > MAX_REDIRECTS {
+                        return Err(StreamError::RedirectLoop);
+                    }
+                    continue 'redirect;
+                }
+
+                // Stream the segments until a new station has been selected by the tuner
+                let new_station = stream_hls(
+                    stack,
+                    &mut socket,
+                    tls_buffers,
+                    &mut hls,
+                    &mut body_buffer,
+                    &metadata,
+                    station_change_receiver,
+                )
+                .await?;
+
+                set_station_url(&mut url_str, new_station)?;
+                playlist.clear();
+                metadata = StreamMetadata::default();
+                redirects = 0;
+                continue 'redirect;
+            }
+            // Normally an error page
+            ContentType::Html => return Err(StreamError::InvalidContent),
+            other => return Err(StreamError::UnsupportedContent(other)),
         }
 
+        // If the station sends metadata then this has to be removed from the audio
+        let mut icy_demux = response
+            .icy_metaint()
+            .map(IcyDemux::<MAX_STREAM_METADATA_LEN>::new);
+
         // // Stream the audio until a new station has been selected by the tuner
         // let new_station =
         //     stream_audio(&mut socket, &mut body_buffer, station_change_receiver).await?;
@@ -329,22 +566,54 @@ async fn stream_station(
         // socket.flush().await?;
 
         // Stream the audio until a new station has been selected by the tuner
-        let new_station =
-            stream_audio(&mut socket, &mut body_buffer, station_change_receiver).await?;
-
-        match new_station {
-            Some(station) => {
-                // A new station has been selected
-                url_str.clear();
-                url_str
-                    .push_str(&station.url())
-                    .map_err(|_| StreamError::RedirectionUrlTooLong)?;
-            }
-            None => {
-                // No station has been selected just clear the url
-                url_str.clear();
-            }
-        }
+        let result = stream_audio(
+            &mut body,
+            &mut body_buffer,
+            body_start,
+            &metadata,
+            icy_demux.as_mut(),
+            station_change_receiver,
+        )
+        .await;
+        let position = body.position();
+
+        let new_station = match result {
+            Ok(new_station) => new_station,
+            // Continue a file where it left off rather than playing it again from the start
+            Err(error) if error.is_connection_lost() => match file_length {
+                Some(file_length) if position < file_length => {
+                    if position > resume_position {
+                        resumes = 0;
+                    }
+                    resumes += 1;
+                    if resumes > MAX_RESUMES {
+                        return Err(error);
+                    }
+
+                    esp_println::println!(
+                        "Connection lost [{:?}], resuming at {}",
+                        error,
+                        position
+                    );
+                    resume_position = position;
+                    drop(connection);
+                    socket.abort();
+                    socket.flush().await?;
+                    continue 'redirect;
+                }
+                _ => return Err(error),
+            },
+            Err(error) => return Err(error),
+        };
+
+        set_station_url(&mut url_str, new_station)?;
+        // The playlist was for the previous station
+        playlist.clear();
+        metadata = StreamMetadata::default();
+        redirects = 0;
+        resume_position = 0;
+        resumes = 0;
+        drop(connection);
         // Close the socket properly. This happens if a new station has been selected AND also if no station
         // selected, so no music plays.
         socket.abort();
@@ -352,46 +621,264 @@ async fn stream_station(
     }
 }
 
-/// Read the headers into the header buffer
-async fn read_headers(
-    socket: &mut TcpSocket<'_>,
+// Connects to the server of the URL (or to the proxy) and requests the URL. Returns the
+// connection with the headers of the response read into the header buffer.
+// If the resume position is not 0, the rest of the resource from there is requested.
+async fn send_request<'c, 's>(
+    stack: Stack<'static>,
+    socket: &'c mut TcpSocket<'s>,
+    url_str: &str,
+    tls_buffers: &'c mut TlsBuffers,
     header_buffer: &mut [u8],
-    //) -> Result<Option<String<MAX_URL_LEN>>, embassy_net::tcp::Error> {
+    resume_position: u64,
+) -> Result<(Connection<'c, &'c mut TcpSocket<'s>>, HeadersRead), StreamError> {
+    // A user name and password in the URL are sent in the Authorization header.
+    // The URL keeps them, so that a redirect relative to it keeps them as well.
+    let (request_url, credentials) = split_credentials(url_str)?;
+    let url = Url::parse(&request_url)?;
+
+    let host = url.host();
+    let port = url.port_or_default();
+    let path = url.path();
+
+    // With a proxy, the connection is made to the proxy instead of the station
+    let (connect_host, connect_port) = match PROXY {
+        Some(proxy) => (proxy.host, proxy.port),
+        None => (host, port),
+    };
+
+    let remote_ip_addresses = stack
+        .dns_query(connect_host, embassy_net::dns::DnsQueryType::A)
+        .await?;
+
+    let remote_ip_addr = if !remote_ip_addresses.is_empty() {
+        remote_ip_addresses[0]
+    } else {
+        return Err(StreamError::IpAddressNotFound);
+    };
+
+    let remote_endpoint = match remote_ip_addr {
+        IpAddress::Ipv4(ipv4_addr) => {
+            let octets = ipv4_addr.octets();
+            (Ipv4Addr::from(octets), connect_port)
+        }
+    };
+
+    // Without verification, the credentials could go to anyone posing as the server
+    let is_https = matches!(url.scheme(), UrlScheme::HTTPS);
+    if is_https && credentials.is_some() && !CERTIFICATE_VERIFICATION.is_verified() {
+        return Err(StreamError::UnverifiedCertificate);
+    }
+
+    // Connect to the socket using the IP address from the DNS
+    socket.connect(remote_endpoint).await?;
+
+    // The proxy has to pass on the encrypted connection, for HTTP it handles the request
+    if PROXY.is_some() && is_https {
+        connect_tunnel(&mut *socket, host, port, header_buffer).await?;
+    }
+
+    // Stations using HTTPS need an encrypted connection
+    let mut connection = if is_https {
+        let (tls_read_buffer, tls_write_buffer) = tls_buffers.split();
+        Connection::tls(
+            socket,
+            host,
+            tls_read_buffer,
+            tls_write_buffer,
+            CERTIFICATE_VERIFICATION,
+            TlsRng::new(),
+        )
+        .await?
+    } else {
+        Connection::plain(socket)
+    };
+
+    // A proxy is sent the whole URL so that it knows where to pass the request on to
+    let request_target = if PROXY.is_some() && !is_https {
+        request_url.as_str()
+    } else {
+        path
+    };
+
+    // Request the data
+    let mut request = Request::new(Method::GET, request_target)?;
+    request.host(host)?;
+
+    // Set the user agent. Note this does not have to be a spoof of
+    // a "normal" browser agent such as
+    // "Mozilla/5.0 (X11; Linux x86_64; rv:138.0) Gecko/20100101 Firefox/138.0"
+    // Note that this is based on the data in cross/app/Cargo.toml
+    let user_agent = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+    request.header("User-Agent", user_agent)?;
+
+    request.header("Connection", "keep-alive")?;
+
+    // Ask for the track titles. These are sent within the audio stream and are removed
+    // in stream_audio.
+    request.header("Icy-MetaData", "1")?;
+
+    if let Some(credentials) = &credentials {
+        request.basic_auth(&credentials.user, &credentials.password)?;
+    }
+
+    if resume_position > 0 {
+        request.range(resume_position, None)?;
+    }
+
+    request.write_to(&mut connection).await?;
+    connection.flush().await?;
+
+    let headers_read = read_headers(&mut connection, header_buffer).await?;
+
+    Ok((connection, headers_read))
+}
+
+// Sets the URL to that of the new station, or clears it if no station has been selected
+fn set_station_url(
+    url_str: &mut String<MAX_URL_LEN>,
+    new_station: Option<RadioStation>,
 ) -> Result<(), StreamError> {
-    let mut header_pos = 0;
-    let mut found_end = false;
-
-    while header_pos < header_buffer.len() && !found_end {
-        match socket
-            .read(&mut header_buffer[header_pos..header_pos + 1])
-            .await
-            .map_err(StreamError::Tcp)?
-        {
-            0 => break,
-            n => {
-                header_pos += n;
-                if header_pos >= 4
-                    && header_buffer[header_pos - 4..header_pos] == [b'\r', b'\n', b'\r', b'\n']
-                {
-                    found_end = true;
+    url_str.clear();
+    if let Some(station) = new_station {
+        url_str
+            .push_str(&station.url())
+            .map_err(|_| StreamError::RedirectionUrlTooLong)?;
+    }
+    Ok(())
+}
+
+// Reads the body of a response, removing the chunk sizes if it is chunked.
+// The start of the body that was read in with the headers is returned first.
+struct BodyReader<'s, R> {
+    reader: &'s mut R,
+    chunked_decoder: Option<ChunkedDecoder>,
+    pending: &'s [u8],
+    // The position in the resource of the next byte of the body
+    position: u64,
+    // The position of the end of the body, if the response gives its length. Otherwise the
+    // body ends when the connection is closed.
+    end: Option<u64>,
+}
+
+impl<'s, R: Read> BodyReader<'s, R>
+where
+    StreamError: From<R::Error>,
+{
+    // The body starts at the position in the resource, which is not 0 for a range request
+    fn new(reader: &'s mut R, response: &Response, pending: &'s [u8], position: u64) -> Self {
+        let (chunked_decoder, end) = match response.transfer_encoding() {
+            TransferEncoding::Chunked => (Some(ChunkedDecoder::new()), None),
+            TransferEncoding::Identity => (
+                None,
+                response
+                    .content_length()
+                    .map(|length| position + length as u64),
+            ),
+        };
+
+        BodyReader {
+            reader,
+            chunked_decoder,
+            pending,
+            position,
+            end,
+        }
+    }
+
+    fn position(&self) -> u64 {
+        self.position
+    }
+
+    // Reads the next part of the body into the buffer. Returns 0 at the end of the body.
+    async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, StreamError> {
+        loop {
+            if let Some(decoder) = self.chunked_decoder.as_ref() {
+                if decoder.is_done() {
+                    return Ok(0);
+                }
+            }
+
+            // Do not read past the end of the body, as the connection is kept alive
+            let buffer = match self.end {
+                Some(end) if self.position >= end => return Ok(0),
+                Some(end) => {
+                    let len = (end - self.position).min(buffer.len() as u64) as usize;
+                    &mut buffer[..len]
+                }
+                None => &mut *buffer,
+            };
+
+            let n = if !self.pending.is_empty() {
+                let n = self.pending.len().min(buffer.len());
+                buffer[..n].copy_from_slice(&self.pending[..n]);
+                self.pending = &self.pending[n..];
+                n
+            } else {
+                self.reader.read(buffer).await?
+            };
+            if n == 0 {
+                return Ok(0);
+            }
+
+            match self.chunked_decoder.as_mut() {
+                Some(decoder) => {
+                    let body_len = decoder.decode(&mut buffer[..n])?;
+                    // If only chunk sizes have been read in, read again
+                    if body_len > 0 {
+                        self.position += body_len as u64;
+                        return Ok(body_len);
+                    }
+                }
+                None => {
+                    self.position += n as u64;
+                    return Ok(n);
                 }
             }
         }
     }
 
-    if !found_end {
-        Err(StreamError::HeadersEndNotFound)
-    } else {
-        Ok(())
+    // Reads until the buffer is full or the end of the body is reached
+    async fn read_up_to(&mut self, buffer: &mut [u8]) -> Result<usize, StreamError> {
+        let mut len = 0;
+        while len < buffer.len() {
+            match self.read(&mut buffer[len..]).await? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl<R: Read> embedded_io_async::ErrorType for BodyReader<'_, R> {
+    type Error = StreamError;
+}
+
+impl<R: Read> Read for BodyReader<'_, R>
+where
+    StreamError: From<R::Error>,
+{
+    async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, StreamError> {
+        BodyReader::read(self, buffer).await
     }
 }
 
 // Handle streaming of body, i.e. the mp3 data.
-async fn stream_audio(
-    socket: &mut TcpSocket<'_>,
+// The initial audio is the start of the body that has already been read in.
+// The metadata, e.g. the title of the playlist entry, is shown until the station sends its own.
+// If an ICY demultiplexer is given, the metadata is removed from the stream and sent to STREAM_METADATA_WATCH.
+async fn stream_audio<R: Read>(
+    body: &mut BodyReader<'_, R>,
     audio_buffer: &mut [u8],
+    initial_audio: &[u8],
+    metadata: &StreamMetadata,
+    mut icy_demux: Option<&mut IcyDemux<MAX_STREAM_METADATA_LEN>>,
     station_change_receiver: &mut StationChangeReceiver,
-) -> Result<Option<RadioStation>, StreamError> {
+) -> Result<Option<RadioStation>, StreamError>
+where
+    StreamError: From<R::Error>,
+{
     // let mut total_bytes = 0u32;
     // let mut last_stats = Instant::now();
     let mut read_state = StreamingState::FillingPipe;
@@ -402,11 +889,20 @@ async fn stream_audio(
     #[cfg(feature = "stats")]
     let (mut total_bytes, mut last_stats) = (0u32, Instant::now());
 
+    // Any title from the previous station is no longer valid
+    let metadata_sender = STREAM_METADATA_WATCH.sender();
+    metadata_sender.send(metadata.clone());
+
+    // Start with the audio that has already been read in, which can be longer than the buffer
+    let mut body = Prepend::new(initial_audio, body);
+
     loop {
         #[cfg(feature = "stats")]
         let read_start = Instant::now();
 
-        match socket.read(audio_buffer).await {
+        let read_result = body.read(audio_buffer).await;
+
+        match read_result {
             Ok(0) => {
                 return Err(StreamError::ConnectionPrematurelyClosed);
             }
@@ -419,8 +915,19 @@ async fn stream_audio(
                     (read_time, write_start)
                 };
 
+                let audio_len = match icy_demux.as_mut() {
+                    Some(demux) => {
+                        let (audio_len, metadata) = demux.demux(&mut audio_buffer[..n]);
+                        if let Some(metadata) = metadata {
+                            metadata_sender.send(metadata);
+                        }
+                        audio_len
+                    }
+                    None => n,
+                };
+
                 // Write immediately without trying to read more
-                MUSIC_PIPE.write_all(&audio_buffer[..n]).await;
+                MUSIC_PIPE.write_all(&audio_buffer[..audio_len]).await;
 
                 if read_state == StreamingState::FillingPipe && MUSIC_PIPE.len() >= initial_fill_len
                 {
@@ -446,10 +953,11 @@ async fn stream_audio(
                     last_stats = Instant::now();
                 }
             }
-            Err(err) => {
+            Err(StreamError::Tcp(err)) => {
                 esp_println::println!("ERROR: Cannot read from socket [{:?}]", err);
                 Timer::after(Duration::from_millis(10)).await;
             }
+            Err(err) => return Err(err),
         }
 
         if let Some(new_station) = station_change_receiver.try_changed() {
@@ -458,104 +966,395 @@ async fn stream_audio(
     }
 }
 
-async fn determine_content_type(socket: &mut TcpSocket<'_>) -> Result<ContentType, StreamError> {
-    let mut token_buffer = [0u8; TOKEN_LEN];
-
-    // Only readinng in one byte at a time for maximum control when it comes to errors
-    let mut buf = [0u8; 1];
+// An HLS stream: its media playlist and the segments that are queued to be played
+struct HlsStream {
+    playlist_url: String<MAX_URL_LEN>,
+    // The URLs of the segments
+    segments: Deque<String<MAX_URL_LEN>, MAX_HLS_SEGMENTS>,
+    // The media sequence number of the next segment to queue, once the playlist has been read
+    next_sequence: Option<u64>,
+    // The maximum duration of a segment in seconds
+    target_duration: u32,
+    // No more segments will be added to the playlist
+    is_end: bool,
+}
 
-    let mut pos: usize = 0;
+impl HlsStream {
+    fn new(playlist_url: String<MAX_URL_LEN>) -> HlsStream {
+        HlsStream {
+            playlist_url,
+            segments: Deque::new(),
+            next_sequence: None,
+            target_duration: DEFAULT_HLS_TARGET_DURATION,
+            is_end: false,
+        }
+    }
 
-    // Read in the token at the start of the content
-    while pos < TOKEN_LEN {
-        match socket.read(&mut buf).await {
-            Ok(0) => {
-                return Err(StreamError::EmptyM3U);
+    // Reads the playlist, queuing the segments that have not been queued yet.
+    // For a master playlist, the URL of the media playlist of the selected variant is returned.
+    // The start of the body has already been read in.
+    async fn read_playlist<R: Read>(
+        &mut self,
+        body: &mut BodyReader<'_, R>,
+        body_start: &[u8],
+    ) -> Result<Option<String<MAX_URL_LEN>>, StreamError>
+    where
+        StreamError: From<R::Error>,
+    {
+        let mut parser = HlsPlaylist::<MAX_URL_LEN>::new();
+        let mut selection = VariantSelection::new(MAX_HLS_BANDWIDTH);
+        let is_first_read = self.next_sequence.is_none();
+
+        let mut buffer = [0u8; 64];
+        let mut data = body_start;
+
+        loop {
+            for &b in data {
+                let result = parser.parse(b);
+                // A live stream is started near the end of the playlist
+                let start_at_end = is_first_read && !parser.is_vod();
+                self.add_item(result, start_at_end, &mut selection);
             }
-            Ok(n) => {
-                token_buffer[pos..pos + n].copy_from_slice(&buf);
-                pos += n;
+
+            match body.read(&mut buffer).await? {
+                // EOF. Not all playlists end with a new line.
+                0 => {
+                    let start_at_end = is_first_read && !parser.is_vod();
+                    self.add_item(parser.terminate(), start_at_end, &mut selection);
+                    break;
+                }
+                n => data = &buffer[..n],
             }
-            Err(e) => return Err(StreamError::UnableToReadContentType(e)),
         }
-    }
 
-    // Safety check
-    if pos < TOKEN_LEN {
-        return Err(StreamError::InvalidContent);
+        self.target_duration = parser
+            .target_duration()
+            .unwrap_or(DEFAULT_HLS_TARGET_DURATION);
+        self.is_end = parser.is_end();
+
+        match selection.take() {
+            Some(variant) => Ok(Some(join_url(&self.playlist_url, &variant.uri)?)),
+            None => Ok(None),
+        }
     }
 
-    let token_read_result = core::str::from_utf8(&token_buffer);
-    let token = match token_read_result {
-        Ok(token) => token,
-        Err(_) => {
-            // Unable to convert to UTF8so assume that the content type is audio
-            return Ok(ContentType::Audio);
+    // Queues a segment read from the playlist, if there is space for it, or offers a variant
+    // for selection. Segments that cannot be used are skipped.
+    fn add_item(
+        &mut self,
+        result: Result<Option<HlsItem<MAX_URL_LEN>>, M3UError>,
+        start_at_end: bool,
+        selection: &mut VariantSelection<MAX_URL_LEN>,
+    ) {
+        let segment = match result {
+            Ok(Some(HlsItem::Segment(segment))) => segment,
+            Ok(Some(HlsItem::Variant(variant))) => {
+                selection.offer(variant);
+                return;
+            }
+            Ok(None) => return,
+            Err(error) => {
+                esp_println::println!("ERROR: Skipping HLS segment [{:?}]", error);
+                return;
+            }
+        };
+
+        // Already queued when the playlist was read in before
+        if self
+            .next_sequence
+            .is_some_and(|next_sequence| segment.sequence < next_sequence)
+        {
+            return;
         }
-    };
 
-    let content_type = match token {
-        "http://" => parse_simple_m3u(socket).await?,
-        "#EXTM3U" => parse_extended_m3u(socket).await?,
-        // Return audio content type in the unlikely event that the first byte scan be intepretated as UTF8
-        _ => ContentType::Audio,
-    };
+        // Otherwise the segment is queued once there is space, when the playlist is read in again
+        if self.segments.is_full() {
+            if !start_at_end {
+                return;
+            }
+            self.segments.pop_front();
+        }
 
-    Ok(content_type)
+        match join_url(&self.playlist_url, &segment.uri) {
+            Ok(url) => {
+                let _ = self.segments.push_back(url);
+                self.next_sequence = Some(segment.sequence + 1);
+            }
+            Err(error) => esp_println::println!("ERROR: Skipping HLS segment [{:?}]", error),
+        }
+    }
 }
 
-async fn parse_simple_m3u(socket: &mut TcpSocket<'_>) -> Result<ContentType, StreamError> {
-    // Make sure that  MAX_URL_LEN can contain the token and, at least, a minimal url (http://a.de);
-    const_assert!(MAX_URL_LEN > TOKEN_LEN + 4);
+// Plays an HLS stream, whose media playlist has already been read in once. The segments are
+// requested one after another and their audio is sent to MUSIC_PIPE. Once all the queued
+// segments have been played, the playlist is read in again to get the newest segments.
+// A segment the server does not give is skipped, but the playlist has to be read in.
+// Returns once a new station has been selected.
+async fn stream_hls(
+    stack: Stack<'static>,
+    socket: &mut TcpSocket<'_>,
+    tls_buffers: &mut TlsBuffers,
+    hls: &mut HlsStream,
+    audio_buffer: &mut [u8],
+    metadata: &StreamMetadata,
+    station_change_receiver: &mut StationChangeReceiver,
+) -> Result<Option<RadioStation>, StreamError> {
+    let mut read_state = StreamingState::FillingPipe;
+    let initial_fill_len = 3 * MUSIC_PIPE.capacity() / 4;
+
+    // Any title from the previous station is no longer valid
+    STREAM_METADATA_WATCH.sender().send(metadata.clone());
+
+    let mut header_buffer = [0u8; HEADER_SIZE];
+    // The number of redirects followed for the current segment
+    let mut redirects = 0;
+
+    loop {
+        let is_playlist = hls.segments.is_empty();
+        if is_playlist && hls.is_end {
+            return Err(StreamError::EndOfStream);
+        }
+        let url_str = match hls.segments.front() {
+            Some(segment_url) => segment_url.clone(),
+            None => hls.playlist_url.clone(),
+        };
+
+        let (mut connection, headers_read) =
+            send_request(stack, socket, &url_str, tls_buffers, &mut header_buffer, 0).await?;
 
-    let mut url_buffer = [0u8; MAX_URL_LEN - TOKEN_LEN];
+        let response = Response::new(headers_read.headers(&header_buffer))?;
 
-    match socket.read(&mut url_buffer).await {
-        Ok(0) => Err(StreamError::EmptyM3U),
-        Ok(n) => {
-            let url_str = core::str::from_utf8(&url_buffer[0..n])?;
+        let error = match response.status_code() {
+            ResponseStatusCode::Successful(_) => None,
 
-            // Sometimes more then one url is recorded. Just take the first one.
-            let first_url_str = url_str.lines().next();
+            ResponseStatusCode::Redirection(_) => {
+                let next_url = match response.location() {
+                    Some(location) => join_url(&url_str, location).map_err(StreamError::from),
+                    None => Err(StreamError::NoRedirectionLocationFound),
+                };
 
-            let first_url_str = first_url_str.ok_or(StreamError::UrlNotFoundInM3U)?;
+                redirects += 1;
+                match next_url {
+                    Ok(next_url) if redirects > MAX_REDIRECTS || next_url == url_str => {
+                        Some(StreamError::RedirectLoop)
+                    }
+                    Ok(next_url) => {
+                        match hls.segments.front_mut() {
+                            Some(segment_url) => *segment_url = next_url,
+                            None => hls.playlist_url = next_url,
+                        }
+                        drop(connection);
+                        socket.abort();
+                        socket.flush().await?;
+                        continue;
+                    }
+                    Err(error) => Some(error),
+                }
+            }
 
-            let mut url = String::<MAX_URL_LEN>::new();
-            // This has already been read as the token
-            // SAFETY: We know this fits due to const_assert
-            url.push_str("http://").unwrap();
-            url.push_str(first_url_str)
-                .map_err(|_| StreamError::StationUrlTooLong)?;
-            Ok(ContentType::SimpleM3U(url))
+            other => Some(StreamError::InvalidHttpCode(other)),
+        };
+        redirects = 0;
+
+        match error {
+            // Without the playlist there is nothing more to play
+            Some(error) if is_playlist => return Err(error),
+            // A segment that cannot be fetched is skipped, the next one may well work
+            Some(error) => {
+                esp_println::println!(
+                    "ERROR: Skipping HLS segment {} [{:?}]",
+                    RedactedUrl(&url_str),
+                    error
+                );
+                hls.segments.pop_front();
+            }
+            None => {
+                let mut body = BodyReader::new(
+                    &mut connection,
+                    &response,
+                    headers_read.body(&header_buffer),
+                    0,
+                );
+
+                if is_playlist {
+                    if hls.read_playlist(&mut body, &[]).await?.is_some() {
+                        // A media playlist has to list segments, not variants
+                        return Err(StreamError::InvalidContent);
+                    }
+                } else {
+                    hls.segments.pop_front();
+                    read_state = stream_segment(
+                        &mut body,
+                        &response,
+                        audio_buffer,
+                        read_state,
+                        initial_fill_len,
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        drop(connection);
+        socket.abort();
+        socket.flush().await?;
+
+        // If there are no new segments yet, wait before reading the playlist in again
+        let new_station = if is_playlist && hls.segments.is_empty() && !hls.is_end {
+            let wait = Duration::from_secs(u64::from(hls.target_duration.max(2) / 2));
+            with_timeout(wait, station_change_receiver.changed())
+                .await
+                .ok()
+        } else {
+            station_change_receiver.try_changed()
+        };
+        if let Some(new_station) = new_station {
+            return Ok(new_station);
         }
-        Err(e) => Err(StreamError::Tcp(e)),
     }
 }
 
-// Extracts the first URL it finds in an extended M3U file and uses that as the next location
-// It is designed to be sparing with memory.
-async fn parse_extended_m3u(socket: &mut TcpSocket<'_>) -> Result<ContentType, StreamError> {
-    let mut m3u = M3U::<MAX_URL_LEN>::new();
+// Sends the audio of a segment of an HLS stream to MUSIC_PIPE. The segment is either an
+// MPEG transport stream, from which the audio is extracted, or just audio (e.g. packed AAC).
+// Returns the streaming state after the segment.
+async fn stream_segment<R: Read>(
+    body: &mut BodyReader<'_, R>,
+    response: &Response,
+    audio_buffer: &mut [u8],
+    mut read_state: StreamingState,
+    initial_fill_len: usize,
+) -> Result<StreamingState, StreamError>
+where
+    StreamError: From<R::Error>,
+{
+    let mut segment_start = [0u8; SNIFF_LEN];
+    let segment_start_len = body.read_up_to(&mut segment_start).await?;
+    let segment_start = &segment_start[..segment_start_len];
+
+    let mut ts_demux = match ContentType::detect(response.content_type(), segment_start) {
+        ContentType::TransportStream => Some(TsDemux::new()),
+        ContentType::Audio(_) | ContentType::Unknown => None,
+        ContentType::Html => return Err(StreamError::InvalidContent),
+        other => return Err(StreamError::UnsupportedContent(other)),
+    };
 
-    let mut char_buf = [0u8; 1];
+    // Start with the part of the segment that has already been read in, which can be longer
+    // than the buffer
+    let mut body = Prepend::new(segment_start, body);
 
     loop {
-        match socket.read(&mut char_buf).await {
-            Ok(0) => {
-                // EOF
-                let url = m3u.terminate()?;
-                return Ok(ContentType::ExtendedM3U(url));
+        let n = body.read(audio_buffer).await?;
+        if n == 0 {
+            return Ok(read_state);
+        }
+
+        match ts_demux.as_mut() {
+            Some(demux) => {
+                let mut data = &audio_buffer[..n];
+                while let Some(audio) = demux.next_audio(&mut data) {
+                    MUSIC_PIPE.write_all(audio).await;
+                }
             }
-            Ok(1) => {
-                let url = m3u.parse_m3u(char_buf[0])?;
-                if let Some(url) = url {
-                    return Ok(ContentType::ExtendedM3U(url));
-                } else {
-                    continue;
+            None => MUSIC_PIPE.write_all(&audio_buffer[..n]).await,
+        }
+
+        if read_state == StreamingState::FillingPipe && MUSIC_PIPE.len() >= initial_fill_len {
+            // If the pipe is more than 75% full, start playing (and emptying the pipe)
+            START_PLAYING.signal(true);
+            read_state = StreamingState::Playing;
+        }
+    }
+}
+
+// Reads the entries of a playlist. The first entry is returned to be played and the next ones
+// are kept in the playlist, to fall back on if its stream does not work.
+// The start of the body has already been read in. Relative URLs in the playlist are resolved
+// against the URL of the playlist.
+// It is designed to be sparing with memory, so stops reading once the playlist is full.
+async fn read_playlist<R: Read, P: PlaylistParser>(
+    body: &mut BodyReader<'_, R>,
+    body_start: &[u8],
+    playlist_url: &str,
+    parser: &mut P,
+    playlist: &mut Playlist,
+) -> Result<PlaylistEntry, StreamError>
+where
+    StreamError: From<R::Error> + From<P::Error>,
+{
+    let mut last_error = None;
+    playlist.clear();
+
+    let mut buffer = [0u8; 64];
+    let mut data = body_start;
+
+    loop {
+        for &b in data {
+            let result = parser.parse(b);
+            add_playlist_entry(result, playlist_url, parser, playlist, &mut last_error);
+        }
+
+        if playlist.is_full() {
+            break;
+        }
+
+        match body.read(&mut buffer).await? {
+            // EOF. Not all playlists end with a new line.
+            0 => {
+                loop {
+                    match parser.terminate() {
+                        Ok(None) => break,
+                        result => add_playlist_entry(
+                            result,
+                            playlist_url,
+                            parser,
+                            playlist,
+                            &mut last_error,
+                        ),
+                    }
                 }
+                break;
             }
-            Ok(n) => return Err(StreamError::TooManyBytesReadIn(n)),
-            Err(e) => return Err(StreamError::Tcp(e)),
+            n => data = &buffer[..n],
+        }
+    }
+
+    // Only give an error if no entry could be used
+    playlist
+        .pop_front()
+        .ok_or(last_error.unwrap_or(StreamError::EmptyPlaylist))
+}
+
+// Keeps an entry read from a playlist, if there is space for it. Entries that cannot be used
+// are skipped. Without a title for the entry, the title of the playlist is shown.
+fn add_playlist_entry<P: PlaylistParser>(
+    result: Result<Option<P::Entry>, P::Error>,
+    playlist_url: &str,
+    parser: &P,
+    playlist: &mut Playlist,
+    last_error: &mut Option<StreamError>,
+) where
+    StreamError: From<P::Error>,
+{
+    let result = result.map_err(StreamError::from).and_then(|entry| {
+        entry
+            .map(|entry| {
+                Ok(PlaylistEntry::new(
+                    join_url(playlist_url, entry.url())?,
+                    entry.title().or(parser.playlist_title()),
+                ))
+            })
+            .transpose()
+    });
+
+    match result {
+        // Once the playlist is full, the other entries are not needed
+        Ok(Some(entry)) => {
+            let _ = playlist.push_back(entry);
+        }
+        Ok(None) => (),
+        Err(error) => {
+            esp_println::println!("ERROR: Skipping playlist entry [{:?}]", error);
+            *last_error = Some(error);
         }
     }
 }
@@ -8,6 +8,8 @@ use embassy_sync::{
     watch::{Receiver, Watch},
 };
 
+use http::IcyMetadata;
+
 use crate::task::radio_stations::RadioStation;
 use crate::Vs1053DriverType;
 
@@ -77,6 +79,21 @@ pub type StationChangeReceiver = Receiver<
     STATION_CHANGE_WATCHERS,
 >;
 
+// The maximum length of the stream title and stream url sent in the stream metadata.
+pub const MAX_STREAM_METADATA_LEN: usize = 128;
+
+/// The metadata (i.e. the title of what is currently playing) sent by the station within the stream.
+pub type StreamMetadata = IcyMetadata<MAX_STREAM_METADATA_LEN>;
+
+// This watches for changes to the metadata of the stream, so that the title
+// can be shown in the UI
+const STREAM_METADATA_WATCHERS: usize = 1;
+pub static STREAM_METADATA_WATCH: Watch<
+    CriticalSectionRawMutex,
+    StreamMetadata,
+    STREAM_METADATA_WATCHERS,
+> = Watch::new();
+
 // This channel transports commands to the display.
 const UI_COMMAND_BUFFER_DEPTH: usize = 3;
 pub static UI_COMMANDS_CHANNEL: Channel<
@@ -0,0 +1,362 @@
+//! Web server to control the radio from a browser, or any other HTTP client.
+//!
+//! Serves a small web page on `/` and these JSON endpoints:
+//! - `GET /api/station`: the current station and the title being played
+//! - `GET /api/stations`: all the stations
+//! - `GET /stations.m3u`: all the stations as an extended M3U playlist, for desktop players,
+//!   without the user names and passwords in the station URLs
+//! - `POST /api/stations/{id}`: selects the station
+//! - `GET /api/presets`: the preset stations
+//! - `POST /api/presets/{id}`: selects the preset station
+//! - `GET /api/volume`: the volume in percent
+//! - `PUT /api/volume`: sets the volume, given in percent as plain text
+
+use core::fmt::{self, Write as _};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use embassy_net::{
+    tcp::{self, TcpSocket},
+    Stack,
+};
+use embassy_time::Duration;
+use embedded_io_async::Write;
+use heapless::String;
+
+use http::{
+    read_request, write_response, write_response_head, JsonString, Method, PathParams, RedactedUrl,
+    RequestParseError, Router, ServerError, ServerRequest, Status,
+};
+use m3u::{M3UWriter, MAX_GROUP_TITLE_LEN};
+
+use crate::task::radio_stations::{
+    RadioStation, RadioStations, MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN, NUMBER_PRESETS,
+    RADIO_STATIONS,
+};
+use crate::task::sync::{CODEC_DRIVER, STATION_CHANGE_WATCH, STREAM_METADATA_WATCH};
+
+const WEB_PORT: u16 = 80;
+
+const INDEX_HTML: &str = include_str!("web.html");
+
+const JSON: &str = "application/json";
+
+// Enough for any of the JSON responses, except the station list which is sent in parts
+const JSON_LEN: usize = 1024;
+
+// Enough for the playlist entry of any station, even with all of its URL percent-encoded
+const PLAYLIST_ENTRY_LEN: usize =
+    MAX_STATION_NAME_LEN + 3 * MAX_STATION_URL_LEN + MAX_GROUP_TITLE_LEN + 32;
+
+// The volume in percent. This is the volume the codec starts with.
+static VOLUME: AtomicU8 = AtomicU8::new(80);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Route {
+    Index,
+    CurrentStation,
+    Stations,
+    Playlist,
+    SelectStation,
+    Presets,
+    SelectPreset,
+    Volume,
+    SetVolume,
+}
+
+const ROUTER: Router<'static, Route> = Router::new(&[
+    (Method::GET, "/", Route::Index),
+    (Method::GET, "/api/station", Route::CurrentStation),
+    (Method::GET, "/api/stations", Route::Stations),
+    (Method::GET, "/stations.m3u", Route::Playlist),
+    (Method::POST, "/api/stations/{}", Route::SelectStation),
+    (Method::GET, "/api/presets", Route::Presets),
+    (Method::POST, "/api/presets/{}", Route::SelectPreset),
+    (Method::GET, "/api/volume", Route::Volume),
+    (Method::PUT, "/api/volume", Route::SetVolume),
+]);
+
+/// Serves the web page and API, one connection at a time.
+#[embassy_executor::task]
+pub async fn web(stack: Stack<'static>) {
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 2048];
+    let mut request_buffer = [0; 1024];
+
+    loop {
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(Duration::from_secs(10)));
+
+        if let Err(err) = socket.accept(WEB_PORT).await {
+            esp_println::println!("ERROR: Web server cannot accept connection [{:?}]", err);
+            continue;
+        }
+
+        if let Err(err) = handle_connection(&mut socket, &mut request_buffer).await {
+            esp_println::println!("ERROR: Web server connection [{:?}]", err);
+        }
+
+        // Make sure the response has been sent before the socket is dropped
+        socket.close();
+        let _ = socket.flush().await;
+    }
+}
+
+async fn handle_connection(
+    socket: &mut TcpSocket<'_>,
+    request_buffer: &mut [u8],
+) -> Result<(), tcp::Error> {
+    let request = match read_request(socket, request_buffer).await {
+        Ok(request) => request,
+        Err(ServerError::Io(err)) => return Err(err),
+        Err(ServerError::RequestTooLarge) => {
+            return respond(socket, Status::CONTENT_TOO_LARGE).await
+        }
+        Err(ServerError::Parse(RequestParseError::UnsupportedMethod)) => {
+            return respond(socket, Status::NOT_IMPLEMENTED).await
+        }
+        Err(_) => return respond(socket, Status::BAD_REQUEST).await,
+    };
+
+    let (route, params) = match ROUTER.route(request.method, request.path) {
+        Ok(route) => route,
+        Err(err) => return respond(socket, err.into()).await,
+    };
+
+    match route {
+        Route::Index => {
+            write_response(
+                socket,
+                Status::OK,
+                "text/html; charset=utf-8",
+                INDEX_HTML.as_bytes(),
+            )
+            .await
+        }
+        Route::CurrentStation => respond_json(socket, current_station_json()).await,
+        Route::Stations => write_stations(socket).await,
+        Route::Playlist => write_playlist(socket).await,
+        Route::SelectStation => {
+            let status = select_station(&params, |stations, id| stations.get_station(id)).await;
+            respond(socket, status).await
+        }
+        Route::Presets => {
+            let json = match RADIO_STATIONS.lock().await.as_ref() {
+                Some(stations) => presets_json(stations),
+                None => return respond(socket, Status::SERVICE_UNAVAILABLE).await,
+            };
+            respond_json(socket, json).await
+        }
+        Route::SelectPreset => {
+            let status = select_station(&params, |stations, id| {
+                stations.preset(id).map(|(_, station)| station)
+            })
+            .await;
+            respond(socket, status).await
+        }
+        Route::Volume => {
+            let mut json = String::<JSON_LEN>::new();
+            let result = write!(json, "{{\"volume\":{}}}", VOLUME.load(Ordering::Relaxed));
+            respond_json(socket, result.map(|_| json)).await
+        }
+        Route::SetVolume => {
+            let status = set_volume(&request).await;
+            respond(socket, status).await
+        }
+    }
+}
+
+// Responds with only the status
+async fn respond(socket: &mut TcpSocket<'_>, status: Status) -> Result<(), tcp::Error> {
+    write_response(socket, status, "", b"").await
+}
+
+async fn respond_json(
+    socket: &mut TcpSocket<'_>,
+    json: Result<String<JSON_LEN>, fmt::Error>,
+) -> Result<(), tcp::Error> {
+    match json {
+        Ok(json) => write_response(socket, Status::OK, JSON, json.as_bytes()).await,
+        // The JSON does not fit
+        Err(_) => respond(socket, Status::INTERNAL_SERVER_ERROR).await,
+    }
+}
+
+fn current_station_json() -> Result<String<JSON_LEN>, fmt::Error> {
+    let mut json = String::new();
+
+    match STATION_CHANGE_WATCH.anon_receiver().try_get().flatten() {
+        Some(station) => {
+            // The URL could contain a password
+            let mut url = String::<MAX_STATION_URL_LEN>::new();
+            write!(url, "{}", RedactedUrl(&station.url()))?;
+
+            let metadata = STREAM_METADATA_WATCH.anon_receiver().try_get();
+            let title = metadata
+                .as_ref()
+                .and_then(|metadata| metadata.stream_title());
+
+            write!(
+                json,
+                "{{\"name\":{},\"url\":{},\"title\":",
+                JsonString(&station.name()),
+                JsonString(&url)
+            )?;
+            match title {
+                Some(title) => write!(json, "{}}}", JsonString(title))?,
+                None => json.push_str("null}").map_err(|_| fmt::Error)?,
+            }
+        }
+        None => json.push_str("null").map_err(|_| fmt::Error)?,
+    }
+
+    Ok(json)
+}
+
+// The list can be too long for a buffer, so it is sent a station at a time
+async fn write_stations(socket: &mut TcpSocket<'_>) -> Result<(), tcp::Error> {
+    // The stations are locked only to copy out an entry, and not while it is sent, so a slow
+    // client does not hold up the other tasks
+    let Some(number_stations) = number_stations().await else {
+        return respond(socket, Status::SERVICE_UNAVAILABLE).await;
+    };
+
+    write_response_head(socket, Status::OK, JSON, None).await?;
+
+    let mut entry = String::<128>::new();
+    let mut separator = "[";
+    for id in 0..number_stations {
+        entry.clear();
+        {
+            let stations = RADIO_STATIONS.lock().await;
+            // The stations can be refreshed in between, the entry is then left out
+            let Some(station) = stations
+                .as_ref()
+                .and_then(|stations| stations.get_station(id))
+            else {
+                continue;
+            };
+
+            // The name is limited in length, so this always fits
+            let _ = write!(
+                entry,
+                "{}{{\"id\":{},\"name\":{}}}",
+                separator,
+                id,
+                JsonString(&station.name())
+            );
+        }
+        socket.write_all(entry.as_bytes()).await?;
+        separator = ",";
+    }
+
+    let end = if separator == "[" { "[]" } else { "]" };
+    socket.write_all(end.as_bytes()).await
+}
+
+// Like the station list, the playlist is sent a station at a time
+async fn write_playlist(socket: &mut TcpSocket<'_>) -> Result<(), tcp::Error> {
+    let Some(number_stations) = number_stations().await else {
+        return respond(socket, Status::SERVICE_UNAVAILABLE).await;
+    };
+
+    // The header always fits
+    let Ok(mut playlist) = M3UWriter::new(String::<PLAYLIST_ENTRY_LEN>::new()) else {
+        return respond(socket, Status::INTERNAL_SERVER_ERROR).await;
+    };
+
+    write_response_head(socket, Status::OK, "audio/x-mpegurl", None).await?;
+    socket.write_all(playlist.get_mut().as_bytes()).await?;
+
+    for id in 0..number_stations {
+        playlist.get_mut().clear();
+        if let Some(stations) = RADIO_STATIONS.lock().await.as_ref() {
+            // The entry always fits
+            let _ = playlist.write_station(stations, id);
+        }
+        socket.write_all(playlist.get_mut().as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+async fn number_stations() -> Option<usize> {
+    RADIO_STATIONS
+        .lock()
+        .await
+        .as_ref()
+        .map(|stations| stations.number_stations())
+}
+
+fn presets_json(stations: &RadioStations) -> Result<String<JSON_LEN>, fmt::Error> {
+    let mut json = String::new();
+
+    json.push('[').map_err(|_| fmt::Error)?;
+    for preset_id in 0..NUMBER_PRESETS {
+        if preset_id > 0 {
+            json.push(',').map_err(|_| fmt::Error)?;
+        }
+        match stations.preset(preset_id) {
+            Some((id, station)) => write!(
+                json,
+                "{{\"preset\":{},\"id\":{},\"name\":{}}}",
+                preset_id,
+                id,
+                JsonString(&station.name())
+            )?,
+            None => write!(json, "{{\"preset\":{},\"id\":null}}", preset_id)?,
+        }
+    }
+    json.push(']').map_err(|_| fmt::Error)?;
+
+    Ok(json)
+}
+
+// Selects the station with the id in the path. The stream task changes to the station.
+async fn select_station(
+    params: &PathParams<'_>,
+    find: impl Fn(&RadioStations, usize) -> Option<RadioStation>,
+) -> Status {
+    let Some(id) = params.parse::<usize>(0) else {
+        return Status::BAD_REQUEST;
+    };
+
+    let station = match RADIO_STATIONS.lock().await.as_ref() {
+        Some(stations) => find(stations, id),
+        None => return Status::SERVICE_UNAVAILABLE,
+    };
+
+    match station {
+        Some(station) => {
+            STATION_CHANGE_WATCH.sender().send(Some(station));
+            Status::NO_CONTENT
+        }
+        None => Status::NOT_FOUND,
+    }
+}
+
+async fn set_volume(request: &ServerRequest<'_>) -> Status {
+    let volume = match request.body_str().map(|body| body.trim().parse::<u8>()) {
+        Ok(Ok(volume)) if volume <= 100 => volume,
+        _ => return Status::BAD_REQUEST,
+    };
+
+    // The codec takes the attenuation in steps of -0.5 dB, where 0 is the loudest.
+    // 100 dB down is as good as silent.
+    let attenuation = (100 - volume) * 2;
+
+    let mut driver = CODEC_DRIVER.lock().await;
+    let Some(driver) = driver.as_mut() else {
+        return Status::SERVICE_UNAVAILABLE;
+    };
+
+    match driver.set_volume(attenuation, attenuation).await {
+        Ok(_) => {
+            VOLUME.store(volume, Ordering::Relaxed);
+            Status::NO_CONTENT
+        }
+        Err(err) => {
+            esp_println::println!("ERROR: Cannot set the volume [{:?}]", err);
+            Status::INTERNAL_SERVER_ERROR
+        }
+    }
+}
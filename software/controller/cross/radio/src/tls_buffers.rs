@@ -0,0 +1,83 @@
+//! The buffers used for TLS connections.
+//!
+//! They are about 20 KB, too much for the task stacks, so they are kept in statics. Each task
+//! has its own, so that an HTTPS station that is playing does not hold up reading the station
+//! list. The station list only gets buffers if `STATIONS_URL` uses HTTPS.
+
+use http::{TLS_READ_BUFFER_SIZE, TLS_WRITE_BUFFER_SIZE};
+use static_cell::ConstStaticCell;
+
+use crate::constants::STATIONS_URL;
+
+const STATIONS_USE_TLS: bool = is_https(STATIONS_URL);
+const STATIONS_TLS_READ_BUFFER_SIZE: usize = if STATIONS_USE_TLS {
+    TLS_READ_BUFFER_SIZE
+} else {
+    0
+};
+const STATIONS_TLS_WRITE_BUFFER_SIZE: usize = if STATIONS_USE_TLS {
+    TLS_WRITE_BUFFER_SIZE
+} else {
+    0
+};
+
+/// The buffers of the station list, which are empty unless `STATIONS_URL` uses HTTPS.
+pub type StationsTlsBuffers =
+    TlsBuffers<STATIONS_TLS_READ_BUFFER_SIZE, STATIONS_TLS_WRITE_BUFFER_SIZE>;
+
+static STREAM_TLS_BUFFERS: ConstStaticCell<TlsBuffers> = ConstStaticCell::new(TlsBuffers::new());
+static STATIONS_TLS_BUFFERS: ConstStaticCell<StationsTlsBuffers> =
+    ConstStaticCell::new(TlsBuffers::new());
+
+pub struct TlsBuffers<
+    const READ_LEN: usize = TLS_READ_BUFFER_SIZE,
+    const WRITE_LEN: usize = TLS_WRITE_BUFFER_SIZE,
+> {
+    read_buffer: [u8; READ_LEN],
+    write_buffer: [u8; WRITE_LEN],
+}
+
+impl TlsBuffers {
+    /// The buffers of the stream task. This can only be called once.
+    pub fn stream() -> &'static mut TlsBuffers {
+        STREAM_TLS_BUFFERS.take()
+    }
+}
+
+impl StationsTlsBuffers {
+    /// The buffers of the station list task. This can only be called once.
+    pub fn stations() -> &'static mut StationsTlsBuffers {
+        STATIONS_TLS_BUFFERS.take()
+    }
+}
+
+impl<const READ_LEN: usize, const WRITE_LEN: usize> TlsBuffers<READ_LEN, WRITE_LEN> {
+    const fn new() -> Self {
+        TlsBuffers {
+            read_buffer: [0; READ_LEN],
+            write_buffer: [0; WRITE_LEN],
+        }
+    }
+
+    /// The read and the write buffer, as given to `Connection::tls`.
+    pub fn split(&mut self) -> (&mut [u8], &mut [u8]) {
+        (&mut self.read_buffer, &mut self.write_buffer)
+    }
+}
+
+// Whether the URL uses HTTPS, worked out when compiling
+const fn is_https(url: &str) -> bool {
+    let url = url.as_bytes();
+    let scheme = b"https://";
+    if url.len() < scheme.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < scheme.len() {
+        if url[i].to_ascii_lowercase() != scheme[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
@@ -0,0 +1,41 @@
+//! The random number generator used for TLS.
+
+use esp_hal::rng::Rng;
+use rand_core::{CryptoRng, RngCore};
+
+/// The hardware random number generator, usable by embedded-tls.
+///
+/// The ESP32-C3 RNG produces true random numbers when the radio (wifi) is running,
+/// which is always the case when a TLS connection is made.
+#[derive(Clone, Copy)]
+pub struct TlsRng(Rng);
+
+impl TlsRng {
+    pub fn new() -> TlsRng {
+        TlsRng(Rng::new())
+    }
+}
+
+impl RngCore for TlsRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.random()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        ((self.0.random() as u64) << 32) | self.0.random() as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let random = self.0.random().to_le_bytes();
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for TlsRng {}
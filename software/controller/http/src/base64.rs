@@ -0,0 +1,75 @@
+//! Base64 encoding (RFC 4648 with padding), as used for Basic authentication.
+
+use heapless::String;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Appends the base64 encoding of the bytes to the string.
+/// Returns an error if the string is too short, in which case it is left unchanged.
+pub(crate) fn encode<const N: usize>(
+    input: impl IntoIterator<Item = u8>,
+    output: &mut String<N>,
+) -> Result<(), ()> {
+    let start = output.len();
+    let mut input = input.into_iter();
+
+    let result = loop {
+        let Some(b0) = input.next() else {
+            break Ok(());
+        };
+        let b1 = input.next();
+        let b2 = b1.and_then(|_| input.next());
+
+        let n = (b0 as u32) << 16 | (b1.unwrap_or(0) as u32) << 8 | b2.unwrap_or(0) as u32;
+        let sextet = |shift: u32| ALPHABET[(n >> shift & 0x3F) as usize] as char;
+
+        let pushed = output
+            .push(sextet(18))
+            .and_then(|_| output.push(sextet(12)))
+            .and_then(|_| output.push(if b1.is_some() { sextet(6) } else { '=' }))
+            .and_then(|_| output.push(if b2.is_some() { sextet(0) } else { '=' }));
+        if pushed.is_err() {
+            break Err(());
+        }
+    };
+
+    if result.is_err() {
+        output.truncate(start);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(input: &str) -> String<64> {
+        let mut output = String::new();
+        encode(input.bytes(), &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_encode() {
+        // Test vectors from RFC 4648
+        assert_eq!(encoded(""), "");
+        assert_eq!(encoded("f"), "Zg==");
+        assert_eq!(encoded("fo"), "Zm8=");
+        assert_eq!(encoded("foo"), "Zm9v");
+        assert_eq!(encoded("foob"), "Zm9vYg==");
+        assert_eq!(encoded("fooba"), "Zm9vYmE=");
+        assert_eq!(encoded("foobar"), "Zm9vYmFy");
+
+        assert_eq!(
+            encoded("Aladdin:open sesame"),
+            "QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+    }
+
+    #[test]
+    fn test_encode_too_long() {
+        let mut output = String::<8>::try_from("ab").unwrap();
+        assert!(encode(b"foobar".iter().copied(), &mut output).is_err());
+        assert_eq!(output, "ab");
+    }
+}
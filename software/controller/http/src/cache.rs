@@ -0,0 +1,86 @@
+//! Conditional requests, so that a resource is only downloaded again if it has changed.
+//!
+//! The `ETag` and `Last-Modified` headers of a response are kept as `CacheValidators`,
+//! which are sent back in the `If-None-Match` and `If-Modified-Since` headers of the next
+//! request for the same resource. If the resource has not changed the server responds
+//! with `304 Not Modified` and no body, so the copy from the last response can be used.
+
+use heapless::String;
+
+use crate::response::Response;
+
+/// The maximum length of an `ETag` or `Last-Modified` value. Longer values are not kept.
+pub const MAX_VALIDATOR_LEN: usize = 64;
+
+/// The values of a response that identify the version of the resource.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CacheValidators {
+    /// The entity tag (`ETag`), including the quotes and any weak prefix, e.g. `W/"1a2b"`
+    pub etag: Option<String<MAX_VALIDATOR_LEN>>,
+    /// The date the resource was last modified (`Last-Modified`)
+    pub last_modified: Option<String<MAX_VALIDATOR_LEN>>,
+}
+
+impl CacheValidators {
+    /// Takes the validators from the headers of a response.
+    pub fn from_response(response: &Response) -> CacheValidators {
+        let validator = |name| {
+            response
+                .header(name)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .and_then(|value| String::try_from(value).ok())
+        };
+
+        CacheValidators {
+            etag: validator("etag"),
+            last_modified: validator("last-modified"),
+        }
+    }
+
+    /// Returns true if there is nothing to make a request conditional on.
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_response() {
+        let response = Response::new(
+            b"HTTP/1.1 200 OK\r\nETag: \"5f3a-1c\"\r\nLast-Modified: Tue, 03 Jun 2025 10:15:00 GMT\r\n\r\n",
+        )
+        .unwrap();
+
+        let validators = CacheValidators::from_response(&response);
+
+        assert_eq!(validators.etag.unwrap(), "\"5f3a-1c\"");
+        assert_eq!(
+            validators.last_modified.unwrap(),
+            "Tue, 03 Jun 2025 10:15:00 GMT"
+        );
+    }
+
+    #[test]
+    fn test_no_validators() {
+        let response = Response::new(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+
+        let validators = CacheValidators::from_response(&response);
+
+        assert!(validators.is_empty());
+        assert_eq!(validators, CacheValidators::default());
+    }
+
+    #[test]
+    fn test_validator_too_long() {
+        let mut header_buffer = std::string::String::from("HTTP/1.1 200 OK\r\nETag: \"");
+        header_buffer.push_str(&"a".repeat(MAX_VALIDATOR_LEN));
+        header_buffer.push_str("\"\r\n\r\n");
+        let response = Response::new(header_buffer.as_bytes()).unwrap();
+
+        assert!(CacheValidators::from_response(&response).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! Decoding of a body sent with `Transfer-Encoding: chunked`.
+//!
+//! Each chunk of the body is preceded by its size in hexadecimal on its own line and followed by CRLF:
+//!
+//! ```text
+//! 1a;optional-extension\r\n
+//! <26 bytes of data>\r\n
+//! 0\r\n
+//! optional-trailer: value\r\n
+//! \r\n
+//! ```
+
+use crate::error::ChunkedError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Size { size: usize, digits: usize },
+    Extension { size: usize },
+    SizeLf { size: usize },
+    Data { remaining: usize },
+    DataCr,
+    DataLf,
+    Trailer { line_len: usize },
+    Done,
+}
+
+/// Incrementally decodes a chunked body.
+///
+/// The raw bytes read from the connection are given to the decoder in pieces of any size
+/// and only the bytes of the body itself are given back.
+pub struct ChunkedDecoder {
+    state: State,
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> ChunkedDecoder {
+        ChunkedDecoder {
+            state: State::Size { size: 0, digits: 0 },
+        }
+    }
+
+    /// Decodes the raw bytes read from the connection.
+    ///
+    /// The body bytes are moved to the start of `data` and the number of them is returned.
+    /// Once the last chunk has been read, any further data is ignored.
+    pub fn decode(&mut self, data: &mut [u8]) -> Result<usize, ChunkedError> {
+        let mut body_len = 0;
+        let mut pos = 0;
+
+        while pos < data.len() {
+            if let State::Data { remaining } = self.state {
+                let n = remaining.min(data.len() - pos);
+                data.copy_within(pos..pos + n, body_len);
+                body_len += n;
+                pos += n;
+
+                self.state = if remaining == n {
+                    State::DataCr
+                } else {
+                    State::Data {
+                        remaining: remaining - n,
+                    }
+                };
+                continue;
+            }
+
+            let b = data[pos];
+            pos += 1;
+
+            self.state = match (self.state, b) {
+                (State::Size { size, digits }, _) if b.is_ascii_hexdigit() => {
+                    let digit = (b as char).to_digit(16).expect("is a hex digit") as usize;
+                    let size = size
+                        .checked_mul(16)
+                        .and_then(|size| size.checked_add(digit))
+                        .ok_or(ChunkedError::ChunkSizeTooLarge)?;
+                    State::Size {
+                        size,
+                        digits: digits + 1,
+                    }
+                }
+                (State::Size { digits: 0, .. }, _) => Err(ChunkedError::InvalidChunkSize)?,
+                (State::Size { size, .. }, b';' | b' ' | b'\t') => State::Extension { size },
+                (State::Size { size, .. }, b'\r') => State::SizeLf { size },
+                // Be lenient with lines only terminated with LF
+                (State::Size { size, .. }, b'\n') => Self::chunk_start(size),
+                (State::Size { .. }, _) => Err(ChunkedError::InvalidChunkSize)?,
+
+                // Chunk extensions are ignored
+                (State::Extension { size }, b'\n') => Self::chunk_start(size),
+                (State::Extension { size }, _) => State::Extension { size },
+
+                (State::SizeLf { size }, b'\n') => Self::chunk_start(size),
+                (State::SizeLf { .. }, _) => Err(ChunkedError::MissingCrLf)?,
+
+                (State::DataCr, b'\r') => State::DataLf,
+                (State::DataCr, b'\n') => State::Size { size: 0, digits: 0 },
+                (State::DataCr, _) => Err(ChunkedError::MissingCrLf)?,
+
+                (State::DataLf, b'\n') => State::Size { size: 0, digits: 0 },
+                (State::DataLf, _) => Err(ChunkedError::MissingCrLf)?,
+
+                // Trailers are ignored. The body ends with an empty line.
+                (State::Trailer { line_len: 0 }, b'\n') => State::Done,
+                (State::Trailer { .. }, b'\n') => State::Trailer { line_len: 0 },
+                (State::Trailer { line_len }, b'\r') => State::Trailer { line_len },
+                (State::Trailer { line_len }, _) => State::Trailer {
+                    line_len: line_len + 1,
+                },
+
+                (State::Done, _) => State::Done,
+
+                // Handled above
+                (State::Data { .. }, _) => unreachable!(),
+            };
+        }
+
+        Ok(body_len)
+    }
+
+    /// Returns true when the last chunk has been read, i.e. the body is complete.
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    fn chunk_start(size: usize) -> State {
+        if size == 0 {
+            State::Trailer { line_len: 0 }
+        } else {
+            State::Data { remaining: size }
+        }
+    }
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use heapless::Vec;
+
+    const BODY: &[u8] =
+        b"7\r\nMozilla\r\n11;name=value\r\nDeveloper Network\r\n0\r\nExpires: never\r\n\r\n";
+
+    #[test]
+    fn test_decode() {
+        let mut data = [0u8; BODY.len()];
+        data.copy_from_slice(BODY);
+
+        let mut decoder = ChunkedDecoder::new();
+        let len = decoder.decode(&mut data).unwrap();
+
+        assert_eq!(&data[..len], b"MozillaDeveloper Network");
+        assert!(decoder.is_done());
+    }
+
+    #[test]
+    fn test_decode_in_pieces() {
+        for piece_len in 1..BODY.len() {
+            let mut decoder = ChunkedDecoder::new();
+            let mut body = Vec::<u8, 64>::new();
+
+            for piece in BODY.chunks(piece_len) {
+                let mut buffer = [0u8; 128];
+                buffer[..piece.len()].copy_from_slice(piece);
+
+                let len = decoder.decode(&mut buffer[..piece.len()]).unwrap();
+                body.extend_from_slice(&buffer[..len]).unwrap();
+            }
+
+            assert_eq!(&body, b"MozillaDeveloper Network");
+            assert!(decoder.is_done());
+        }
+    }
+
+    #[test]
+    fn test_decode_incomplete() {
+        let mut data = *b"A\r\n01234";
+
+        let mut decoder = ChunkedDecoder::new();
+        let len = decoder.decode(&mut data).unwrap();
+
+        assert_eq!(&data[..len], b"01234");
+        assert!(!decoder.is_done());
+    }
+
+    #[test]
+    fn test_decode_lf_only() {
+        let mut data = *b"3\nabc\n0\n\n";
+
+        let mut decoder = ChunkedDecoder::new();
+        let len = decoder.decode(&mut data).unwrap();
+
+        assert_eq!(&data[..len], b"abc");
+        assert!(decoder.is_done());
+    }
+
+    #[test]
+    fn test_decode_invalid_size() {
+        let mut decoder = ChunkedDecoder::new();
+        assert_eq!(
+            decoder.decode(&mut b"\r\nabc".to_owned()),
+            Err(ChunkedError::InvalidChunkSize)
+        );
+
+        let mut decoder = ChunkedDecoder::new();
+        assert_eq!(
+            decoder.decode(&mut b"3x\r\nabc".to_owned()),
+            Err(ChunkedError::InvalidChunkSize)
+        );
+
+        let mut decoder = ChunkedDecoder::new();
+        assert_eq!(
+            decoder.decode(&mut b"fffffffffffffffff\r\n".to_owned()),
+            Err(ChunkedError::ChunkSizeTooLarge)
+        );
+    }
+
+    #[test]
+    fn test_decode_missing_crlf() {
+        let mut decoder = ChunkedDecoder::new();
+        assert_eq!(
+            decoder.decode(&mut b"3\r\nabcd\r\n".to_owned()),
+            Err(ChunkedError::MissingCrLf)
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! Determining what a response body contains.
+//!
+//! The `Content-Type` header is used if it is clear. Otherwise the start of the body is
+//! examined ("sniffed"). Many stations send a wrong or generic content type (e.g. `text/plain`
+//! for playlists), so sniffing is still needed.
+
+/// The number of bytes at the start of a body that are needed to sniff the content type.
+/// Less can be given if the body is shorter.
+///
+/// Most types can be told from the first few bytes, but an HLS playlist is only told apart
+/// from an M3U playlist by its `#EXT-X-` tags, which come before the first URI. This is
+/// enough for those tags after a byte order mark and a few comments.
+pub const SNIFF_LEN: usize = 512;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The codec used for audio content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCodec {
+    Mp3,
+    Aac,
+    Ogg,
+    /// Audio, but the codec is not known. Leave it to the decoder to find out.
+    Unknown,
+}
+
+/// The type of content in a response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    /// An audio stream or file
+    Audio(AudioCodec),
+    /// An M3U playlist, either simple (just URLs) or extended (`#EXTM3U`)
+    M3U,
+    /// A PLS playlist (`[playlist]`)
+    Pls,
+    /// An ASX playlist (`<asx>`)
+    Asx,
+    /// An XSPF playlist (`<playlist>` in XML)
+    Xspf,
+    /// An HLS playlist (M3U8 with `#EXT-X-` tags)
+    Hls,
+    /// An MPEG transport stream, normally a segment of an HLS stream
+    TransportStream,
+    /// An HTML page, normally an error page
+    Html,
+    /// Could not be determined
+    Unknown,
+}
+
+impl ContentType {
+    /// Determines the content type, using the media type from the `Content-Type` header
+    /// if it is known and, if not, by sniffing the start of the body.
+    ///
+    /// An HTML page is never taken to be audio, even if the header says so, as some
+    /// servers send error pages with the content type of the stream.
+    ///
+    /// The M3U media types (e.g. `audio/mpegurl`) are also used for HLS playlists
+    /// (RFC 8216), so the body decides between the two.
+    pub fn detect(media_type: Option<&str>, body_start: &[u8]) -> ContentType {
+        match media_type.and_then(ContentType::from_media_type) {
+            Some(ContentType::Audio(codec)) => match ContentType::sniff(body_start) {
+                ContentType::Html => ContentType::Html,
+                _ => ContentType::Audio(codec),
+            },
+            Some(ContentType::M3U) => match ContentType::sniff(body_start) {
+                ContentType::Hls => ContentType::Hls,
+                _ => ContentType::M3U,
+            },
+            Some(content_type) => content_type,
+            None => ContentType::sniff(body_start),
+        }
+    }
+
+    /// Maps a media type (e.g. `audio/mpeg`) to the content type.
+    /// Returns `None` if the media type is too generic (e.g. `application/octet-stream`)
+    /// or not known.
+    pub fn from_media_type(media_type: &str) -> Option<ContentType> {
+        let media_type = media_type.split(';').next().unwrap_or_default().trim();
+        let is = |name: &str| media_type.eq_ignore_ascii_case(name);
+
+        let content_type = if is("audio/mpeg") || is("audio/mp3") || is("audio/mpeg3") {
+            ContentType::Audio(AudioCodec::Mp3)
+        } else if is("audio/aac") || is("audio/aacp") || is("audio/x-aac") {
+            ContentType::Audio(AudioCodec::Aac)
+        } else if is("audio/ogg") || is("application/ogg") {
+            ContentType::Audio(AudioCodec::Ogg)
+        } else if is("audio/x-mpegurl") || is("audio/mpegurl") {
+            ContentType::M3U
+        } else if is("application/vnd.apple.mpegurl") || is("application/x-mpegurl") {
+            ContentType::Hls
+        } else if is("audio/x-scpls") {
+            ContentType::Pls
+        } else if is("video/x-ms-asf") || is("video/x-ms-asx") || is("audio/x-ms-wax") {
+            ContentType::Asx
+        } else if is("application/xspf+xml") {
+            ContentType::Xspf
+        } else if is("video/mp2t") {
+            ContentType::TransportStream
+        } else if is("text/html") {
+            ContentType::Html
+        } else if media_type.len() > 6 && media_type[..6].eq_ignore_ascii_case("audio/") {
+            ContentType::Audio(AudioCodec::Unknown)
+        } else {
+            return None;
+        };
+
+        Some(content_type)
+    }
+
+    /// Determines the content type from the start of the body, which should be at least
+    /// `SNIFF_LEN` bytes if the body is long enough.
+    pub fn sniff(body_start: &[u8]) -> ContentType {
+        if let Some(codec) = sniff_audio(body_start) {
+            return ContentType::Audio(codec);
+        }
+
+        // The sync byte of the first packet, without the transport error indicator and with
+        // a payload or adaptation field
+        if let [0x47, b, _, c, ..] = body_start {
+            if b & 0x80 == 0 && c & 0x30 != 0 {
+                return ContentType::TransportStream;
+            }
+        }
+
+        // Playlists and HTML are text
+        let text = body_start.strip_prefix(UTF8_BOM).unwrap_or(body_start);
+        let text = text.trim_ascii_start();
+        let starts_with = |token: &[u8]| {
+            text.len() >= token.len() && text[..token.len()].eq_ignore_ascii_case(token)
+        };
+
+        if starts_with(b"#EXTM3U") {
+            if has_hls_tag(text) {
+                ContentType::Hls
+            } else {
+                ContentType::M3U
+            }
+        } else if starts_with(b"http://") || starts_with(b"https://") {
+            ContentType::M3U
+        } else if starts_with(b"[playlist]") {
+            ContentType::Pls
+        } else if starts_with(b"<asx") {
+            ContentType::Asx
+        } else if starts_with(b"<playlist") || starts_with(b"<?xml") {
+            // ASX playlists do not normally start with an XML declaration
+            ContentType::Xspf
+        } else if starts_with(b"<!doctype html") || starts_with(b"<html") {
+            ContentType::Html
+        } else {
+            ContentType::Unknown
+        }
+    }
+}
+
+// Whether an extended M3U playlist has a #EXT-X- tag before its first URI, so is an HLS
+// playlist
+fn has_hls_tag(text: &[u8]) -> bool {
+    for line in text.split(|&b| b == b'\n').map(<[u8]>::trim_ascii) {
+        if line.starts_with(b"#EXT-X-") {
+            return true;
+        }
+        if !line.is_empty() && !line.starts_with(b"#") {
+            return false;
+        }
+    }
+    false
+}
+
+// Looks for the signatures of the audio formats
+fn sniff_audio(body_start: &[u8]) -> Option<AudioCodec> {
+    match body_start {
+        [b'I', b'D', b'3', ..] => Some(AudioCodec::Mp3),
+        [b'O', b'g', b'g', b'S', ..] => Some(AudioCodec::Ogg),
+        // ADTS frame sync with layer 0
+        [0xFF, b, ..] if b & 0xF6 == 0xF0 => Some(AudioCodec::Aac),
+        // MPEG audio frame sync with a valid layer
+        [0xFF, b, ..] if b & 0xE0 == 0xE0 && b & 0x06 != 0 => Some(AudioCodec::Mp3),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_media_type() {
+        assert_eq!(
+            ContentType::from_media_type("audio/mpeg"),
+            Some(ContentType::Audio(AudioCodec::Mp3))
+        );
+        assert_eq!(
+            ContentType::from_media_type("audio/aacp"),
+            Some(ContentType::Audio(AudioCodec::Aac))
+        );
+        assert_eq!(
+            ContentType::from_media_type("audio/aac"),
+            Some(ContentType::Audio(AudioCodec::Aac))
+        );
+        assert_eq!(
+            ContentType::from_media_type("audio/ogg"),
+            Some(ContentType::Audio(AudioCodec::Ogg))
+        );
+        assert_eq!(
+            ContentType::from_media_type("audio/flac"),
+            Some(ContentType::Audio(AudioCodec::Unknown))
+        );
+        assert_eq!(
+            ContentType::from_media_type("audio/x-mpegurl"),
+            Some(ContentType::M3U)
+        );
+        assert_eq!(
+            ContentType::from_media_type("application/vnd.apple.mpegurl"),
+            Some(ContentType::Hls)
+        );
+        assert_eq!(
+            ContentType::from_media_type("audio/x-scpls"),
+            Some(ContentType::Pls)
+        );
+        assert_eq!(
+            ContentType::from_media_type("video/x-ms-asf"),
+            Some(ContentType::Asx)
+        );
+        assert_eq!(
+            ContentType::from_media_type("application/xspf+xml"),
+            Some(ContentType::Xspf)
+        );
+        assert_eq!(
+            ContentType::from_media_type("video/MP2T"),
+            Some(ContentType::TransportStream)
+        );
+        assert_eq!(
+            ContentType::from_media_type("text/html; charset=utf-8"),
+            Some(ContentType::Html)
+        );
+        assert_eq!(
+            ContentType::from_media_type("Audio/MPEG"),
+            Some(ContentType::Audio(AudioCodec::Mp3))
+        );
+
+        assert_eq!(
+            ContentType::from_media_type("application/octet-stream"),
+            None
+        );
+        assert_eq!(ContentType::from_media_type("text/plain"), None);
+        // The XML Pronunciation Lexicon, not a PLS playlist
+        assert_eq!(ContentType::from_media_type("application/pls+xml"), None);
+        assert_eq!(ContentType::from_media_type("audio/"), None);
+    }
+
+    #[test]
+    fn test_sniff_playlists() {
+        assert_eq!(
+            ContentType::sniff(b"#EXTM3U\n#EXTINF:-1,"),
+            ContentType::M3U
+        );
+        assert_eq!(
+            ContentType::sniff(b"#EXTM3U\n#EXT-X-VERSION:3\n"),
+            ContentType::Hls
+        );
+        assert_eq!(
+            ContentType::sniff(b"\xEF\xBB\xBF#EXTM3U\r\n#EXT-X-TARGETDURATION:10\r\n"),
+            ContentType::Hls
+        );
+        assert_eq!(
+            ContentType::sniff(b"#EXTM3U\n# A comment\n\n#EXT-X-VERSION:3\n"),
+            ContentType::Hls
+        );
+        // Only the tags before the first URI count
+        assert_eq!(
+            ContentType::sniff(b"#EXTM3U\n#EXTINF:-1,Radio\nhttp://a.de/s\n#EXT-X-VERSION:3\n"),
+            ContentType::M3U
+        );
+        assert_eq!(ContentType::sniff(b"http://listen.181f"), ContentType::M3U);
+        assert_eq!(ContentType::sniff(b"https://a.de/s\n"), ContentType::M3U);
+        assert_eq!(
+            ContentType::sniff(b"\xEF\xBB\xBF#EXTM3U\r\n#EXT"),
+            ContentType::M3U
+        );
+        assert_eq!(ContentType::sniff(b"[playlist]\nFile1="), ContentType::Pls);
+        assert_eq!(
+            ContentType::sniff(b"\n[Playlist]\r\nNumberOf"),
+            ContentType::Pls
+        );
+        assert_eq!(ContentType::sniff(b"<ASX version=\"3"), ContentType::Asx);
+        assert_eq!(
+            ContentType::sniff(b"<?xml version=\"1.0"),
+            ContentType::Xspf
+        );
+        assert_eq!(ContentType::sniff(b"<playlist version"), ContentType::Xspf);
+    }
+
+    #[test]
+    fn test_sniff_html() {
+        assert_eq!(
+            ContentType::sniff(b"<!DOCTYPE html>\n<ht"),
+            ContentType::Html
+        );
+        assert_eq!(ContentType::sniff(b"  <html><head><tit"), ContentType::Html);
+    }
+
+    #[test]
+    fn test_sniff_audio() {
+        assert_eq!(
+            ContentType::sniff(b"ID3\x04\x00\x00\x00\x00\x00\x00"),
+            ContentType::Audio(AudioCodec::Mp3)
+        );
+        // MPEG-1 layer III
+        assert_eq!(
+            ContentType::sniff(&[0xFF, 0xFB, 0x90, 0x64, 0x00]),
+            ContentType::Audio(AudioCodec::Mp3)
+        );
+        // ADTS
+        assert_eq!(
+            ContentType::sniff(&[0xFF, 0xF1, 0x50, 0x80, 0x02]),
+            ContentType::Audio(AudioCodec::Aac)
+        );
+        assert_eq!(
+            ContentType::sniff(b"OggS\x00\x02\x00\x00"),
+            ContentType::Audio(AudioCodec::Ogg)
+        );
+        assert_eq!(
+            ContentType::sniff(&[0x47, 0x40, 0x00, 0x10, 0x00]),
+            ContentType::TransportStream
+        );
+        assert_eq!(
+            ContentType::sniff(&[0x12, 0x34, 0x56, 0x78]),
+            ContentType::Unknown
+        );
+        assert_eq!(ContentType::sniff(b""), ContentType::Unknown);
+    }
+
+    #[test]
+    fn test_detect() {
+        // The header takes priority
+        assert_eq!(
+            ContentType::detect(Some("audio/x-scpls"), b"#EXTM3U\n"),
+            ContentType::Pls
+        );
+        // Generic header so sniff
+        assert_eq!(
+            ContentType::detect(Some("text/plain"), b"#EXTM3U\n"),
+            ContentType::M3U
+        );
+        // An HLS playlist sent as M3U
+        assert_eq!(
+            ContentType::detect(Some("audio/mpegurl"), b"#EXTM3U\n#EXT-X-VERSION:3\n"),
+            ContentType::Hls
+        );
+        assert_eq!(
+            ContentType::detect(Some("audio/x-mpegurl"), b"#EXTM3U\n#EXTINF:-1,R\nhttp://a"),
+            ContentType::M3U
+        );
+        // A simple M3U playlist
+        assert_eq!(
+            ContentType::detect(Some("audio/x-mpegurl"), b"http://a.de/s\n"),
+            ContentType::M3U
+        );
+        assert_eq!(ContentType::detect(None, b"<html>"), ContentType::Html);
+        // An error page sent with an audio content type
+        assert_eq!(
+            ContentType::detect(Some("audio/mpeg"), b"<html>"),
+            ContentType::Html
+        );
+        assert_eq!(
+            ContentType::detect(Some("audio/aacp"), &[0x12, 0x34]),
+            ContentType::Audio(AudioCodec::Aac)
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::response::ResponseStatusCode;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RequestError {
     /// Error when trying to convert a string to a fixed-size string.
@@ -30,6 +32,138 @@ pub enum ResponseError {
     BufferOverflow,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkedError {
+    /// The size of a chunk is not a hexadecimal number
+    InvalidChunkSize,
+
+    /// The size of a chunk is too large to be handled
+    ChunkSizeTooLarge,
+
+    /// A chunk size line or chunk data is not terminated with CRLF
+    MissingCrLf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadHeadersError<E> {
+    /// Error from the connection
+    Io(E),
+
+    /// The connection was closed before the end of the headers
+    UnexpectedEof,
+
+    /// The headers do not fit into the buffer
+    BufferOverflow,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyError<E> {
+    /// Error from the connection to the proxy
+    Io(E),
+
+    /// The CONNECT request could not be built
+    Request(RequestError),
+
+    /// The response of the proxy could not be read
+    Response(ResponseError),
+
+    /// The proxy did not open the tunnel
+    Refused(ResponseStatusCode),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestParseError {
+    /// The request is not complete, e.g. the body is shorter than `Content-Length`
+    Incomplete,
+
+    /// The request is not a valid HTTP request
+    Invalid,
+
+    /// The method is not one of `Method`
+    UnsupportedMethod,
+
+    /// The body is sent with a transfer encoding, e.g. chunked, which is not supported
+    UnsupportedTransferEncoding,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerError<E> {
+    /// Error from the connection
+    Io(E),
+
+    /// The connection was closed before the whole request was read
+    UnexpectedEof,
+
+    /// The request does not fit into the buffer
+    RequestTooLarge,
+
+    /// The request could not be parsed
+    Parse(RequestParseError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    /// The base URL is not an absolute URL
+    InvalidBase,
+
+    /// The resolved URL is longer than `MAX_URL_LEN`, or the user name or password
+    /// longer than `MAX_CREDENTIAL_LEN`
+    TooLong,
+
+    /// The user name or password in the URL is not correctly percent-encoded
+    InvalidCredentials,
+}
+
+impl<E> From<RequestError> for ProxyError<E> {
+    fn from(e: RequestError) -> ProxyError<E> {
+        ProxyError::Request(e)
+    }
+}
+
+impl<E> From<ResponseError> for ProxyError<E> {
+    fn from(e: ResponseError) -> ProxyError<E> {
+        ProxyError::Response(e)
+    }
+}
+
+impl<E> From<ReadHeadersError<E>> for ProxyError<E> {
+    fn from(error: ReadHeadersError<E>) -> Self {
+        match error {
+            ReadHeadersError::Io(error) => ProxyError::Io(error),
+            ReadHeadersError::UnexpectedEof => ProxyError::Response(ResponseError::UnexpectedEof),
+            ReadHeadersError::BufferOverflow => ProxyError::Response(ResponseError::BufferOverflow),
+        }
+    }
+}
+
+impl From<httparse::Error> for RequestParseError {
+    fn from(_e: httparse::Error) -> RequestParseError {
+        RequestParseError::Invalid
+    }
+}
+
+impl<E> From<RequestParseError> for ServerError<E> {
+    fn from(e: RequestParseError) -> ServerError<E> {
+        ServerError::Parse(e)
+    }
+}
+
+impl<E> From<httparse::Error> for ServerError<E> {
+    fn from(e: httparse::Error) -> ServerError<E> {
+        ServerError::Parse(e.into())
+    }
+}
+
+impl<E> From<ReadHeadersError<E>> for ServerError<E> {
+    fn from(error: ReadHeadersError<E>) -> Self {
+        match error {
+            ReadHeadersError::Io(error) => ServerError::Io(error),
+            ReadHeadersError::UnexpectedEof => ServerError::UnexpectedEof,
+            ReadHeadersError::BufferOverflow => ServerError::RequestTooLarge,
+        }
+    }
+}
+
 impl From<httparse::Error> for ResponseError {
     fn from(e: httparse::Error) -> ResponseError {
         ResponseError::HeaderParse(e)
@@ -0,0 +1,354 @@
+use heapless::{String, Vec};
+
+use crate::range::ContentRange;
+
+/// The maximum number of headers kept in `Headers`.
+pub const MAX_HEADERS: usize = 32;
+
+/// The space available for the names and values of all the headers kept in `Headers`.
+pub const HEADERS_POOL_SIZE: usize = 1024;
+
+// The headers read through the accessors of `Headers` and `Response`. Space is kept for them,
+// so that other headers, e.g. a long cookie, cannot push them out.
+const RESERVED_HEADERS: &[&str] = &[
+    "location",
+    "transfer-encoding",
+    "content-type",
+    "content-length",
+    "content-range",
+    "accept-ranges",
+    "etag",
+    "last-modified",
+    "icy-metaint",
+    "icy-name",
+    "icy-genre",
+    "icy-br",
+];
+
+// The position of a header name and value in the pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct HeaderPositions {
+    // Start and end index of the header name
+    name: (usize, usize),
+    // Start and end index of the header value
+    value: (usize, usize),
+}
+
+/// The headers of a response.
+///
+/// To save storage the names and values are stored in a string pool of fixed size.
+/// Headers that do not fit into the pool, or exceed `MAX_HEADERS`, are dropped.
+///
+/// Header names are looked up ignoring case.
+#[derive(Default, Clone, Debug)]
+pub struct Headers {
+    pool: String<HEADERS_POOL_SIZE>,
+    positions: Vec<HeaderPositions, MAX_HEADERS>,
+}
+
+impl Headers {
+    /// Creates an empty set of headers.
+    pub fn new() -> Headers {
+        Headers {
+            pool: String::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    // Builds the headers from those parsed by httparse
+    pub(crate) fn from_parsed(parsed: &[httparse::Header<'_>]) -> Headers {
+        let is_reserved = |header: &&httparse::Header<'_>| {
+            RESERVED_HEADERS
+                .iter()
+                .any(|name| header.name.eq_ignore_ascii_case(name))
+        };
+        // The space still needed for the reserved headers that are yet to be added
+        let mut reserved_len: usize = parsed
+            .iter()
+            .filter(is_reserved)
+            .map(|header| header.name.len() + pool_len(header.value))
+            .sum();
+        let mut reserved_count = parsed.iter().filter(is_reserved).count();
+
+        let mut headers = Headers::new();
+        for header in parsed {
+            let len = header.name.len() + pool_len(header.value);
+            if is_reserved(&header) {
+                reserved_len -= len;
+                reserved_count -= 1;
+            } else if headers.pool.len() + len + reserved_len > HEADERS_POOL_SIZE
+                || headers.positions.len() + 1 + reserved_count > MAX_HEADERS
+            {
+                continue;
+            }
+            // A header that does not fit is dropped, but the others are still kept if possible.
+            let _ = headers.insert(header.name, header.value);
+        }
+        headers
+    }
+
+    // Adds a header. If the value is not UTF-8 it is taken to be ISO-8859-1.
+    // Nothing is added if there is not enough space.
+    fn insert(&mut self, name: &str, value: &[u8]) -> Result<(), ()> {
+        if self.positions.is_full() {
+            return Err(());
+        }
+
+        let pool_len = self.pool.len();
+
+        let name_positions = (self.pool.len(), self.pool.len() + name.len());
+        let value_start = name_positions.1;
+
+        let pushed = self
+            .pool
+            .push_str(name)
+            .and_then(|_| match core::str::from_utf8(value) {
+                Ok(value) => self.pool.push_str(value),
+                Err(_) => value
+                    .iter()
+                    .try_for_each(|&b| self.pool.push(char::from(b))),
+            });
+
+        if pushed.is_err() {
+            // Remove anything that has been partially added
+            self.pool.truncate(pool_len);
+            return Err(());
+        }
+
+        self.positions
+            .push(HeaderPositions {
+                name: name_positions,
+                value: (value_start, self.pool.len()),
+            })
+            .expect("checked that positions is not full");
+
+        Ok(())
+    }
+
+    /// Returns the value of the first header with the name, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the values of all the headers with the name, ignoring case.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.iter()
+            .filter(move |(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns true if there is a header with the name, ignoring case.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Iterates over the names and values of the headers in the order they were received.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.positions.iter().map(|positions| {
+            (
+                &self.pool[positions.name.0..positions.name.1],
+                &self.pool[positions.value.0..positions.value.1],
+            )
+        })
+    }
+
+    /// The number of headers.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns true if there are no headers.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The media type given by the `Content-Type` header without any parameters,
+    /// e.g. `audio/mpeg` for `audio/mpeg; charset=utf-8`.
+    ///
+    /// Note that media types are not case sensitive.
+    pub fn content_type(&self) -> Option<&str> {
+        self.get("content-type").and_then(media_type)
+    }
+
+    /// The length of the body given by the `Content-Length` header, or `None` if
+    /// the header is not present or is not a valid number.
+    pub fn content_length(&self) -> Option<usize> {
+        self.get("content-length").and_then(content_length)
+    }
+
+    /// The part of the resource sent in the body given by the `Content-Range` header.
+    pub fn content_range(&self) -> Option<ContentRange> {
+        self.get("content-range").and_then(ContentRange::parse)
+    }
+
+    /// Returns true if the server has said with `Accept-Ranges: bytes` that it supports
+    /// range requests.
+    pub fn accepts_ranges(&self) -> bool {
+        self.get_all("accept-ranges").any(accepts_byte_ranges)
+    }
+}
+
+// The length of a header value in the pool, where a value that is not UTF-8 is taken to be
+// ISO-8859-1
+fn pool_len(value: &[u8]) -> usize {
+    match core::str::from_utf8(value) {
+        Ok(value) => value.len(),
+        Err(_) => value.iter().map(|&b| char::from(b).len_utf8()).sum(),
+    }
+}
+
+// The media type of a Content-Type header value, without the parameters
+pub(crate) fn media_type(value: &str) -> Option<&str> {
+    value
+        .split(';')
+        .next()
+        .map(str::trim)
+        .filter(|media_type| !media_type.is_empty())
+}
+
+// The length in a Content-Length header value
+pub(crate) fn content_length(value: &str) -> Option<usize> {
+    value.trim().parse().ok()
+}
+
+// Whether an Accept-Ranges header value includes byte ranges
+pub(crate) fn accepts_byte_ranges(value: &str) -> bool {
+    value
+        .split(',')
+        .any(|unit| unit.trim().eq_ignore_ascii_case("bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(header_buffer: &[u8]) -> Headers {
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut response = httparse::Response::new(&mut headers);
+        response.parse(header_buffer).unwrap();
+        Headers::from_parsed(response.headers)
+    }
+
+    #[test]
+    fn test_get() {
+        let headers = parse(include_bytes!("test_resources/example_response.txt"));
+
+        assert_eq!(headers.len(), 7);
+        assert_eq!(headers.get("Connection"), Some("keep-alive"));
+        assert_eq!(headers.get("connection"), Some("keep-alive"));
+        assert_eq!(headers.get("CONTENT-LANGUAGE"), Some("en-US"));
+        assert_eq!(
+            headers.get("cache-control"),
+            Some("s-maxage=300, public, max-age=0")
+        );
+        assert_eq!(headers.get("ETag"), None);
+        assert!(headers.contains("date"));
+    }
+
+    #[test]
+    fn test_iter() {
+        let headers = parse(b"HTTP/1.1 200 OK\r\nServer: nginx\r\nETag: \"abc\"\r\n\r\n");
+
+        let mut iter = headers.iter();
+        assert_eq!(iter.next(), Some(("Server", "nginx")));
+        assert_eq!(iter.next(), Some(("ETag", "\"abc\"")));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_get_all() {
+        let headers = parse(
+            b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nServer: nginx\r\nset-cookie: b=2\r\n\r\n",
+        );
+
+        let mut cookies = headers.get_all("Set-Cookie");
+        assert_eq!(cookies.next(), Some("a=1"));
+        assert_eq!(cookies.next(), Some("b=2"));
+        assert_eq!(cookies.next(), None);
+    }
+
+    #[test]
+    fn test_content_type() {
+        let headers = parse(include_bytes!("test_resources/example_response.txt"));
+        assert_eq!(headers.content_type(), Some("text/html"));
+
+        let headers = parse(b"HTTP/1.1 200 OK\r\nContent-Type: audio/mpeg\r\n\r\n");
+        assert_eq!(headers.content_type(), Some("audio/mpeg"));
+
+        let headers = parse(b"HTTP/1.1 200 OK\r\nContent-Type: \r\n\r\n");
+        assert_eq!(headers.content_type(), None);
+    }
+
+    #[test]
+    fn test_content_length() {
+        let headers = parse(include_bytes!("test_resources/example_response.txt"));
+        assert_eq!(headers.content_length(), Some(55743));
+
+        let headers = parse(b"HTTP/1.1 200 OK\r\nContent-Length: lots\r\n\r\n");
+        assert_eq!(headers.content_length(), None);
+
+        let headers = parse(b"HTTP/1.1 200 OK\r\n\r\n");
+        assert_eq!(headers.content_length(), None);
+    }
+
+    #[test]
+    fn test_latin1_value() {
+        let headers = parse(b"HTTP/1.1 200 OK\r\nicy-name: Radio K\xf6ln\r\n\r\n");
+        assert_eq!(headers.get("icy-name"), Some("Radio Köln"));
+    }
+
+    #[test]
+    fn test_too_many_headers() {
+        let mut headers = Headers::new();
+        for i in 0..MAX_HEADERS + 1 {
+            let value = [b'0' + (i % 10) as u8];
+            let r = headers.insert("X-Header", &value);
+            assert_eq!(r.is_ok(), i < MAX_HEADERS);
+        }
+        assert_eq!(headers.len(), MAX_HEADERS);
+    }
+
+    #[test]
+    fn test_pool_full() {
+        let mut headers = Headers::new();
+        let long_value = [b'a'; HEADERS_POOL_SIZE - 20];
+
+        assert!(headers.insert("X-Long", &long_value).is_ok());
+        // Does not fit
+        assert!(headers.insert("X-Too-Long", b"0123456789abcdef").is_err());
+        // Does fit
+        assert!(headers.insert("Server", b"nginx").is_ok());
+
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers.get("x-too-long"), None);
+        assert_eq!(headers.get("server"), Some("nginx"));
+    }
+
+    #[test]
+    fn test_space_kept_for_reserved_headers() {
+        // The cookie comes first, but would leave no space for the content type
+        let cookie = "c".repeat(HEADERS_POOL_SIZE - 20);
+        let headers = parse(
+            std::format!(
+                "HTTP/1.1 200 OK\r\nSet-Cookie: {cookie}\r\nContent-Type: audio/mpeg\r\nServer: nginx\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        assert_eq!(headers.get("set-cookie"), None);
+        assert_eq!(headers.content_type(), Some("audio/mpeg"));
+        assert_eq!(headers.get("server"), Some("nginx"));
+
+        // As many headers as can be kept, before the content length
+        let mut header_buffer = std::string::String::from("HTTP/1.1 200 OK\r\n");
+        for i in 0..MAX_HEADERS {
+            header_buffer.push_str(&std::format!("X-Header-{i}: {i}\r\n"));
+        }
+        header_buffer.push_str("Content-Length: 1000\r\n\r\n");
+        let headers = parse(header_buffer.as_bytes());
+        assert_eq!(headers.len(), MAX_HEADERS);
+        assert_eq!(headers.get("x-header-31"), None);
+        assert_eq!(headers.content_length(), Some(1000));
+    }
+}
@@ -0,0 +1,304 @@
+//! Handling of ICY (Shoutcast/Icecast) in-band metadata.
+//!
+//! If a client sends the header `Icy-MetaData: 1` then the server can insert metadata blocks
+//! into the audio stream. The interval (in audio bytes) between the blocks is given by the
+//! `icy-metaint` response header. Each block starts with a length byte (the length of the
+//! block divided by 16) followed by the metadata text, e.g.:
+//!
+//! ```text
+//! StreamTitle='Artist - Title';StreamUrl='http://example.com';
+//! ```
+//!
+//! The metadata blocks must be removed before the audio data is sent to the decoder.
+
+use heapless::{String, Vec};
+
+const STREAM_TITLE_KEY: &[u8] = b"StreamTitle='";
+const STREAM_URL_KEY: &[u8] = b"StreamUrl='";
+
+/// The metadata sent in-band by an ICY server.
+///
+/// The text values are limited to `LEN` bytes and are truncated if longer.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IcyMetadata<const LEN: usize> {
+    stream_title: Option<String<LEN>>,
+    stream_url: Option<String<LEN>>,
+}
+
+impl<const LEN: usize> IcyMetadata<LEN> {
+    /// Parses the text of a metadata block.
+    pub fn parse(block: &[u8]) -> IcyMetadata<LEN> {
+        IcyMetadata {
+            stream_title: field(block, STREAM_TITLE_KEY).map(decode_text),
+            stream_url: field(block, STREAM_URL_KEY).map(decode_text),
+        }
+    }
+
+    /// Metadata with only a title, e.g. the title of a playlist entry before the station
+    /// sends its own. The title is truncated if it is too long.
+    pub fn with_title(title: &str) -> IcyMetadata<LEN> {
+        IcyMetadata {
+            stream_title: Some(decode_text(title.as_bytes())),
+            stream_url: None,
+        }
+    }
+
+    /// The title of what is currently playing, if sent.
+    pub fn stream_title(&self) -> Option<&str> {
+        self.stream_title.as_deref()
+    }
+
+    /// The URL associated with the stream, if sent.
+    pub fn stream_url(&self) -> Option<&str> {
+        self.stream_url.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DemuxState {
+    Audio { remaining: usize },
+    Length,
+    Metadata { remaining: usize },
+}
+
+/// Separates the ICY metadata blocks from the audio data in a stream.
+///
+/// At most `LEN` bytes of each metadata block are kept. This is normally enough for
+/// the stream title.
+pub struct IcyDemux<const LEN: usize> {
+    metaint: usize,
+    state: DemuxState,
+    metadata: Vec<u8, LEN>,
+}
+
+impl<const LEN: usize> IcyDemux<LEN> {
+    /// Creates a demultiplexer for a stream with metadata blocks every `metaint` audio bytes,
+    /// i.e. the value of the `icy-metaint` header.
+    pub fn new(metaint: usize) -> IcyDemux<LEN> {
+        IcyDemux {
+            metaint,
+            state: DemuxState::Audio { remaining: metaint },
+            metadata: Vec::new(),
+        }
+    }
+
+    /// Removes any metadata from the data just read from the stream.
+    ///
+    /// The audio bytes are moved to the start of `data` and the number of them is returned.
+    /// If a metadata block has been completed then it is parsed and returned as well.
+    /// The data can be given in pieces of any size.
+    pub fn demux(&mut self, data: &mut [u8]) -> (usize, Option<IcyMetadata<LEN>>) {
+        let mut audio_len = 0;
+        let mut pos = 0;
+        let mut metadata = None;
+
+        while pos < data.len() {
+            match self.state {
+                DemuxState::Audio { remaining } => {
+                    let n = remaining.min(data.len() - pos);
+                    data.copy_within(pos..pos + n, audio_len);
+                    audio_len += n;
+                    pos += n;
+
+                    self.state = if remaining == n {
+                        DemuxState::Length
+                    } else {
+                        DemuxState::Audio {
+                            remaining: remaining - n,
+                        }
+                    };
+                }
+                DemuxState::Length => {
+                    let len = data[pos] as usize * 16;
+                    pos += 1;
+
+                    self.metadata.clear();
+                    self.state = if len == 0 {
+                        // Most blocks are empty as the metadata is only sent when it changes
+                        DemuxState::Audio {
+                            remaining: self.metaint,
+                        }
+                    } else {
+                        DemuxState::Metadata { remaining: len }
+                    };
+                }
+                DemuxState::Metadata { remaining } => {
+                    let n = remaining.min(data.len() - pos);
+
+                    // Only keep what fits, the rest of the block is dropped
+                    let keep = n.min(LEN - self.metadata.len());
+                    self.metadata
+                        .extend_from_slice(&data[pos..pos + keep])
+                        .expect("keep has been limited to the space left");
+                    pos += n;
+
+                    self.state = if remaining == n {
+                        metadata = Some(IcyMetadata::parse(&self.metadata));
+                        DemuxState::Audio {
+                            remaining: self.metaint,
+                        }
+                    } else {
+                        DemuxState::Metadata {
+                            remaining: remaining - n,
+                        }
+                    };
+                }
+            }
+        }
+
+        (audio_len, metadata)
+    }
+}
+
+// Finds the value of a field such as StreamTitle='...'; in a metadata block
+fn field<'a>(block: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    let start = block.windows(key.len()).position(|w| w == key)? + key.len();
+    let value = &block[start..];
+
+    // The value can contain single quotes so look for the end of the field
+    let end = value
+        .windows(2)
+        .position(|w| w == b"';")
+        .unwrap_or_else(|| {
+            // Unterminated, possibly as the block has been truncated
+            value
+                .iter()
+                .rposition(|&b| b != 0 && b != b'\'')
+                .map_or(0, |p| p + 1)
+        });
+
+    Some(&value[..end])
+}
+
+/// Converts text from an ICY server to a string, truncating it if it is too long.
+///
+/// Older servers send ISO-8859-1 rather than UTF-8, so if the text is not valid UTF-8
+/// it is decoded as ISO-8859-1.
+pub(crate) fn decode_text<const LEN: usize>(text: &[u8]) -> String<LEN> {
+    let mut s = String::new();
+
+    match core::str::from_utf8(text) {
+        Ok(text) => {
+            for c in text.chars() {
+                if s.push(c).is_err() {
+                    break;
+                }
+            }
+        }
+        Err(_) => {
+            for &b in text {
+                if s.push(char::from(b)).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metadata() {
+        let block =
+            b"StreamTitle='Queen - Don't Stop Me Now';StreamUrl='http://example.com';\0\0\0";
+        let metadata = IcyMetadata::<64>::parse(block);
+
+        assert_eq!(metadata.stream_title(), Some("Queen - Don't Stop Me Now"));
+        assert_eq!(metadata.stream_url(), Some("http://example.com"));
+    }
+
+    #[test]
+    fn test_parse_metadata_without_url() {
+        let metadata = IcyMetadata::<64>::parse(b"StreamTitle='News';\0\0\0\0");
+
+        assert_eq!(metadata.stream_title(), Some("News"));
+        assert_eq!(metadata.stream_url(), None);
+    }
+
+    #[test]
+    fn test_parse_metadata_truncated() {
+        let metadata = IcyMetadata::<8>::parse(b"StreamTitle='A very long title';");
+        assert_eq!(metadata.stream_title(), Some("A very l"));
+
+        // The block itself has been truncated
+        let metadata = IcyMetadata::<64>::parse(b"StreamTitle='Truncat");
+        assert_eq!(metadata.stream_title(), Some("Truncat"));
+    }
+
+    #[test]
+    fn test_with_title() {
+        let metadata = IcyMetadata::<8>::with_title("Radio Caroline");
+
+        assert_eq!(metadata.stream_title(), Some("Radio Ca"));
+        assert_eq!(metadata.stream_url(), None);
+    }
+
+    #[test]
+    fn test_parse_metadata_latin1() {
+        let metadata = IcyMetadata::<64>::parse(b"StreamTitle='Bj\xf6rk - J\xf3ga';");
+        assert_eq!(metadata.stream_title(), Some("Björk - Jóga"));
+    }
+
+    // Builds a stream with metadata every 8 bytes
+    fn stream() -> [u8; 8 + 1 + 32 + 8 + 1 + 4] {
+        let mut stream = [0u8; 8 + 1 + 32 + 8 + 1 + 4];
+        stream[0..8].copy_from_slice(b"AUDIO001");
+        stream[8] = 2;
+        stream[9..9 + 20].copy_from_slice(b"StreamTitle='Title';");
+        stream[41..49].copy_from_slice(b"AUDIO002");
+        stream[49] = 0;
+        stream[50..54].copy_from_slice(b"AUDI");
+        stream
+    }
+
+    #[test]
+    fn test_demux() {
+        let mut data = stream();
+        let mut demux = IcyDemux::<64>::new(8);
+
+        let (audio_len, metadata) = demux.demux(&mut data);
+
+        assert_eq!(&data[..audio_len], b"AUDIO001AUDIO002AUDI");
+        assert_eq!(metadata.unwrap().stream_title(), Some("Title"));
+    }
+
+    #[test]
+    fn test_demux_in_pieces() {
+        let data = stream();
+
+        for piece_len in 1..data.len() {
+            let mut demux = IcyDemux::<64>::new(8);
+            let mut audio = Vec::<u8, 64>::new();
+            let mut title = None;
+
+            for piece in data.chunks(piece_len) {
+                let mut buffer = [0u8; 64];
+                buffer[..piece.len()].copy_from_slice(piece);
+
+                let (audio_len, metadata) = demux.demux(&mut buffer[..piece.len()]);
+                audio.extend_from_slice(&buffer[..audio_len]).unwrap();
+                if let Some(metadata) = metadata {
+                    title = metadata.stream_title().map(String::<64>::try_from);
+                }
+            }
+
+            assert_eq!(&audio, b"AUDIO001AUDIO002AUDI");
+            assert_eq!(title.unwrap().unwrap(), "Title");
+        }
+    }
+
+    #[test]
+    fn test_demux_metadata_too_long() {
+        let mut data = stream();
+        let mut demux = IcyDemux::<16>::new(8);
+
+        let (audio_len, metadata) = demux.demux(&mut data);
+
+        assert_eq!(&data[..audio_len], b"AUDIO001AUDIO002AUDI");
+        assert_eq!(metadata.unwrap().stream_title(), Some("Tit"));
+    }
+}
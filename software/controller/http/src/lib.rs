@@ -14,14 +14,64 @@
 //! ## Modules
 //! - `request`: HTTP request construction and parsing
 //! - `response`: HTTP response parsing and status code handling
+//! - `headers`: Access to all the headers of a response
+//! - `range`: Range requests, e.g. to resume a download
+//! - `cache`: Conditional requests using the `ETag` and `Last-Modified` of a response
+//! - `reader`: Reading the headers of a response from a connection
+//! - `url`: Resolving relative URLs, e.g. in redirects, and credentials in URLs
 //! - `error`: Error types for request and response operations
+//! - `content_type`: Determining the type of content in a response body
+//! - `chunked`: Decoding of bodies sent with chunked transfer encoding
+//! - `icy`: Removal of ICY (Shoutcast/Icecast) metadata from audio streams
+//! - `prepend`: Reading on from the start of a body that has been read in to find its type
+//! - `ts`: Extracting the audio from MPEG transport streams, e.g. HLS segments
+//! - `server`: A minimal HTTP server with request parsing, routing and responses
+//! - `proxy`: Connections through an HTTP proxy
+//! - `tls`: HTTPS connections (with the `tls` feature)
 //!
 
+mod base64;
+mod cache;
+mod chunked;
+mod content_type;
 mod error;
+mod headers;
+mod icy;
+mod prepend;
+mod proxy;
+mod range;
+mod reader;
 mod request;
 mod response;
+mod server;
+#[cfg(feature = "tls")]
+mod tls;
+mod ts;
+mod url;
 
-pub use error::{RequestError, ResponseError};
-pub use request::Method;
-pub use request::Request;
-pub use response::{Response, ResponseStatusCode, MAX_URL_LEN};
+pub use cache::{CacheValidators, MAX_VALIDATOR_LEN};
+pub use chunked::ChunkedDecoder;
+pub use content_type::{AudioCodec, ContentType, SNIFF_LEN};
+pub use error::{
+    ChunkedError, ProxyError, ReadHeadersError, RequestError, RequestParseError, ResponseError,
+    ServerError, UrlError,
+};
+pub use headers::{Headers, HEADERS_POOL_SIZE, MAX_HEADERS};
+pub use icy::{IcyDemux, IcyMetadata};
+pub use prepend::Prepend;
+pub use proxy::{connect_tunnel, Proxy};
+pub use range::ContentRange;
+pub use reader::{read_headers, HeadersRead};
+pub use request::{Method, Request, Version, REQUEST_SIZE};
+pub use response::{Response, ResponseStatusCode, TransferEncoding, MAX_URL_LEN};
+pub use server::{
+    read_request, write_response, write_response_head, JsonString, PathParams, RouteError, Router,
+    ServerRequest, Status, MAX_PATH_PARAMS, MAX_REQUEST_HEADERS,
+};
+#[cfg(feature = "tls")]
+pub use tls::{
+    CertificateVerification, Connection, ConnectionError, TlsError, TLS_READ_BUFFER_SIZE,
+    TLS_WRITE_BUFFER_SIZE,
+};
+pub use ts::{TsDemux, TS_PACKET_LEN};
+pub use url::{join_url, split_credentials, Credentials, RedactedUrl, MAX_CREDENTIAL_LEN};
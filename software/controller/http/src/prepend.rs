@@ -0,0 +1,76 @@
+//! Reading on from the start of a body that has already been read in.
+//!
+//! The start of a body is read in to find out what it is (see `ContentType::detect`), and is
+//! usually longer than the buffer the rest of the body is then read into. `Prepend` gives
+//! out the start in parts that fit the buffer before reading on, so the whole body goes
+//! through the same code, e.g. to remove ICY metadata or extract the audio of a segment.
+
+use embedded_io_async::{ErrorType, Read};
+
+/// A reader that first returns the bytes that have already been read in, and then reads on
+/// from the reader.
+pub struct Prepend<'a, R> {
+    start: &'a [u8],
+    reader: R,
+}
+
+impl<'a, R> Prepend<'a, R> {
+    pub fn new(start: &'a [u8], reader: R) -> Prepend<'a, R> {
+        Prepend { start, reader }
+    }
+}
+
+impl<R: ErrorType> ErrorType for Prepend<'_, R> {
+    type Error = R::Error;
+}
+
+impl<R: Read> Read for Prepend<'_, R> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.start.is_empty() {
+            return self.reader.read(buf).await;
+        }
+
+        let len = self.start.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.start[..len]);
+        self.start = &self.start[len..];
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embassy_futures::block_on;
+
+    #[test]
+    fn test_read_in_parts() {
+        let body: std::vec::Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let (start, rest) = body.split_at(600);
+
+        let mut reader = Prepend::new(start, rest);
+        let mut buffer = [0u8; 16];
+        let mut read = std::vec::Vec::new();
+        loop {
+            match block_on(reader.read(&mut buffer)).unwrap() {
+                0 => break,
+                n => {
+                    assert!(n <= buffer.len());
+                    read.extend_from_slice(&buffer[..n]);
+                }
+            }
+        }
+
+        assert_eq!(read, body);
+    }
+
+    #[test]
+    fn test_empty_start() {
+        let mut reader = Prepend::new(&[], &b"rest"[..]);
+        let mut buffer = [0u8; 16];
+
+        assert_eq!(block_on(reader.read(&mut buffer)).unwrap(), 4);
+        assert_eq!(&buffer[..4], b"rest");
+        assert_eq!(block_on(reader.read(&mut buffer)).unwrap(), 0);
+    }
+}
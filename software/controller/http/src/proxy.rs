@@ -0,0 +1,221 @@
+//! Connections through an HTTP proxy.
+//!
+//! For `http://` URLs the request is sent to the proxy with the whole URL as the request
+//! target (absolute-form), e.g. `GET http://example.com/stream HTTP/1.1`, which is done by
+//! giving the URL instead of the path to `Request::new`.
+//!
+//! For `https://` URLs the proxy is asked with `CONNECT` to open a tunnel to the server
+//! (see `connect_tunnel`). The TLS connection is then made through the tunnel as if it was
+//! a direct connection to the server.
+
+use core::fmt::Write as _;
+
+use embedded_io_async::{Read, Write};
+use heapless::String;
+
+use crate::error::{ProxyError, RequestError};
+use crate::reader::read_headers;
+use crate::request::{Method, Request};
+use crate::response::{Response, ResponseStatusCode, MAX_URL_LEN};
+
+/// The proxy that connections are made through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Proxy<'a> {
+    /// The host name or IP address of the proxy
+    pub host: &'a str,
+    /// The port of the proxy, often 3128 or 8080
+    pub port: u16,
+}
+
+impl<'a> Proxy<'a> {
+    pub const fn new(host: &'a str, port: u16) -> Proxy<'a> {
+        Proxy { host, port }
+    }
+}
+
+/// Asks the proxy, which the socket is connected to, to open a tunnel to the server.
+///
+/// The buffer is used to read the response of the proxy, so has to be large enough for
+/// its headers. Once this returns, everything written to and read from the socket goes
+/// to and comes from the server.
+pub async fn connect_tunnel<S: Read + Write>(
+    socket: &mut S,
+    host: &str,
+    port: u16,
+    buffer: &mut [u8],
+) -> Result<(), ProxyError<S::Error>> {
+    let mut authority = String::<MAX_URL_LEN>::new();
+    write!(authority, "{}:{}", host, port).map_err(|_| RequestError::StringPushError)?;
+
+    // The request target of CONNECT is only the host and port (authority-form)
+    let mut request = Request::new(Method::CONNECT, &authority)?;
+    request.host(&authority)?;
+
+    request.write_to(socket).await.map_err(ProxyError::Io)?;
+    socket.flush().await.map_err(ProxyError::Io)?;
+
+    let headers_read = read_headers(socket, buffer).await?;
+    let response = Response::new(headers_read.headers(buffer))?;
+
+    match response.status_code() {
+        ResponseStatusCode::Successful(_) => Ok(()),
+        // E.g. 407 if the proxy needs authentication or 403 if the port is not allowed
+        other => Err(ProxyError::Refused(other)),
+    }
+}
+
+// The tests against a real proxy are ignored by default. To run them, start a proxy on port
+// 8888 that can reach the internet, e.g. with tinyproxy, and then:
+//
+// cargo test -p http -- --ignored
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::error::ResponseError;
+    use embassy_futures::block_on;
+    use embedded_io_async::{ErrorKind, ErrorType};
+    use mock_embedded_io::{MockError, Source};
+    use std::net::TcpStream;
+
+    const PROXY: &str = "127.0.0.1:8888";
+
+    // Responds with what the source gives and keeps what is written
+    struct TestConnection {
+        source: Source,
+        written: std::vec::Vec<u8>,
+    }
+
+    impl TestConnection {
+        fn new(response: &[u8]) -> TestConnection {
+            TestConnection {
+                source: Source::new().data(response).closed(),
+                written: std::vec::Vec::new(),
+            }
+        }
+    }
+
+    impl ErrorType for TestConnection {
+        type Error = MockError;
+    }
+
+    impl Read for TestConnection {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.source.read(buf).await
+        }
+    }
+
+    impl Write for TestConnection {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    // Blocking std socket used as an async one, which is enough for the tests
+    struct TestSocket(TcpStream);
+
+    impl ErrorType for TestSocket {
+        type Error = ErrorKind;
+    }
+
+    impl Read for TestSocket {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            std::io::Read::read(&mut self.0, buf).map_err(|_| ErrorKind::Other)
+        }
+    }
+
+    impl Write for TestSocket {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            std::io::Write::write(&mut self.0, buf).map_err(|_| ErrorKind::Other)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            std::io::Write::flush(&mut self.0).map_err(|_| ErrorKind::Other)
+        }
+    }
+
+    #[test]
+    fn test_connect_tunnel() {
+        let mut connection = TestConnection::new(b"HTTP/1.1 200 Connection established\r\n\r\n");
+        let mut buffer = [0u8; 256];
+
+        let result = block_on(connect_tunnel(
+            &mut connection,
+            "stream.example.org",
+            443,
+            &mut buffer,
+        ));
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            connection.written,
+            b"CONNECT stream.example.org:443 HTTP/1.1\r\nHost: stream.example.org:443\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_connect_tunnel_refused() {
+        let mut connection = TestConnection::new(
+            b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic\r\n\r\n",
+        );
+        let mut buffer = [0u8; 256];
+
+        let result = block_on(connect_tunnel(
+            &mut connection,
+            "stream.example.org",
+            443,
+            &mut buffer,
+        ));
+
+        assert_eq!(
+            result,
+            Err(ProxyError::Refused(ResponseStatusCode::ClientError(407)))
+        );
+    }
+
+    #[test]
+    fn test_connect_tunnel_closed() {
+        let mut connection = TestConnection::new(b"HTTP/1.1 200 Connection");
+        let mut buffer = [0u8; 256];
+
+        let result = block_on(connect_tunnel(
+            &mut connection,
+            "stream.example.org",
+            443,
+            &mut buffer,
+        ));
+
+        assert_eq!(
+            result,
+            Err(ProxyError::Response(ResponseError::UnexpectedEof))
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a local proxy"]
+    fn test_absolute_form_through_proxy() {
+        let mut socket = TestSocket(TcpStream::connect(PROXY).unwrap());
+        let mut request = Request::new(Method::GET, "http://example.com/").unwrap();
+        request.host("example.com").unwrap();
+        request.header("Connection", "close").unwrap();
+
+        block_on(request.write_to(&mut socket)).unwrap();
+        let mut buffer = [0u8; 2048];
+        let headers_read = block_on(read_headers(&mut socket, &mut buffer)).unwrap();
+        let response = Response::new(headers_read.headers(&buffer)).unwrap();
+
+        assert_eq!(response.status_code, ResponseStatusCode::Successful(200));
+    }
+
+    #[test]
+    #[ignore = "needs a local proxy"]
+    fn test_connect_tunnel_through_proxy() {
+        let mut socket = TestSocket(TcpStream::connect(PROXY).unwrap());
+        let mut buffer = [0u8; 2048];
+
+        let result = block_on(connect_tunnel(&mut socket, "example.com", 443, &mut buffer));
+
+        assert_eq!(result, Ok(()));
+    }
+}
@@ -0,0 +1,85 @@
+//! Range requests, to read only part of a resource, e.g. to resume a download.
+//!
+//! A request with a `Range` header (see `Request::range`) is answered with
+//! `206 Partial Content` and a `Content-Range` header giving the part that is sent.
+//! Servers that do not support ranges ignore the header and send the whole resource.
+//!
+//! Only byte ranges are supported, as these are the only ones in use.
+
+/// The part of a resource sent in a `206 Partial Content` response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    /// The position of the first byte sent
+    pub start: u64,
+    /// The position of the last byte sent (inclusive, as in the header)
+    pub end: u64,
+    /// The length of the whole resource, if known
+    pub complete_length: Option<u64>,
+}
+
+impl ContentRange {
+    /// Parses the value of a `Content-Range` header, e.g. `bytes 1000-1999/5000`.
+    ///
+    /// Returns `None` if the value is invalid or has no range, as sent in a
+    /// `416 Range Not Satisfiable` response (`bytes */5000`).
+    pub fn parse(value: &str) -> Option<ContentRange> {
+        let (unit, range) = value.trim().split_once(' ')?;
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return None;
+        }
+
+        let (range, complete_length) = range.trim_start().split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+
+        let complete_length = match complete_length {
+            "*" => None,
+            complete_length => Some(complete_length.parse().ok()?),
+        };
+
+        let content_range = ContentRange {
+            start: start.parse().ok()?,
+            end: end.parse().ok()?,
+            complete_length,
+        };
+
+        let is_valid = content_range.start <= content_range.end
+            && complete_length.is_none_or(|length| content_range.end < length);
+        is_valid.then_some(content_range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ContentRange::parse("bytes 1000-1999/5000"),
+            Some(ContentRange {
+                start: 1000,
+                end: 1999,
+                complete_length: Some(5000)
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("bytes 0-0/*"),
+            Some(ContentRange {
+                start: 0,
+                end: 0,
+                complete_length: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        // Range not satisfiable
+        assert_eq!(ContentRange::parse("bytes */5000"), None);
+        assert_eq!(ContentRange::parse("items 0-9/10"), None);
+        assert_eq!(ContentRange::parse("bytes 10-9/100"), None);
+        assert_eq!(ContentRange::parse("bytes 0-100/100"), None);
+        assert_eq!(ContentRange::parse("bytes 0-99"), None);
+        assert_eq!(ContentRange::parse(""), None);
+    }
+}
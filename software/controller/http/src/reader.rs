@@ -0,0 +1,162 @@
+//! Reading the headers of a response from a connection.
+//!
+//! The headers are read in blocks, so normally some of the body is read in as well.
+//! These bytes are left in the buffer after the headers and have to be handled as the
+//! start of the body.
+
+use embedded_io_async::Read;
+
+use crate::error::ReadHeadersError;
+
+const HEADERS_END: &[u8] = b"\r\n\r\n";
+
+/// Where the headers, and the part of the body that has been read with them, are in the
+/// buffer given to `read_headers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadersRead {
+    /// The length of the headers, including the empty line that ends them.
+    pub headers_len: usize,
+    /// The number of body bytes that follow the headers in the buffer.
+    pub body_len: usize,
+}
+
+impl HeadersRead {
+    /// The headers in the buffer.
+    pub fn headers<'b>(&self, buffer: &'b [u8]) -> &'b [u8] {
+        &buffer[..self.headers_len]
+    }
+
+    /// The start of the body that was read in with the headers. This can be empty.
+    pub fn body<'b>(&self, buffer: &'b [u8]) -> &'b [u8] {
+        &buffer[self.headers_len..self.headers_len + self.body_len]
+    }
+}
+
+/// Reads the status line and headers of a response into the buffer.
+///
+/// Reading stops as soon as the empty line at the end of the headers has been read.
+/// Any body bytes read in the same block are kept in the buffer after the headers.
+pub async fn read_headers<R: Read>(
+    reader: &mut R,
+    buffer: &mut [u8],
+) -> Result<HeadersRead, ReadHeadersError<R::Error>> {
+    let mut len = 0;
+
+    while len < buffer.len() {
+        let n = reader
+            .read(&mut buffer[len..])
+            .await
+            .map_err(ReadHeadersError::Io)?;
+        if n == 0 {
+            return Err(ReadHeadersError::UnexpectedEof);
+        }
+
+        // The end of the headers can be split over reads
+        let search_start = len.saturating_sub(HEADERS_END.len() - 1);
+        len += n;
+
+        if let Some(pos) = buffer[search_start..len]
+            .windows(HEADERS_END.len())
+            .position(|w| w == HEADERS_END)
+        {
+            let headers_len = search_start + pos + HEADERS_END.len();
+            return Ok(HeadersRead {
+                headers_len,
+                body_len: len - headers_len,
+            });
+        }
+    }
+
+    Err(ReadHeadersError::BufferOverflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embassy_futures::block_on;
+    use embedded_io_async::ErrorKind;
+    use mock_embedded_io::{MockError, Source};
+
+    const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: audio/mpeg\r\n\r\nID3AUDIO";
+
+    #[test]
+    fn test_read_headers() {
+        let mut source = Source::new().data(RESPONSE);
+        let mut buffer = [0u8; 128];
+
+        let read = block_on(read_headers(&mut source, &mut buffer)).unwrap();
+
+        assert_eq!(
+            read.headers(&buffer),
+            b"HTTP/1.1 200 OK\r\nContent-Type: audio/mpeg\r\n\r\n"
+        );
+        assert_eq!(read.body(&buffer), b"ID3AUDIO");
+    }
+
+    #[test]
+    fn test_read_headers_in_pieces() {
+        for piece_len in 1..RESPONSE.len() {
+            let mut source = Source::new();
+            for piece in RESPONSE.chunks(piece_len) {
+                source = source.data(piece);
+            }
+            let mut buffer = [0u8; 128];
+
+            let read = block_on(read_headers(&mut source, &mut buffer)).unwrap();
+
+            assert_eq!(read.headers_len, RESPONSE.len() - 8);
+            // Only what has been read in with the last piece of the headers is kept
+            let body = read.body(&buffer);
+            assert!(b"ID3AUDIO".starts_with(body));
+        }
+    }
+
+    #[test]
+    fn test_read_headers_no_body() {
+        let mut source = Source::new().data(b"HTTP/1.1 204 No Content\r\n\r\n");
+        let mut buffer = [0u8; 128];
+
+        let read = block_on(read_headers(&mut source, &mut buffer)).unwrap();
+
+        assert_eq!(read.headers_len, 27);
+        assert_eq!(read.body(&buffer), b"");
+    }
+
+    #[test]
+    fn test_read_headers_eof() {
+        let mut source = Source::new()
+            .data(b"HTTP/1.1 200 OK\r\nServer: ng")
+            .closed();
+        let mut buffer = [0u8; 128];
+
+        let result = block_on(read_headers(&mut source, &mut buffer));
+
+        assert!(matches!(result, Err(ReadHeadersError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn test_read_headers_buffer_overflow() {
+        let mut source = Source::new().data(RESPONSE);
+        let mut buffer = [0u8; 32];
+
+        let result = block_on(read_headers(&mut source, &mut buffer));
+
+        assert!(matches!(result, Err(ReadHeadersError::BufferOverflow)));
+    }
+
+    #[test]
+    fn test_read_headers_io_error() {
+        let mut source = Source::new()
+            .data(b"HTTP/1.1 200 OK\r\n")
+            .error(MockError(ErrorKind::BrokenPipe));
+        let mut buffer = [0u8; 128];
+
+        let result = block_on(read_headers(&mut source, &mut buffer));
+
+        assert!(matches!(
+            result,
+            Err(ReadHeadersError::Io(MockError(ErrorKind::BrokenPipe)))
+        ));
+    }
+}
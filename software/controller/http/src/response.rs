@@ -1,16 +1,51 @@
-use heapless::{String, Vec};
-
 use crate::error::ResponseError;
+use crate::headers::Headers;
+use crate::range::ContentRange;
 
 // Max size for a url
 pub const MAX_URL_LEN: usize = 256;
 
+// The start of the status line sent by Shoutcast v1 servers, e.g. "ICY 200 OK"
+const ICY_STATUS_LINE_START: &[u8] = b"ICY ";
+
 /// This is limited for of a HTTP response that contains what is required for this project.
+///
+/// The values of the headers are looked up in `headers`, which keeps space for the headers
+/// read through the methods here.
 #[derive(Default, Clone)]
 pub struct Response {
     pub status_code: ResponseStatusCode,
-    pub location: Option<String<MAX_URL_LEN>>,
     pub size: usize, //TODO
+
+    /// Set if the server responded with an ICY status line (e.g. `ICY 200 OK`)
+    /// instead of an HTTP one. Only older Shoutcast servers do this.
+    pub icy_server: bool,
+    /// All the headers in the response (as far as there is space for them)
+    pub headers: Headers,
+}
+
+/// The transfer encoding of the body given in the `Transfer-Encoding` header.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferEncoding {
+    /// The body is sent as is
+    #[default]
+    Identity,
+    /// The body is sent in chunks. Use `ChunkedDecoder` to decode it.
+    Chunked,
+}
+
+impl TransferEncoding {
+    // The encodings are listed in the order they were applied, so only the
+    // last one matters for decoding the transfer
+    fn from_header_value(value: &[u8]) -> TransferEncoding {
+        let last_encoding = value.rsplit(|&b| b == b',').next().unwrap_or_default();
+
+        if last_encoding.trim_ascii().eq_ignore_ascii_case(b"chunked") {
+            TransferEncoding::Chunked
+        } else {
+            TransferEncoding::Identity
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
@@ -51,6 +86,11 @@ impl Response {
     // Function to handle header reading
     pub fn new(header_buffer: &[u8]) -> Result<Response, ResponseError> {
         let mut headers = [httparse::EMPTY_HEADER; 64];
+
+        if header_buffer.starts_with(ICY_STATUS_LINE_START) {
+            return Self::new_icy(header_buffer, &mut headers);
+        }
+
         let mut response = httparse::Response::new(&mut headers);
 
         let size = match response.parse(header_buffer)? {
@@ -60,40 +100,149 @@ impl Response {
             }
         };
 
-        let headers = response.headers;
         let code = ResponseStatusCode::from(response.code);
 
-        let redirect_location = headers
+        Ok(Self::from_headers(code, response.headers, size, false))
+    }
+
+    // Shoutcast v1 servers respond with a status line such as "ICY 200 OK" which httparse rejects.
+    // Apart from this the response has the same format as HTTP, so the status line is parsed
+    // here and the rest is left to httparse.
+    fn new_icy<'b>(
+        header_buffer: &'b [u8],
+        headers: &mut [httparse::Header<'b>],
+    ) -> Result<Response, ResponseError> {
+        let status_line_len = header_buffer
             .iter()
-            .filter_map(|h| {
-                if h.name.eq_ignore_ascii_case("location") {
-                    Some(h.value)
-                } else {
-                    None
+            .position(|&b| b == b'\n')
+            .ok_or(ResponseError::IncompleteHeaders)?
+            + 1;
+
+        let code = header_buffer[ICY_STATUS_LINE_START.len()..status_line_len]
+            .split(|&b| b == b' ' || b == b'\r' || b == b'\n')
+            .next()
+            .filter(|code| code.len() == 3)
+            .and_then(|code| core::str::from_utf8(code).ok())
+            .and_then(parse_number::<u16>)
+            .ok_or(ResponseError::HeaderParse(httparse::Error::Status))?;
+
+        let (size, headers) =
+            match httparse::parse_headers(&header_buffer[status_line_len..], headers)? {
+                httparse::Status::Complete((size, headers)) => (status_line_len + size, headers),
+                httparse::Status::Partial => {
+                    return Err(ResponseError::IncompleteHeaders);
                 }
-            })
-            .next();
-
-        let redirect_url = if let Some(redirect_location) = redirect_location {
-            let mut v = Vec::<u8, MAX_URL_LEN>::new();
-            v.extend_from_slice(redirect_location)
-                .map_err(|_| ResponseError::UrlParse)?;
-            let s = String::from_utf8(v)?;
-            Some(s)
-        } else {
-            None
-        };
+            };
+
+        Ok(Self::from_headers(
+            ResponseStatusCode::from(code),
+            headers,
+            size,
+            true,
+        ))
+    }
 
-        Ok(Response {
+    // Keeps the parsed headers
+    fn from_headers(
+        code: ResponseStatusCode,
+        headers: &[httparse::Header<'_>],
+        size: usize,
+        icy_server: bool,
+    ) -> Response {
+        Response {
             status_code: code,
-            location: redirect_url,
             size,
-        })
+            icy_server,
+            headers: Headers::from_parsed(headers),
+        }
     }
 
     pub fn status_code(&self) -> ResponseStatusCode {
         self.status_code.clone()
     }
+
+    /// Returns true for `304 Not Modified`, the response to a conditional request for a
+    /// resource that has not changed. This has no body.
+    pub fn is_not_modified(&self) -> bool {
+        self.status_code == ResponseStatusCode::Redirection(304)
+    }
+
+    /// Returns true for `206 Partial Content`, the response to a range request.
+    /// The part that is sent is given by `content_range`.
+    pub fn is_partial_content(&self) -> bool {
+        self.status_code == ResponseStatusCode::Successful(206)
+    }
+
+    /// The value of the first header with the name, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// The URL to go to for a redirection (`Location`).
+    pub fn location(&self) -> Option<&str> {
+        self.header("location").map(str::trim)
+    }
+
+    /// How the body is encoded for transfer.
+    pub fn transfer_encoding(&self) -> TransferEncoding {
+        self.header("transfer-encoding")
+            .map(|value| TransferEncoding::from_header_value(value.as_bytes()))
+            .unwrap_or_default()
+    }
+
+    /// The media type of the body, e.g. `audio/mpeg`. See `Headers::content_type`.
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.content_type()
+    }
+
+    /// The length of the body, if given.
+    pub fn content_length(&self) -> Option<usize> {
+        self.headers.content_length()
+    }
+
+    /// The part of the resource sent in the body. See `Headers::content_range`.
+    pub fn content_range(&self) -> Option<ContentRange> {
+        self.headers.content_range()
+    }
+
+    /// Returns true if the server supports range requests. See `Headers::accepts_ranges`.
+    pub fn accepts_ranges(&self) -> bool {
+        self.headers.accepts_ranges()
+    }
+
+    /// The number of audio bytes between ICY metadata blocks (`icy-metaint`).
+    /// This is only sent if the request has the header `Icy-MetaData: 1`.
+    pub fn icy_metaint(&self) -> Option<usize> {
+        // A metadata interval of 0 makes no sense so is treated as no metadata
+        self.header("icy-metaint")
+            .and_then(parse_number::<usize>)
+            .filter(|&metaint| metaint > 0)
+    }
+
+    /// The name of the station (`icy-name`).
+    pub fn icy_name(&self) -> Option<&str> {
+        self.header("icy-name")
+    }
+
+    /// The genre of the station (`icy-genre`).
+    pub fn icy_genre(&self) -> Option<&str> {
+        self.header("icy-genre")
+    }
+
+    /// The bit rate of the stream in kbit/s (`icy-br`).
+    pub fn icy_br(&self) -> Option<u16> {
+        self.header("icy-br").and_then(parse_number::<u16>)
+    }
+}
+
+// Parses the leading digits of a header value. Some servers send values
+// such as "128,128" for icy-br, so anything after the digits is ignored.
+fn parse_number<T: core::str::FromStr>(value: &str) -> Option<T> {
+    let value = value.trim();
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
 }
 
 #[cfg(test)]
@@ -112,8 +261,169 @@ mod tests {
         let response = r.unwrap();
 
         assert_eq!(response.status_code, ResponseStatusCode::Successful(200));
-        assert!(response.location.is_some());
-        assert_eq!(response.location.unwrap(), "http://redirect.com");
+        assert_eq!(response.content_type(), Some("text/html"));
+        assert_eq!(response.content_length(), Some(55743));
+        assert_eq!(
+            response.header("date"),
+            Some("Thu, 06 Dec 2018 17:37:18 GMT")
+        );
+        assert_eq!(response.location(), Some("http://redirect.com"));
+        assert!(response.icy_metaint().is_none());
+        assert!(response.icy_name().is_none());
+        assert_eq!(response.transfer_encoding(), TransferEncoding::Identity);
+    }
+
+    #[test]
+    fn test_not_modified_response() {
+        let response = Response::new(b"HTTP/1.1 304 Not Modified\r\nETag: \"1\"\r\n\r\n").unwrap();
+        assert!(response.is_not_modified());
+
+        let response = Response::new(b"HTTP/1.1 302 Found\r\nLocation: /\r\n\r\n").unwrap();
+        assert!(!response.is_not_modified());
+    }
+
+    #[test]
+    fn test_partial_content_response() {
+        let response = Response::new(
+            b"HTTP/1.1 206 Partial Content\r\nAccept-Ranges: bytes\r\nContent-Range: bytes 1000-4999/5000\r\nContent-Length: 4000\r\n\r\n",
+        )
+        .unwrap();
+
+        assert!(response.is_partial_content());
+        assert!(response.accepts_ranges());
+        assert_eq!(
+            response.content_range(),
+            Some(ContentRange {
+                start: 1000,
+                end: 4999,
+                complete_length: Some(5000)
+            })
+        );
+
+        let response = Response::new(b"HTTP/1.1 200 OK\r\nAccept-Ranges: none\r\n\r\n").unwrap();
+        assert!(!response.is_partial_content());
+        assert!(!response.accepts_ranges());
+        assert_eq!(response.content_range(), None);
+    }
+
+    #[test]
+    fn test_response_with_long_headers() {
+        // The cookie would take up the space of the headers after it, so is dropped
+        let cookie = "c".repeat(crate::headers::HEADERS_POOL_SIZE - 20);
+        let header_buffer = std::format!(
+            "HTTP/1.1 206 Partial Content\r\nSet-Cookie: {cookie}\r\nContent-Type: audio/mpeg\r\nContent-Length: 1000\r\nContent-Range: bytes 1000-1999/5000\r\nAccept-Ranges: bytes\r\n\r\n"
+        );
+
+        let response = Response::new(header_buffer.as_bytes()).unwrap();
+
+        assert_eq!(response.header("set-cookie"), None);
+        assert_eq!(response.content_type(), Some("audio/mpeg"));
+        assert_eq!(response.content_length(), Some(1000));
+        assert_eq!(
+            response.content_range().unwrap().complete_length,
+            Some(5000)
+        );
+        assert!(response.accepts_ranges());
+    }
+
+    #[test]
+    fn test_chunked_response() {
+        let header_buffer = include_bytes!("test_resources/chunked_response.txt");
+
+        let response = Response::new(header_buffer).unwrap();
+
+        assert_eq!(response.status_code, ResponseStatusCode::Successful(200));
+        assert_eq!(response.transfer_encoding(), TransferEncoding::Chunked);
+    }
+
+    #[test]
+    fn test_transfer_encoding_from_header_value() {
+        assert_eq!(
+            TransferEncoding::from_header_value(b"chunked"),
+            TransferEncoding::Chunked
+        );
+        assert_eq!(
+            TransferEncoding::from_header_value(b"gzip, Chunked"),
+            TransferEncoding::Chunked
+        );
+        assert_eq!(
+            TransferEncoding::from_header_value(b"chunked, gzip"),
+            TransferEncoding::Identity
+        );
+        assert_eq!(
+            TransferEncoding::from_header_value(b""),
+            TransferEncoding::Identity
+        );
+    }
+
+    #[test]
+    fn test_icy_response() {
+        let header_buffer = include_bytes!("test_resources/icecast_response.txt");
+
+        let response = Response::new(header_buffer).unwrap();
+
+        assert_eq!(response.status_code, ResponseStatusCode::Successful(200));
+        assert_eq!(response.header("Server"), Some("Icecast 2.4.0-kh15"));
+        assert_eq!(response.content_type(), Some("audio/mpeg"));
+        assert_eq!(response.content_length(), None);
+        assert_eq!(response.icy_metaint(), Some(16000));
+        assert_eq!(response.icy_name(), Some("SWR3"));
+        assert_eq!(response.icy_genre(), Some("Pop"));
+        assert_eq!(response.icy_br(), Some(128));
+        assert!(!response.icy_server);
+    }
+
+    #[test]
+    fn test_icy_status_line() {
+        let header_buffer = include_bytes!("test_resources/shoutcast_v1_response.txt");
+
+        let response = Response::new(header_buffer).unwrap();
+
+        assert!(response.icy_server);
+        assert_eq!(response.status_code, ResponseStatusCode::Successful(200));
+        assert_eq!(response.size, header_buffer.len());
+        assert_eq!(response.headers.len(), 9);
+        assert_eq!(response.header("icy-url"), Some("http://www.181.fm"));
+        assert_eq!(response.icy_metaint(), Some(8192));
+        assert_eq!(response.icy_name(), Some("181.FM - Classical Music"));
+        assert_eq!(response.icy_genre(), Some("Classical"));
+        assert_eq!(response.icy_br(), Some(128));
+    }
+
+    #[test]
+    fn test_icy_status_line_error() {
+        let header_buffer = include_bytes!("test_resources/shoutcast_v1_error_response.txt");
+
+        let response = Response::new(header_buffer).unwrap();
+
+        assert!(response.icy_server);
+        assert_eq!(response.status_code, ResponseStatusCode::ClientError(404));
+    }
+
+    #[test]
+    fn test_icy_status_line_invalid() {
+        let response = Response::new(b"ICY OK\r\n\r\n");
+        assert!(matches!(
+            response,
+            Err(ResponseError::HeaderParse(httparse::Error::Status))
+        ));
+
+        let response = Response::new(b"ICY 200 OK\r\nicy-br:128\r\n");
+        assert!(matches!(response, Err(ResponseError::IncompleteHeaders)));
+    }
+
+    #[test]
+    fn test_http_1_0_status_line() {
+        let header_buffer = include_bytes!("test_resources/http_1_0_redirect_response.txt");
+
+        let response = Response::new(header_buffer).unwrap();
+
+        assert!(!response.icy_server);
+        assert_eq!(response.status_code, ResponseStatusCode::Redirection(302));
+        assert_eq!(
+            response.location(),
+            Some("http://streams.example.com/classical.mp3")
+        );
     }
 
     #[test]
@@ -0,0 +1,587 @@
+//! A minimal HTTP server, e.g. to configure and control a device from a browser.
+//!
+//! Only what is needed for a small web page and JSON endpoints is supported: one request
+//! per connection, which is closed after the response, and request bodies given by
+//! `Content-Length` (no chunked requests).
+//!
+//! - `read_request` reads a request from a connection
+//! - `Router` finds what to do for the method and path of the request
+//! - `write_response` and `write_response_head` write the response
+//! - `JsonString` writes text as a JSON string
+
+use core::fmt::{self, Write as _};
+
+use embedded_io_async::{Read, Write};
+use heapless::{String, Vec};
+
+use crate::error::{RequestParseError, ServerError};
+use crate::headers::Headers;
+use crate::reader::read_headers;
+use crate::request::Method;
+
+/// The maximum number of headers in a request. Requests with more are rejected.
+pub const MAX_REQUEST_HEADERS: usize = 24;
+
+/// The maximum number of `{}` segments in a route.
+pub const MAX_PATH_PARAMS: usize = 4;
+
+/// A request received by the server.
+pub struct ServerRequest<'b> {
+    pub method: Method,
+    /// The path without the query, e.g. `/api/stations`
+    pub path: &'b str,
+    /// The query without the `?`, e.g. `page=2`
+    pub query: Option<&'b str>,
+    /// The headers of the request (as far as there is space for them)
+    pub headers: Headers,
+    /// The body, which is empty if the request has none
+    pub body: &'b [u8],
+}
+
+impl<'b> ServerRequest<'b> {
+    /// Parses a request. The buffer has to contain the whole request, i.e. the headers and
+    /// the body given by `Content-Length`.
+    pub fn parse(buffer: &'b [u8]) -> Result<ServerRequest<'b>, RequestParseError> {
+        let mut headers = [httparse::EMPTY_HEADER; MAX_REQUEST_HEADERS];
+        let mut request = httparse::Request::new(&mut headers);
+
+        let headers_len = match request.parse(buffer)? {
+            httparse::Status::Complete(headers_len) => headers_len,
+            httparse::Status::Partial => return Err(RequestParseError::Incomplete),
+        };
+
+        let method = request
+            .method
+            .and_then(parse_method)
+            .ok_or(RequestParseError::UnsupportedMethod)?;
+        let target = request.path.ok_or(RequestParseError::Invalid)?;
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target, None),
+        };
+
+        let headers = Headers::from_parsed(request.headers);
+        let body_len = body_len(&headers)?;
+        let body = buffer
+            .get(headers_len..headers_len + body_len)
+            .ok_or(RequestParseError::Incomplete)?;
+
+        Ok(ServerRequest {
+            method,
+            path,
+            query,
+            headers,
+            body,
+        })
+    }
+
+    /// The body as text.
+    pub fn body_str(&self) -> Result<&'b str, RequestParseError> {
+        core::str::from_utf8(self.body).map_err(|_| RequestParseError::Invalid)
+    }
+}
+
+/// Reads a request from a connection into the buffer, which has to be large enough for
+/// the headers and the body.
+pub async fn read_request<'b, R: Read>(
+    reader: &mut R,
+    buffer: &'b mut [u8],
+) -> Result<ServerRequest<'b>, ServerError<R::Error>> {
+    let headers_read = read_headers(reader, buffer).await?;
+
+    // Only the length of the body is needed, so the headers are parsed properly afterwards
+    let body_len = {
+        let mut headers = [httparse::EMPTY_HEADER; MAX_REQUEST_HEADERS];
+        let mut request = httparse::Request::new(&mut headers);
+        request.parse(headers_read.headers(buffer))?;
+        body_len(&Headers::from_parsed(request.headers))?
+    };
+
+    let request_len = headers_read.headers_len + body_len;
+    if request_len > buffer.len() {
+        return Err(ServerError::RequestTooLarge);
+    }
+
+    // Part of the body may have been read in with the headers
+    let mut len = headers_read.headers_len + headers_read.body_len;
+    while len < request_len {
+        match reader
+            .read(&mut buffer[len..request_len])
+            .await
+            .map_err(ServerError::Io)?
+        {
+            0 => return Err(ServerError::UnexpectedEof),
+            n => len += n,
+        }
+    }
+
+    let buffer: &'b [u8] = buffer;
+    Ok(ServerRequest::parse(&buffer[..request_len])?)
+}
+
+// The methods have the same names as in the request line
+fn parse_method(name: &str) -> Option<Method> {
+    let method = match name {
+        "GET" => Method::GET,
+        "POST" => Method::POST,
+        "PUT" => Method::PUT,
+        "DELETE" => Method::DELETE,
+        "PATCH" => Method::PATCH,
+        "HEAD" => Method::HEAD,
+        "OPTIONS" => Method::OPTIONS,
+        "CONNECT" => Method::CONNECT,
+        _ => return None,
+    };
+    Some(method)
+}
+
+fn body_len(headers: &Headers) -> Result<usize, RequestParseError> {
+    if headers.contains("transfer-encoding") {
+        return Err(RequestParseError::UnsupportedTransferEncoding);
+    }
+    match headers.get("content-length") {
+        Some(_) => headers.content_length().ok_or(RequestParseError::Invalid),
+        None => Ok(0),
+    }
+}
+
+/// Why a request has no route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteError {
+    /// No route has the path (404)
+    NotFound,
+    /// There are routes with the path, but not with the method (405)
+    MethodNotAllowed,
+}
+
+/// The segments of the path matched by the `{}` segments of a route.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PathParams<'p>(Vec<&'p str, MAX_PATH_PARAMS>);
+
+impl<'p> PathParams<'p> {
+    /// The segment matched by the `{}` with the index, counting from 0.
+    pub fn get(&self, index: usize) -> Option<&'p str> {
+        self.0.get(index).copied()
+    }
+
+    /// The segment matched by the `{}` with the index as a number (or anything else that
+    /// can be parsed). Returns `None` if it cannot be parsed.
+    pub fn parse<T: core::str::FromStr>(&self, index: usize) -> Option<T> {
+        self.get(index).and_then(|param| param.parse().ok())
+    }
+}
+
+/// Finds the route for the method and path of a request.
+///
+/// A route is a method, a path pattern and a value, normally an enum saying what to do.
+/// A segment of the pattern given as `{}` matches any single segment of the path, e.g.
+/// `/api/stations/{}` matches `/api/stations/12`. The routes are tried in order.
+pub struct Router<'r, T> {
+    routes: &'r [(Method, &'r str, T)],
+}
+
+impl<'r, T: Copy> Router<'r, T> {
+    pub const fn new(routes: &'r [(Method, &'r str, T)]) -> Router<'r, T> {
+        Router { routes }
+    }
+
+    /// Returns the value of the route and the segments matched by `{}`.
+    pub fn route<'p>(
+        &self,
+        method: Method,
+        path: &'p str,
+    ) -> Result<(T, PathParams<'p>), RouteError> {
+        let mut path_found = false;
+
+        for (route_method, pattern, value) in self.routes {
+            if let Some(params) = match_path(pattern, path) {
+                if *route_method == method {
+                    return Ok((*value, params));
+                }
+                path_found = true;
+            }
+        }
+
+        Err(if path_found {
+            RouteError::MethodNotAllowed
+        } else {
+            RouteError::NotFound
+        })
+    }
+}
+
+// A trailing '/' is ignored, so "/api/stations/" matches "/api/stations"
+fn match_path<'p>(pattern: &str, path: &'p str) -> Option<PathParams<'p>> {
+    let trim = |s: &'p str| -> &'p str {
+        match s.strip_suffix('/') {
+            Some(trimmed) if !trimmed.is_empty() => trimmed,
+            _ => s,
+        }
+    };
+
+    let mut pattern_segments = pattern.split('/');
+    let mut path_segments = trim(path).split('/');
+    let mut params = PathParams::default();
+
+    loop {
+        match (pattern_segments.next(), path_segments.next()) {
+            (None, None) => return Some(params),
+            (Some("{}"), Some(segment)) if !segment.is_empty() => {
+                params.0.push(segment).ok()?;
+            }
+            (Some(expected), Some(segment)) if expected == segment => (),
+            _ => return None,
+        }
+    }
+}
+
+/// The status of a response sent by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    pub code: u16,
+    pub reason: &'static str,
+}
+
+impl Status {
+    pub const OK: Status = Status::new(200, "OK");
+    pub const NO_CONTENT: Status = Status::new(204, "No Content");
+    pub const BAD_REQUEST: Status = Status::new(400, "Bad Request");
+    pub const NOT_FOUND: Status = Status::new(404, "Not Found");
+    pub const METHOD_NOT_ALLOWED: Status = Status::new(405, "Method Not Allowed");
+    pub const CONTENT_TOO_LARGE: Status = Status::new(413, "Content Too Large");
+    pub const INTERNAL_SERVER_ERROR: Status = Status::new(500, "Internal Server Error");
+    pub const NOT_IMPLEMENTED: Status = Status::new(501, "Not Implemented");
+    pub const SERVICE_UNAVAILABLE: Status = Status::new(503, "Service Unavailable");
+
+    pub const fn new(code: u16, reason: &'static str) -> Status {
+        Status { code, reason }
+    }
+}
+
+impl From<RouteError> for Status {
+    fn from(error: RouteError) -> Self {
+        match error {
+            RouteError::NotFound => Status::NOT_FOUND,
+            RouteError::MethodNotAllowed => Status::METHOD_NOT_ALLOWED,
+        }
+    }
+}
+
+/// Writes a whole response. The content type is only sent if the body is not empty.
+pub async fn write_response<W: Write>(
+    writer: &mut W,
+    status: Status,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), W::Error> {
+    let content_type = if body.is_empty() { "" } else { content_type };
+    write_response_head(writer, status, content_type, Some(body.len())).await?;
+    writer.write_all(body).await
+}
+
+/// Writes the status line and headers of a response. The body is then written by the
+/// caller, e.g. in parts if it is too large for a buffer.
+///
+/// If the length of the body is not given, the body ends when the connection is closed.
+/// In all cases the client is told that the connection is closed after the response.
+pub async fn write_response_head<W: Write>(
+    writer: &mut W,
+    status: Status,
+    content_type: &str,
+    content_length: Option<usize>,
+) -> Result<(), W::Error> {
+    // The numbers have at most 20 digits, so this always fits
+    let mut head = String::<64>::new();
+    let _ = write!(head, "HTTP/1.1 {} ", status.code);
+
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(status.reason.as_bytes()).await?;
+    writer.write_all(b"\r\n").await?;
+
+    if !content_type.is_empty() {
+        writer.write_all(b"Content-Type: ").await?;
+        writer.write_all(content_type.as_bytes()).await?;
+        writer.write_all(b"\r\n").await?;
+    }
+
+    if let Some(content_length) = content_length {
+        head.clear();
+        let _ = write!(head, "Content-Length: {}\r\n", content_length);
+        writer.write_all(head.as_bytes()).await?;
+    }
+
+    writer.write_all(b"Connection: close\r\n\r\n").await
+}
+
+/// Displays text as a JSON string, i.e. in quotes with the characters that need it escaped.
+pub struct JsonString<'a>(pub &'a str);
+
+impl fmt::Display for JsonString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embassy_futures::block_on;
+    use embedded_io_async::ErrorKind;
+    use mock_embedded_io::{MockError, Source};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Route {
+        Index,
+        Stations,
+        SelectStation,
+        Volume,
+        SetVolume,
+    }
+
+    const ROUTER: Router<'static, Route> = Router::new(&[
+        (Method::GET, "/", Route::Index),
+        (Method::GET, "/api/stations", Route::Stations),
+        (Method::POST, "/api/stations/{}", Route::SelectStation),
+        (Method::GET, "/api/volume", Route::Volume),
+        (Method::PUT, "/api/volume", Route::SetVolume),
+    ]);
+
+    // What has been written into the buffer, given what has not been written to
+    fn written(buffer: &[u8], unwritten: usize) -> &str {
+        core::str::from_utf8(&buffer[..buffer.len() - unwritten]).unwrap()
+    }
+
+    #[test]
+    fn test_parse_get() {
+        let request = ServerRequest::parse(
+            b"GET /api/stations?page=2 HTTP/1.1\r\nHost: radio.local\r\nAccept: application/json\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(request.method, Method::GET);
+        assert_eq!(request.path, "/api/stations");
+        assert_eq!(request.query, Some("page=2"));
+        assert_eq!(request.headers.get("accept"), Some("application/json"));
+        assert_eq!(request.body, b"");
+    }
+
+    #[test]
+    fn test_parse_put() {
+        let request = ServerRequest::parse(
+            b"PUT /api/volume HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\n75",
+        )
+        .unwrap();
+
+        assert_eq!(request.method, Method::PUT);
+        assert_eq!(request.query, None);
+        assert_eq!(request.body_str(), Ok("75"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |request: &[u8]| ServerRequest::parse(request).err();
+
+        assert_eq!(
+            parse(b"GET /api/volume HTTP/1.1\r\nHost: radio"),
+            Some(RequestParseError::Incomplete)
+        );
+        assert_eq!(
+            parse(b"PUT /api/volume HTTP/1.1\r\nContent-Length: 3\r\n\r\n75"),
+            Some(RequestParseError::Incomplete)
+        );
+        assert_eq!(
+            parse(b"BREW /pot HTTP/1.1\r\n\r\n"),
+            Some(RequestParseError::UnsupportedMethod)
+        );
+        assert_eq!(
+            parse(b"PUT /api/volume HTTP/1.1\r\nContent-Length: many\r\n\r\n"),
+            Some(RequestParseError::Invalid)
+        );
+        assert_eq!(
+            parse(b"PUT /api/volume HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Some(RequestParseError::UnsupportedTransferEncoding)
+        );
+        assert_eq!(
+            parse(b"\x16\x03\x01\x02\x00\x01\x00\x01\xfc\x03\x03"),
+            Some(RequestParseError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_read_request() {
+        // The body comes after the headers in a separate read
+        let mut source = Source::new()
+            .data(b"POST /api/stations/3 HTTP/1.1\r\nContent-Length: 5\r\n\r\n")
+            .data(b"he")
+            .data(b"llo");
+        let mut buffer = [0u8; 256];
+
+        let request = block_on(read_request(&mut source, &mut buffer)).unwrap();
+
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(request.path, "/api/stations/3");
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[test]
+    fn test_read_request_errors() {
+        let mut buffer = [0u8; 64];
+
+        let mut source =
+            Source::new().data(b"PUT /api/volume HTTP/1.1\r\nContent-Length: 100\r\n\r\n");
+        let result = block_on(read_request(&mut source, &mut buffer));
+        assert!(matches!(result, Err(ServerError::RequestTooLarge)));
+
+        let mut source = Source::new()
+            .data(b"PUT /api/volume HTTP/1.1\r\nContent-Length: 2\r\n\r\n7")
+            .closed();
+        let result = block_on(read_request(&mut source, &mut buffer));
+        assert!(matches!(result, Err(ServerError::UnexpectedEof)));
+
+        let mut source = Source::new()
+            .data(b"GET / HTTP/1.1\r\n")
+            .error(MockError(ErrorKind::ConnectionReset));
+        let result = block_on(read_request(&mut source, &mut buffer));
+        assert!(matches!(
+            result,
+            Err(ServerError::Io(MockError(ErrorKind::ConnectionReset)))
+        ));
+    }
+
+    #[test]
+    fn test_route() {
+        assert_eq!(
+            ROUTER.route(Method::GET, "/"),
+            Ok((Route::Index, PathParams::default()))
+        );
+        assert_eq!(
+            ROUTER.route(Method::GET, "/api/stations").unwrap().0,
+            Route::Stations
+        );
+        assert_eq!(
+            ROUTER.route(Method::GET, "/api/stations/").unwrap().0,
+            Route::Stations
+        );
+        assert_eq!(
+            ROUTER.route(Method::PUT, "/api/volume").unwrap().0,
+            Route::SetVolume
+        );
+
+        let (route, params) = ROUTER.route(Method::POST, "/api/stations/12").unwrap();
+        assert_eq!(route, Route::SelectStation);
+        assert_eq!(params.get(0), Some("12"));
+        assert_eq!(params.parse::<usize>(0), Some(12));
+        assert_eq!(params.get(1), None);
+    }
+
+    #[test]
+    fn test_route_errors() {
+        assert_eq!(
+            ROUTER.route(Method::GET, "/api/presets").err(),
+            Some(RouteError::NotFound)
+        );
+        assert_eq!(
+            ROUTER.route(Method::POST, "/api/stations/").err(),
+            Some(RouteError::MethodNotAllowed)
+        );
+        assert_eq!(
+            ROUTER.route(Method::POST, "/api/stations/1/2").err(),
+            Some(RouteError::NotFound)
+        );
+        assert_eq!(
+            ROUTER.route(Method::DELETE, "/api/volume").err(),
+            Some(RouteError::MethodNotAllowed)
+        );
+        assert_eq!(
+            ROUTER
+                .route(Method::POST, "/api/stations/x")
+                .unwrap()
+                .1
+                .parse::<usize>(0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_write_response() {
+        let mut buffer = [0u8; 256];
+        let mut writer = &mut buffer[..];
+
+        block_on(write_response(
+            &mut writer,
+            Status::OK,
+            "application/json",
+            b"{\"volume\":75}",
+        ))
+        .unwrap();
+
+        let unwritten = writer.len();
+        assert_eq!(
+            written(&buffer, unwritten),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 13\r\nConnection: close\r\n\r\n{\"volume\":75}"
+        );
+    }
+
+    #[test]
+    fn test_write_response_without_body() {
+        let mut buffer = [0u8; 256];
+        let mut writer = &mut buffer[..];
+
+        block_on(write_response(
+            &mut writer,
+            RouteError::NotFound.into(),
+            "text/plain",
+            b"",
+        ))
+        .unwrap();
+
+        let unwritten = writer.len();
+        assert_eq!(
+            written(&buffer, unwritten),
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_write_response_head() {
+        let mut buffer = [0u8; 256];
+        let mut writer = &mut buffer[..];
+
+        block_on(write_response_head(
+            &mut writer,
+            Status::OK,
+            "text/html; charset=utf-8",
+            None,
+        ))
+        .unwrap();
+
+        let unwritten = writer.len();
+        assert_eq!(
+            written(&buffer, unwritten),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(format!("{}", JsonString("Radio 1")), "\"Radio 1\"");
+        assert_eq!(
+            format!("{}", JsonString("\"Quoted\" \\ Path\nNext\u{1}")),
+            "\"\\\"Quoted\\\" \\\\ Path\\nNext\\u0001\""
+        );
+        assert_eq!(format!("{}", JsonString("Café")), "\"Café\"");
+    }
+}
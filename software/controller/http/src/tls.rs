@@ -0,0 +1,358 @@
+//! HTTPS connections using [embedded-tls](https://crates.io/crates/embedded-tls).
+//!
+//! A `Connection` is either a plain connection or a TLS connection over a socket, so
+//! that the same code can handle `http://` and `https://` URLs.
+//!
+//! Note that embedded-tls only supports TLS 1.3.
+
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+use embedded_tls::webpki::CertVerifier;
+use embedded_tls::{
+    Aes128GcmSha256, Certificate, NoClock, NoVerify, TlsConfig, TlsConnection, TlsContext,
+};
+
+pub use embedded_tls::TlsError;
+use rand_core::{CryptoRng, RngCore};
+
+/// The size of the buffer needed to read TLS records. This has to hold the largest
+/// possible record.
+pub const TLS_READ_BUFFER_SIZE: usize = 16640;
+
+/// The size of the buffer used to write TLS records. This only has to hold the
+/// requests sent, so can be a lot smaller than the read buffer.
+pub const TLS_WRITE_BUFFER_SIZE: usize = 4096;
+
+// The maximum size of a certificate that can be verified
+const MAX_CERTIFICATE_LEN: usize = 4096;
+
+/// How the certificate of the server is verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateVerification<'a> {
+    /// The certificate is not verified. The connection is encrypted, but the server
+    /// could be anyone.
+    None,
+    /// The certificate has to be signed by the certificate authority, which is given
+    /// as a DER encoded X.509 certificate.
+    ///
+    /// The validity period of the certificate is not checked as there is no clock.
+    CertificateAuthority(&'a [u8]),
+}
+
+impl CertificateVerification<'_> {
+    /// Whether the server is known to be the one asked for, so that credentials can be
+    /// sent to it.
+    pub const fn is_verified(&self) -> bool {
+        matches!(self, CertificateVerification::CertificateAuthority(_))
+    }
+}
+
+/// The errors of a `Connection`.
+#[derive(Debug, Clone, Copy)]
+pub enum ConnectionError<E> {
+    /// Error from the socket of a plain connection
+    Io(E),
+    /// Error from a TLS connection
+    Tls(TlsError),
+}
+
+impl<E: embedded_io_async::Error> embedded_io_async::Error for ConnectionError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ConnectionError::Io(error) => error.kind(),
+            ConnectionError::Tls(error) => error.kind(),
+        }
+    }
+}
+
+/// A connection to a server, which is encrypted for HTTPS.
+///
+/// The socket is normally given as a mutable reference, so that it can still be
+/// closed or aborted once the connection is no longer needed.
+// Without a heap the TLS connection cannot be boxed. The plain connection is used for the
+// same tasks, which have the space for both.
+#[allow(clippy::large_enum_variant)]
+pub enum Connection<'b, S: Read + Write> {
+    Plain(S),
+    Tls(TlsConnection<'b, S, Aes128GcmSha256>),
+}
+
+impl<'b, S: Read + Write> Connection<'b, S> {
+    /// Uses the (connected) socket without encryption.
+    pub fn plain(socket: S) -> Connection<'b, S> {
+        Connection::Plain(socket)
+    }
+
+    /// Opens a TLS connection over the (connected) socket.
+    ///
+    /// The server name is sent to the server (SNI) and used to verify the certificate.
+    /// The buffers should be `TLS_READ_BUFFER_SIZE` and `TLS_WRITE_BUFFER_SIZE` long.
+    pub async fn tls<R: CryptoRng + RngCore>(
+        socket: S,
+        server_name: &str,
+        read_buffer: &'b mut [u8],
+        write_buffer: &'b mut [u8],
+        verification: CertificateVerification<'_>,
+        mut rng: R,
+    ) -> Result<Connection<'b, S>, TlsError> {
+        let mut connection = TlsConnection::new(socket, read_buffer, write_buffer);
+        let config = TlsConfig::new().with_server_name(server_name);
+
+        match verification {
+            CertificateVerification::None => {
+                connection
+                    .open::<R, NoVerify>(TlsContext::new(&config, &mut rng))
+                    .await?;
+            }
+            CertificateVerification::CertificateAuthority(ca) => {
+                let config = config.with_ca(Certificate::X509(ca));
+                connection
+                    .open::<R, CertVerifier<Aes128GcmSha256, NoClock, MAX_CERTIFICATE_LEN>>(
+                        TlsContext::new(&config, &mut rng),
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(Connection::Tls(connection))
+    }
+
+    /// Returns true if the connection is encrypted.
+    pub fn is_tls(&self) -> bool {
+        matches!(self, Connection::Tls(_))
+    }
+}
+
+impl<S: Read + Write> ErrorType for Connection<'_, S> {
+    type Error = ConnectionError<S::Error>;
+}
+
+impl<S: Read + Write> Read for Connection<'_, S> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match self {
+            Connection::Plain(socket) => socket.read(buf).await.map_err(ConnectionError::Io),
+            Connection::Tls(connection) => match connection.read(buf).await {
+                // The server closing the connection is the end of the data, as for a plain socket
+                Err(TlsError::ConnectionClosed) => Ok(0),
+                result => result.map_err(ConnectionError::Tls),
+            },
+        }
+    }
+}
+
+impl<S: Read + Write> Write for Connection<'_, S> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        match self {
+            Connection::Plain(socket) => socket.write(buf).await.map_err(ConnectionError::Io),
+            Connection::Tls(connection) => {
+                connection.write(buf).await.map_err(ConnectionError::Tls)
+            }
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        match self {
+            Connection::Plain(socket) => socket.flush().await.map_err(ConnectionError::Io),
+            Connection::Tls(connection) => connection.flush().await.map_err(ConnectionError::Tls),
+        }
+    }
+}
+
+// Most of these tests need a local TLS 1.3 server with a self-signed certificate, so they are
+// ignored by default. To run them:
+//
+// openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes -days 30 \
+//     -subj /CN=localhost -keyout key.pem -out cert.pem
+// openssl x509 -in cert.pem -outform der -out cert.der
+// openssl s_server -accept 4443 -tls1_3 -cert cert.pem -key key.pem -www
+//
+// TLS_TEST_CA=cert.der cargo test -p http --features tls -- --ignored
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embassy_futures::block_on;
+    use std::hash::BuildHasher;
+    use std::net::TcpStream;
+
+    const SERVER: &str = "127.0.0.1:4443";
+
+    // xorshift seeded with the random keys std uses for hashing. Only good enough for tests.
+    struct TestRng(u64);
+
+    impl TestRng {
+        fn new() -> TestRng {
+            TestRng(std::collections::hash_map::RandomState::new().hash_one(0) | 1)
+        }
+    }
+
+    impl RngCore for TestRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                let random = self.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&random[..chunk.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for TestRng {}
+
+    // Blocking std socket used as an async one, which is enough for the tests
+    struct TestSocket(TcpStream);
+
+    impl ErrorType for TestSocket {
+        type Error = ErrorKind;
+    }
+
+    impl Read for TestSocket {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            std::io::Read::read(&mut self.0, buf).map_err(|_| ErrorKind::Other)
+        }
+    }
+
+    impl Write for TestSocket {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            std::io::Write::write(&mut self.0, buf).map_err(|_| ErrorKind::Other)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            std::io::Write::flush(&mut self.0).map_err(|_| ErrorKind::Other)
+        }
+    }
+
+    // Socket for a server that closes the connection straight away, keeping what is sent to it
+    #[derive(Default)]
+    struct ClosedSocket {
+        written: std::vec::Vec<u8>,
+    }
+
+    impl ErrorType for ClosedSocket {
+        type Error = ErrorKind;
+    }
+
+    impl Read for ClosedSocket {
+        async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+            Ok(0)
+        }
+    }
+
+    impl Write for ClosedSocket {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_is_verified() {
+        assert!(!CertificateVerification::None.is_verified());
+        assert!(CertificateVerification::CertificateAuthority(&[]).is_verified());
+    }
+
+    #[test]
+    fn test_tls_handshake_starts() {
+        let ca = include_bytes!("test_resources/other_ca.der");
+        for verification in [
+            CertificateVerification::None,
+            CertificateVerification::CertificateAuthority(ca),
+        ] {
+            let mut socket = ClosedSocket::default();
+            let mut read_buffer = [0u8; TLS_READ_BUFFER_SIZE];
+            let mut write_buffer = [0u8; TLS_WRITE_BUFFER_SIZE];
+
+            let result = block_on(Connection::tls(
+                &mut socket,
+                "radio.example.com",
+                &mut read_buffer,
+                &mut write_buffer,
+                verification,
+                TestRng::new(),
+            ));
+
+            // The server went away during the handshake
+            assert!(result.is_err());
+            // The client hello is a handshake record, which names the server
+            assert_eq!(socket.written[0], 0x16);
+            assert!(socket
+                .written
+                .windows(b"radio.example.com".len())
+                .any(|window| window == b"radio.example.com"));
+        }
+    }
+
+    async fn get(verification: CertificateVerification<'_>) -> Result<std::vec::Vec<u8>, TlsError> {
+        let socket = TestSocket(TcpStream::connect(SERVER).unwrap());
+        let mut read_buffer = [0u8; TLS_READ_BUFFER_SIZE];
+        let mut write_buffer = [0u8; TLS_WRITE_BUFFER_SIZE];
+
+        let mut connection = Connection::tls(
+            socket,
+            "localhost",
+            &mut read_buffer,
+            &mut write_buffer,
+            verification,
+            TestRng::new(),
+        )
+        .await?;
+        assert!(connection.is_tls());
+
+        connection
+            .write_all(b"GET / HTTP/1.0\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        connection.flush().await.unwrap();
+
+        let mut response = std::vec::Vec::new();
+        let mut buf = [0u8; 512];
+        loop {
+            match connection.read(&mut buf).await.unwrap() {
+                0 => break,
+                n => response.extend_from_slice(&buf[..n]),
+            }
+        }
+        Ok(response)
+    }
+
+    #[test]
+    #[ignore = "needs a local TLS server"]
+    fn test_tls_without_verification() {
+        let response = block_on(get(CertificateVerification::None)).unwrap();
+        assert!(response.starts_with(b"HTTP/1.0 200 ok"));
+    }
+
+    #[test]
+    #[ignore = "needs a local TLS server"]
+    fn test_tls_with_verification() {
+        let ca = std::fs::read(std::env::var("TLS_TEST_CA").unwrap()).unwrap();
+        let response = block_on(get(CertificateVerification::CertificateAuthority(&ca))).unwrap();
+        assert!(response.starts_with(b"HTTP/1.0 200 ok"));
+    }
+
+    #[test]
+    #[ignore = "needs a local TLS server"]
+    fn test_tls_with_wrong_certificate_authority() {
+        // Any certificate other than that of the server
+        let ca = include_bytes!("test_resources/other_ca.der");
+        let result = block_on(get(CertificateVerification::CertificateAuthority(ca)));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,432 @@
+//! Extracting the audio from an MPEG transport stream, as used for the segments of HLS streams.
+//!
+//! A transport stream is made of 188 byte packets, each belonging to a stream given by its
+//! packet id (PID). The program association table (PAT, PID 0) gives the PID of the program
+//! map table (PMT), which gives the PIDs and types of the streams of the program. The packets
+//! of the audio stream carry PES packets, whose payload is the audio for the decoder
+//! (e.g. AAC in ADTS frames).
+//!
+//! Only the first program and its first MP3 or AAC stream are used. The tables are expected
+//! to fit in a single packet, which they do for audio.
+
+use core::ops::Range;
+
+use crate::content_type::AudioCodec;
+
+/// The length of a transport stream packet.
+pub const TS_PACKET_LEN: usize = 188;
+
+const SYNC_BYTE: u8 = 0x47;
+const PAT_PID: u16 = 0x0000;
+const PAT_TABLE_ID: u8 = 0x00;
+const PMT_TABLE_ID: u8 = 0x02;
+
+/// Separates the audio from the other data in a transport stream.
+pub struct TsDemux {
+    packet: [u8; TS_PACKET_LEN],
+    len: usize,
+    pmt_pid: Option<u16>,
+    audio_pid: Option<u16>,
+    codec: Option<AudioCodec>,
+}
+
+impl TsDemux {
+    pub fn new() -> TsDemux {
+        TsDemux {
+            packet: [0; TS_PACKET_LEN],
+            len: 0,
+            pmt_pid: None,
+            audio_pid: None,
+            codec: None,
+        }
+    }
+
+    /// The codec of the audio, once the stream has been found.
+    pub fn codec(&self) -> Option<AudioCodec> {
+        self.codec
+    }
+
+    /// Reads the data up to the end of the next packet with audio and returns the audio.
+    ///
+    /// The data is advanced past what has been read. Returns `None` once all of it has been
+    /// read. Packets can be split across the data given in different calls.
+    pub fn next_audio(&mut self, data: &mut &[u8]) -> Option<&[u8]> {
+        while !data.is_empty() {
+            // Look for the start of a packet, which is also how the stream is synchronised
+            // again if data has been lost
+            if self.len == 0 {
+                match data.iter().position(|&b| b == SYNC_BYTE) {
+                    Some(start) => *data = &data[start..],
+                    None => {
+                        *data = &[];
+                        return None;
+                    }
+                }
+            }
+
+            let n = (TS_PACKET_LEN - self.len).min(data.len());
+            self.packet[self.len..self.len + n].copy_from_slice(&data[..n]);
+            self.len += n;
+            *data = &data[n..];
+
+            if self.len == TS_PACKET_LEN {
+                self.len = 0;
+                if let Some(audio) = self.read_packet() {
+                    return Some(&self.packet[audio]);
+                }
+            }
+        }
+
+        None
+    }
+
+    // Returns where the audio is in the packet, if it has any
+    fn read_packet(&mut self) -> Option<Range<usize>> {
+        let packet = &self.packet;
+
+        let has_error = packet[1] & 0x80 != 0;
+        let is_unit_start = packet[1] & 0x40 != 0;
+        let pid = (u16::from(packet[1] & 0x1F) << 8) | u16::from(packet[2]);
+        let adaptation_field_control = (packet[3] >> 4) & 0x03;
+
+        let mut start = 4;
+        if adaptation_field_control & 0x02 != 0 {
+            start += 1 + usize::from(packet[4]);
+        }
+        let has_payload = adaptation_field_control & 0x01 != 0;
+        if has_error || !has_payload || start >= TS_PACKET_LEN {
+            return None;
+        }
+        let payload = &packet[start..];
+
+        if pid == PAT_PID {
+            if is_unit_start {
+                self.pmt_pid = parse_pat(payload).or(self.pmt_pid);
+            }
+            None
+        } else if Some(pid) == self.pmt_pid {
+            if let Some((audio_pid, codec)) = parse_pmt(payload).filter(|_| is_unit_start) {
+                self.audio_pid = Some(audio_pid);
+                self.codec = Some(codec);
+            }
+            None
+        } else if Some(pid) == self.audio_pid {
+            if is_unit_start {
+                // Skip the header of the PES packet
+                if payload.len() < 9 || payload[..3] != [0x00, 0x00, 0x01] {
+                    return None;
+                }
+                start += 9 + usize::from(payload[8]);
+            }
+            (start < TS_PACKET_LEN).then_some(start..TS_PACKET_LEN)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for TsDemux {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Returns the data of a table section, after the common header and without the CRC
+fn section(payload: &[u8], table_id: u8) -> Option<&[u8]> {
+    let pointer = usize::from(*payload.first()?);
+    let section = payload.get(1 + pointer..)?;
+    if *section.first()? != table_id {
+        return None;
+    }
+
+    // The length counts from after it and includes the 4 byte CRC
+    let length = (usize::from(section.get(1)? & 0x0F) << 8) | usize::from(*section.get(2)?);
+    section.get(8..(3 + length).checked_sub(4)?)
+}
+
+// Returns the PID of the PMT of the first program
+fn parse_pat(payload: &[u8]) -> Option<u16> {
+    let (programs, _) = section(payload, PAT_TABLE_ID)?.as_chunks::<4>();
+    programs
+        .iter()
+        // Program 0 is the network information table
+        .find(|program| program[0] != 0 || program[1] != 0)
+        .map(|program| (u16::from(program[2] & 0x1F) << 8) | u16::from(program[3]))
+}
+
+// Returns the PID and codec of the first audio stream
+fn parse_pmt(payload: &[u8]) -> Option<(u16, AudioCodec)> {
+    let data = section(payload, PMT_TABLE_ID)?;
+    let program_info_length = (usize::from(data.get(2)? & 0x0F) << 8) | usize::from(*data.get(3)?);
+    let mut streams = data.get(4 + program_info_length..)?;
+
+    while streams.len() >= 5 {
+        let codec = match streams[0] {
+            // MPEG-1 and MPEG-2 audio
+            0x03 | 0x04 => Some(AudioCodec::Mp3),
+            // AAC in ADTS frames
+            0x0F => Some(AudioCodec::Aac),
+            _ => None,
+        };
+        let pid = (u16::from(streams[1] & 0x1F) << 8) | u16::from(streams[2]);
+        if let Some(codec) = codec {
+            return Some((pid, codec));
+        }
+
+        let info_length = (usize::from(streams[3] & 0x0F) << 8) | usize::from(streams[4]);
+        streams = streams.get(5 + info_length..)?;
+    }
+
+    None
+}
+
+// The test against a local file server is ignored by default. To run it, create an HLS stream
+// and serve it on port 8000, e.g.:
+//
+// ffmpeg -f lavfi -i sine=duration=10 -c:a aac -f hls -hls_segment_filename stream%d.ts stream.m3u8
+// python3 -m http.server 8000
+//
+// and then:
+//
+// cargo test -p http -- --ignored
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::content_type::{ContentType, SNIFF_LEN};
+    use crate::prepend::Prepend;
+    use crate::reader::read_headers;
+    use crate::request::{Method, Request};
+    use crate::response::{Response, ResponseStatusCode};
+    use embassy_futures::block_on;
+    use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+    use std::net::TcpStream;
+
+    const FILE_SERVER: &str = "127.0.0.1:8000";
+
+    const PMT_PID: u16 = 0x1000;
+    const AUDIO_PID: u16 = 0x0101;
+
+    // Makes a packet, filling the space before the payload with an adaptation field
+    fn packet(pid: u16, is_unit_start: bool, payload: &[u8]) -> std::vec::Vec<u8> {
+        let mut packet = std::vec![
+            SYNC_BYTE,
+            (if is_unit_start { 0x40 } else { 0x00 }) | (pid >> 8) as u8,
+            pid as u8,
+            0x10,
+        ];
+
+        let stuffing = TS_PACKET_LEN - 4 - payload.len();
+        if stuffing > 0 {
+            packet[3] |= 0x20;
+            packet.push((stuffing - 1) as u8);
+            if stuffing > 1 {
+                packet.push(0x00);
+                packet.extend(std::iter::repeat_n(0xFF, stuffing - 2));
+            }
+        }
+
+        packet.extend_from_slice(payload);
+        assert_eq!(packet.len(), TS_PACKET_LEN);
+        packet
+    }
+
+    fn pat() -> std::vec::Vec<u8> {
+        let payload = [
+            0x00, // Pointer
+            PAT_TABLE_ID,
+            0xB0,
+            0x0D, // Section length
+            0x00,
+            0x01,
+            0xC1,
+            0x00,
+            0x00,
+            0x00,
+            0x01, // Program 1
+            0xE0 | (PMT_PID >> 8) as u8,
+            PMT_PID as u8,
+            0x00,
+            0x00,
+            0x00,
+            0x00, // CRC
+        ];
+        packet(PAT_PID, true, &payload)
+    }
+
+    fn pmt() -> std::vec::Vec<u8> {
+        let payload = [
+            0x00, // Pointer
+            PMT_TABLE_ID,
+            0xB0,
+            0x17, // Section length
+            0x00,
+            0x01,
+            0xC1,
+            0x00,
+            0x00,
+            0xE1,
+            0x00, // PCR PID
+            0xF0,
+            0x00, // Program info length
+            0x15, // Metadata stream
+            0xE1,
+            0x02,
+            0xF0,
+            0x00,
+            0x0F, // AAC stream
+            0xE0 | (AUDIO_PID >> 8) as u8,
+            AUDIO_PID as u8,
+            0xF0,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00, // CRC
+        ];
+        packet(PMT_PID, true, &payload)
+    }
+
+    fn pes_start(audio: &[u8]) -> std::vec::Vec<u8> {
+        let mut payload = std::vec![0x00, 0x00, 0x01, 0xC0, 0x00, 0x00, 0x80, 0x80, 0x05];
+        payload.extend_from_slice(&[0x21, 0x00, 0x01, 0x00, 0x01]); // PTS
+        payload.extend_from_slice(audio);
+        packet(AUDIO_PID, true, &payload)
+    }
+
+    fn demux_all(demux: &mut TsDemux, mut data: &[u8]) -> std::vec::Vec<u8> {
+        let mut audio = std::vec::Vec::new();
+        while let Some(part) = demux.next_audio(&mut data) {
+            audio.extend_from_slice(part);
+        }
+        audio
+    }
+
+    #[test]
+    fn test_demux() {
+        let mut stream = pat();
+        stream.extend(pmt());
+        stream.extend(pes_start(b"\xFF\xF1first"));
+        // Packets of other streams are left out
+        stream.extend(packet(0x0102, true, b"metadata"));
+        stream.extend(packet(AUDIO_PID, false, b"second"));
+
+        let mut demux = TsDemux::new();
+        let audio = demux_all(&mut demux, &stream);
+
+        assert_eq!(audio, b"\xFF\xF1firstsecond");
+        assert_eq!(demux.codec(), Some(AudioCodec::Aac));
+    }
+
+    #[test]
+    fn test_demux_in_pieces() {
+        let mut stream = pat();
+        stream.extend(pmt());
+        stream.extend(pes_start(b"audio"));
+
+        let mut demux = TsDemux::new();
+        let mut audio = std::vec::Vec::new();
+        for piece in stream.chunks(7) {
+            audio.extend(demux_all(&mut demux, piece));
+        }
+
+        assert_eq!(audio, b"audio");
+    }
+
+    #[test]
+    fn test_demux_resynchronise() {
+        let mut stream = b"\x00\x01 lost".to_vec();
+        stream.extend(pat());
+        stream.extend(pmt());
+        stream.extend(pes_start(b"audio"));
+
+        assert_eq!(demux_all(&mut TsDemux::new(), &stream), b"audio");
+    }
+
+    #[test]
+    fn test_demux_without_tables() {
+        // The audio stream is not known without the PAT and PMT
+        let stream = pes_start(b"audio");
+
+        let mut demux = TsDemux::new();
+        assert!(demux_all(&mut demux, &stream).is_empty());
+        assert_eq!(demux.codec(), None);
+    }
+
+    #[test]
+    fn test_demux_segment_longer_than_buffer() {
+        let mut segment = pat();
+        segment.extend(pmt());
+        segment.extend(pes_start(b"first"));
+        for _ in 0..4 {
+            segment.extend(packet(AUDIO_PID, false, b"more"));
+        }
+        assert!(segment.len() > SNIFF_LEN);
+
+        // As the stream task does: the start is read in to find the type, then the segment
+        // is read into a small buffer
+        let (start, rest) = segment.split_at(SNIFF_LEN);
+        assert_eq!(ContentType::sniff(start), ContentType::TransportStream);
+
+        let mut reader = Prepend::new(start, rest);
+        let mut buffer = [0u8; 16];
+        let mut demux = TsDemux::new();
+        let mut audio = std::vec::Vec::new();
+        loop {
+            match block_on(reader.read(&mut buffer)).unwrap() {
+                0 => break,
+                n => audio.extend(demux_all(&mut demux, &buffer[..n])),
+            }
+        }
+
+        assert_eq!(audio, b"firstmoremoremoremore");
+    }
+
+    // Blocking std socket used as an async one, which is enough for the tests
+    struct TestSocket(TcpStream);
+
+    impl ErrorType for TestSocket {
+        type Error = ErrorKind;
+    }
+
+    impl Read for TestSocket {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            std::io::Read::read(&mut self.0, buf).map_err(|_| ErrorKind::Other)
+        }
+    }
+
+    impl Write for TestSocket {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            std::io::Write::write(&mut self.0, buf).map_err(|_| ErrorKind::Other)
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a local file server with an HLS stream"]
+    fn test_demux_segment_from_file_server() {
+        let mut socket = TestSocket(TcpStream::connect(FILE_SERVER).unwrap());
+        let mut request = Request::new(Method::GET, "/stream0.ts").unwrap();
+        request.host(FILE_SERVER).unwrap();
+        request.header("Connection", "close").unwrap();
+        block_on(request.write_to(&mut socket)).unwrap();
+
+        let mut buffer = [0u8; 2048];
+        let headers_read = block_on(read_headers(&mut socket, &mut buffer)).unwrap();
+        let response = Response::new(headers_read.headers(&buffer)).unwrap();
+        assert!(matches!(
+            response.status_code(),
+            ResponseStatusCode::Successful(_)
+        ));
+
+        let mut segment = headers_read.body(&buffer).to_vec();
+        std::io::Read::read_to_end(&mut socket.0, &mut segment).unwrap();
+
+        let mut demux = TsDemux::new();
+        let audio = demux_all(&mut demux, &segment);
+
+        assert_eq!(demux.codec(), Some(AudioCodec::Aac));
+        // Starts with an ADTS frame
+        assert_eq!(audio[0], 0xFF);
+        assert_eq!(audio[1] & 0xF0, 0xF0);
+    }
+}
@@ -0,0 +1,120 @@
+// Streaming parser for ASX playlists, as used by Windows Media Player.
+//
+// An ASX playlist looks like this:
+//
+// <asx version="3.0">
+//   <title>Radio</title>
+//   <entry>
+//     <title>Station name</title>
+//     <ref href="http://example.com/stream.mp3"/>
+//     <ref href="http://backup.example.com/stream.mp3"/>
+//   </entry>
+// </asx>
+//
+// The elements are often in upper case. An entry can have more than one ref, of which only
+// the first is used.
+
+use crate::xml::{Format, UrlSource, XmlPlaylist};
+use crate::{Entry, M3UError};
+
+const ASX: Format = Format {
+    entry: "entry",
+    url: UrlSource::Attribute {
+        element: "ref",
+        attribute: "href",
+    },
+};
+
+/// Parses an ASX playlist as it arrives, returning the entries one at a time.
+///
+/// The URLs are limited to `LEN` bytes.
+pub struct Asx<const LEN: usize> {
+    playlist: XmlPlaylist<LEN>,
+}
+
+impl<const LEN: usize> Asx<LEN> {
+    pub fn new() -> Asx<LEN> {
+        Asx {
+            playlist: XmlPlaylist::new(ASX),
+        }
+    }
+
+    /// The title of the whole playlist, if it has been read yet.
+    pub fn playlist_title(&self) -> Option<&str> {
+        self.playlist.playlist_title()
+    }
+
+    // Give the bytes of the playlist one at a time. Returns an entry at the end of its
+    // element, otherwise None.
+    // An entry with an invalid URL is returned as an error. The parser then continues with the
+    // next entry, so that the entry with the error can be skipped.
+    pub fn parse(&mut self, byte: u8) -> Result<Option<Entry<LEN>>, M3UError> {
+        self.playlist.parse(byte)
+    }
+
+    // Once the end of the playlist has been reached, use this function to return the last
+    // entry if it was not closed.
+    pub fn terminate(&mut self) -> Result<Option<Entry<LEN>>, M3UError> {
+        self.playlist.terminate()
+    }
+}
+
+impl<const LEN: usize> Default for Asx<LEN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASX_PLAYLIST: &str = "<ASX version=\"3.0\">\r\n  <Title>Radio &amp; more</Title>\r\n  <!-- Main <stream> -->\r\n  <Entry>\r\n    <Title>Station</Title>\r\n    <Ref href=\"http://example.com/stream.mp3?a=1&amp;b=2\" />\r\n    <Ref href=\"http://backup.example.com/stream.mp3\" />\r\n  </Entry>\r\n  <Entry><Ref HREF='http://example.com/second.aac'/></Entry>\r\n</ASX>\r\n";
+
+    fn parse_all(playlist: &str) -> (Asx<64>, std::vec::Vec<Result<Entry<64>, M3UError>>) {
+        let mut asx = Asx::<64>::new();
+        let mut entries: std::vec::Vec<_> = playlist
+            .bytes()
+            .filter_map(|b| asx.parse(b).transpose())
+            .collect();
+        entries.extend(asx.terminate().transpose());
+        (asx, entries)
+    }
+
+    #[test]
+    fn test_parse() {
+        let (asx, entries) = parse_all(ASX_PLAYLIST);
+
+        assert_eq!(asx.playlist_title(), Some("Radio & more"));
+        assert_eq!(entries.len(), 2);
+
+        let entry = entries[0].as_ref().unwrap();
+        assert_eq!(entry.title.as_deref(), Some("Station"));
+        assert_eq!(entry.url, "http://example.com/stream.mp3?a=1&b=2");
+
+        let entry = entries[1].as_ref().unwrap();
+        assert_eq!(entry.title, None);
+        assert_eq!(entry.url, "http://example.com/second.aac");
+    }
+
+    #[test]
+    fn test_skip_invalid_entry() {
+        let (_, entries) = parse_all(&std::format!(
+            "<asx><entry><ref href=\"http://a.de/{}\"/></entry><entry><ref/></entry><entry><title>No ref</title></entry><entry><ref href=\"http://a.de/s\"/></entry></asx>",
+            "a".repeat(64)
+        ));
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], Err(M3UError::UrlTooLong));
+        assert_eq!(entries[1], Err(M3UError::MalformedUrl));
+        assert_eq!(entries[2].as_ref().unwrap().url, "http://a.de/s");
+    }
+
+    #[test]
+    fn test_unterminated() {
+        let (_, entries) = parse_all("<asx><entry><ref href=\"http://a.de/s\"/>");
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].as_ref().unwrap().url, "http://a.de/s");
+    }
+}
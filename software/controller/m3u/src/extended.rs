@@ -0,0 +1,378 @@
+// Streaming parser for extended M3U playlists, which returns all the entries.
+//
+// An extended M3U playlist looks like this:
+//
+// #EXTM3U
+// #PLAYLIST:Radio
+// #EXTINF:-1 tvg-logo="http://example.com/logo.png" group-title="News",Station name
+// http://example.com/stream.mp3
+//
+// The #EXTINF line gives the duration (-1 or 0 for a stream), any attributes and the title of
+// the entry on the line after it. Simple M3U playlists only have the URLs, so their entries
+// have no duration or title.
+
+use heapless::{String, Vec};
+
+use crate::{BOM, LineBuffer, M3UError, non_empty};
+
+/// The maximum length of a line. Longer comments are ignored, longer URLs are an error.
+pub const MAX_LINE_LEN: usize = 1024;
+
+/// The maximum number of attributes kept for an entry. Any more are ignored.
+pub const MAX_ATTRIBUTES: usize = 4;
+
+/// The maximum length of the name of an attribute. Attributes with longer names are ignored.
+pub const MAX_ATTRIBUTE_NAME_LEN: usize = 16;
+
+const EXTINF: &str = "#EXTINF:";
+const PLAYLIST: &str = "#PLAYLIST:";
+
+/// An attribute of an entry given in the `#EXTINF` line, e.g. `tvg-logo="http://..."`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute<const LEN: usize> {
+    pub name: String<MAX_ATTRIBUTE_NAME_LEN>,
+    pub value: String<LEN>,
+}
+
+/// An entry of a playlist.
+///
+/// The title and attribute values are limited to `LEN` bytes. Longer titles are truncated
+/// and longer attribute values are ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Entry<const LEN: usize> {
+    /// The duration in seconds, normally -1 or 0 for a stream. `None` if not given.
+    pub duration: Option<i32>,
+    pub title: Option<String<LEN>>,
+    pub attributes: Vec<Attribute<LEN>, MAX_ATTRIBUTES>,
+    pub url: String<LEN>,
+}
+
+impl<const LEN: usize> Entry<LEN> {
+    /// Returns the value of the attribute with the name, e.g. `group-title`.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name.eq_ignore_ascii_case(name))
+            .map(|attribute| attribute.value.as_str())
+    }
+}
+
+/// Parses a simple or extended M3U playlist as it arrives, returning the entries one at a time.
+///
+/// The URLs are limited to `LEN` bytes.
+pub struct ExtendedM3U<const LEN: usize> {
+    line: LineBuffer<MAX_LINE_LEN>,
+    // The information from the #EXTINF line for the next URL
+    info: Option<Entry<LEN>>,
+    playlist_title: Option<String<LEN>>,
+}
+
+impl<const LEN: usize> ExtendedM3U<LEN> {
+    pub fn new() -> ExtendedM3U<LEN> {
+        ExtendedM3U {
+            line: LineBuffer::new(),
+            info: None,
+            playlist_title: None,
+        }
+    }
+
+    /// The title of the whole playlist, given in a `#PLAYLIST:` line.
+    pub fn playlist_title(&self) -> Option<&str> {
+        self.playlist_title.as_deref()
+    }
+
+    // Give the bytes of the playlist one at a time. Returns an entry when the end of its URL
+    // has been reached, otherwise None.
+    // After an error the parser continues with the next line, so that the entry with the
+    // error can be skipped.
+    pub fn parse(&mut self, byte: u8) -> Result<Option<Entry<LEN>>, M3UError> {
+        match byte {
+            b'\n' | b'\r' => self.end_line(),
+            _ => {
+                self.line.push(byte);
+                Ok(None)
+            }
+        }
+    }
+
+    // Not all playlists end with a new line. Once the end of the playlist has been reached,
+    // use this function to return the last entry if there is one.
+    pub fn terminate(&mut self) -> Result<Option<Entry<LEN>>, M3UError> {
+        self.end_line()
+    }
+
+    fn end_line(&mut self) -> Result<Option<Entry<LEN>>, M3UError> {
+        let result = self.parse_line();
+        self.line.clear();
+        result
+    }
+
+    fn parse_line(&mut self) -> Result<Option<Entry<LEN>>, M3UError> {
+        // The byte order mark can only be at the start of the first line, but there is no
+        // harm in removing it from any line
+        let line = self.line.line();
+        let line = line.strip_prefix(BOM).unwrap_or(line);
+        let is_comment = line.trim_ascii_start().first() == Some(&b'#');
+
+        if is_comment {
+            // Comments that are too long or not UTF-8 only have information that is not needed
+            if let (false, Ok(line)) = (self.line.is_too_long(), core::str::from_utf8(line)) {
+                let line = line.trim();
+                if let Some(info) = strip_prefix_ignore_case(line, EXTINF) {
+                    self.info = Some(parse_info(info));
+                } else if let Some(title) = strip_prefix_ignore_case(line, PLAYLIST) {
+                    self.playlist_title = non_empty(title);
+                }
+            }
+            return Ok(None);
+        }
+
+        if line.trim_ascii().is_empty() {
+            return Ok(None);
+        }
+
+        // The #EXTINF line only applies to this URL, even if it is not valid
+        let mut entry = self.info.take().unwrap_or_default();
+
+        if self.line.is_too_long() {
+            return Err(M3UError::UrlTooLong);
+        }
+        let url = core::str::from_utf8(line)?.trim();
+        if url.contains(|c: char| c.is_ascii_whitespace()) {
+            return Err(M3UError::MalformedUrl);
+        }
+        entry.url.push_str(url).map_err(|_| M3UError::UrlTooLong)?;
+
+        Ok(Some(entry))
+    }
+}
+
+impl<const LEN: usize> Default for ExtendedM3U<LEN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let start = line.get(..prefix.len())?;
+    start
+        .eq_ignore_ascii_case(prefix)
+        .then(|| &line[prefix.len()..])
+}
+
+// Parses what follows #EXTINF:, i.e. the duration, the attributes and after a comma the title
+fn parse_info<const LEN: usize>(info: &str) -> Entry<LEN> {
+    // The title starts after the first comma that is not in the value of an attribute
+    let mut in_quotes = false;
+    let comma = info.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ',' if !in_quotes => Some(i),
+        _ => None,
+    });
+    let (head, title) = match comma {
+        Some(i) => (&info[..i], Some(&info[i + 1..])),
+        None => (info, None),
+    };
+
+    let head = head.trim_start();
+    let (duration, mut attributes) = head
+        .split_once(|c: char| c.is_ascii_whitespace())
+        .unwrap_or((head, ""));
+
+    let mut entry = Entry {
+        // Some playlists give the duration in fractions of a second
+        duration: duration
+            .parse()
+            .ok()
+            .or_else(|| duration.parse::<f32>().ok().map(|duration| duration as i32)),
+        title: title.and_then(non_empty),
+        ..Entry::default()
+    };
+
+    while let Some((name, value, rest)) = next_attribute(attributes) {
+        attributes = rest;
+        if let (Ok(name), Ok(value)) = (String::try_from(name), String::try_from(value))
+            && entry.attributes.push(Attribute { name, value }).is_err()
+        {
+            break;
+        }
+    }
+
+    entry
+}
+
+// Splits off the next attribute, e.g. name="value" or name=value, returning the name, the
+// value and the rest
+fn next_attribute(attributes: &str) -> Option<(&str, &str, &str)> {
+    let (name, rest) = attributes.trim_start().split_once('=')?;
+
+    let (value, rest) = match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+        None => rest
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .unwrap_or((rest, "")),
+    };
+
+    Some((name.trim(), value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTENDED_M3U: &str = "#EXTM3U\n#PLAYLIST:Radio\n\n#EXTINF:-1 tvg-logo=\"http://example.com/logo.png\" group-title=\"News, Talk\",BBC World Service\nhttp://stream.example.com/world.mp3\n#EXTINF:0,DLF\r\nhttps://st01.sslstream.dlf.de/dlf/01/128/mp3/stream.mp3\r\nhttp://listen.181fm.com/181-classical_128k.mp3";
+
+    fn parse_all(playlist: &str) -> std::vec::Vec<Result<Entry<256>, M3UError>> {
+        let mut m3u = ExtendedM3U::<256>::new();
+        let mut entries: std::vec::Vec<_> = playlist
+            .bytes()
+            .filter_map(|b| m3u.parse(b).transpose())
+            .collect();
+        entries.extend(m3u.terminate().transpose());
+        entries
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let mut m3u = ExtendedM3U::<256>::new();
+        let mut entries = std::vec::Vec::new();
+        for b in EXTENDED_M3U.bytes() {
+            if let Some(entry) = m3u.parse(b).unwrap() {
+                entries.push(entry);
+            }
+        }
+        entries.extend(m3u.terminate().unwrap());
+
+        assert_eq!(m3u.playlist_title(), Some("Radio"));
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(entries[0].duration, Some(-1));
+        assert_eq!(entries[0].title.as_deref(), Some("BBC World Service"));
+        assert_eq!(
+            entries[0].attribute("tvg-logo"),
+            Some("http://example.com/logo.png")
+        );
+        assert_eq!(entries[0].attribute("group-title"), Some("News, Talk"));
+        assert_eq!(entries[0].url, "http://stream.example.com/world.mp3");
+
+        assert_eq!(entries[1].duration, Some(0));
+        assert_eq!(entries[1].title.as_deref(), Some("DLF"));
+        assert!(entries[1].attributes.is_empty());
+        assert_eq!(
+            entries[1].url,
+            "https://st01.sslstream.dlf.de/dlf/01/128/mp3/stream.mp3"
+        );
+
+        // A simple entry, without a #EXTINF line or a new line at the end
+        assert_eq!(entries[2].duration, None);
+        assert_eq!(entries[2].title, None);
+        assert_eq!(
+            entries[2].url,
+            "http://listen.181fm.com/181-classical_128k.mp3"
+        );
+    }
+
+    #[test]
+    fn test_parse_test_resource() {
+        let entries = parse_all(include_str!("../test_resources/extended_m3u.m3u"));
+
+        assert_eq!(entries.len(), 1);
+        let entry = entries[0].as_ref().unwrap();
+        assert_eq!(entry.title.as_deref(), Some("181FM Classical"));
+        assert_eq!(entry.url, "http://listen.181fm.com/181-classical_128k.mp3");
+    }
+
+    #[test]
+    fn test_parse_bom() {
+        let entries = parse_all(include_str!("../test_resources/bom.m3u"));
+
+        assert_eq!(entries.len(), 1);
+        let entry = entries[0].as_ref().unwrap();
+        assert_eq!(entry.title.as_deref(), Some("181FM Classical"));
+        assert_eq!(entry.url, "http://listen.181fm.com/181-classical_128k.mp3");
+    }
+
+    #[test]
+    fn test_parse_relative_and_uppercase_urls() {
+        // Relative URLs are resolved by the caller, which knows the URL of the playlist
+        let entries = parse_all(include_str!("../test_resources/relative.m3u"));
+        let urls: std::vec::Vec<&str> = entries
+            .iter()
+            .map(|entry| entry.as_ref().unwrap().url.as_str())
+            .collect();
+        assert_eq!(urls, ["181-classical_128k.mp3", "/181-classical_64k.aac"]);
+
+        let entries = parse_all(include_str!("../test_resources/uppercase_scheme.m3u"));
+        assert_eq!(
+            entries[0].as_ref().unwrap().url,
+            "HTTP://listen.181fm.com/181-classical_128k.mp3"
+        );
+    }
+
+    #[test]
+    fn test_parse_info() {
+        let entry = parse_info::<64>("123.9 tvg-id=bbc1 tvg-name=\"BBC One\" , A, B ");
+
+        assert_eq!(entry.duration, Some(123));
+        assert_eq!(entry.attribute("tvg-id"), Some("bbc1"));
+        assert_eq!(entry.attribute("TVG-NAME"), Some("BBC One"));
+        assert_eq!(entry.title.as_deref(), Some("A, B"));
+
+        let entry = parse_info::<64>("");
+        assert_eq!(entry, Entry::default());
+    }
+
+    #[test]
+    fn test_limits() {
+        let long_value = "v".repeat(20);
+        let info = std::format!(
+            "-1 a=1 b=\"{long_value}\" c=3 d=4 e=5 {}=6,{}",
+            "n".repeat(MAX_ATTRIBUTE_NAME_LEN + 1),
+            "t".repeat(20)
+        );
+
+        let entry = parse_info::<16>(&info);
+
+        // The attribute with the value or name that is too long is left out
+        let names: std::vec::Vec<&str> = entry.attributes.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["a", "c", "d", "e"]);
+        assert_eq!(entry.title.unwrap(), "t".repeat(16).as_str());
+    }
+
+    #[test]
+    fn test_skip_invalid_entry() {
+        let entries = parse_all(
+            "#EXTINF:-1,Broken\nhttp://radio.com/ stream.mp3\n#EXTINF:-1,Working\nhttp://radio.com/stream.mp3\n",
+        );
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], Err(M3UError::MalformedUrl));
+        // The title of the broken entry is not given to the next one
+        let entry = entries[1].as_ref().unwrap();
+        assert_eq!(entry.title.as_deref(), Some("Working"));
+        assert_eq!(entry.url, "http://radio.com/stream.mp3");
+    }
+
+    #[test]
+    fn test_url_too_long() {
+        let entries = parse_all(&std::format!(
+            "http://radio.com/{}\nhttp://radio.com/{}\n",
+            "a".repeat(300),
+            "b".repeat(MAX_LINE_LEN)
+        ));
+
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| *e == Err(M3UError::UrlTooLong)));
+
+        // Comments that are too long are ignored
+        let entries = parse_all(&std::format!(
+            "#EXTINF:-1,{}\nhttp://radio.com/\n",
+            "a".repeat(MAX_LINE_LEN)
+        ));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].as_ref().unwrap().title, None);
+    }
+}
@@ -0,0 +1,420 @@
+// Streaming parser for HLS (HTTP Live Streaming) playlists, see RFC 8216.
+//
+// A master playlist lists the variants of a stream, e.g. in different bit rates:
+//
+// #EXTM3U
+// #EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS="mp4a.40.5"
+// low/playlist.m3u8
+// #EXT-X-STREAM-INF:BANDWIDTH=128000,CODECS="mp4a.40.2"
+// high/playlist.m3u8
+//
+// The media playlist of a variant lists the segments of the stream. For a live stream it is
+// read in again and again, as the newest segments are added at the end:
+//
+// #EXTM3U
+// #EXT-X-TARGETDURATION:10
+// #EXT-X-MEDIA-SEQUENCE:2680
+// #EXTINF:9.984,
+// segment2680.ts
+// #EXTINF:9.984,
+// segment2681.ts
+
+use heapless::String;
+
+use crate::{LineBuffer, M3UError, MAX_LINE_LEN};
+
+const EXTINF: &str = "#EXTINF:";
+const STREAM_INF: &str = "#EXT-X-STREAM-INF:";
+const TARGET_DURATION: &str = "#EXT-X-TARGETDURATION:";
+const MEDIA_SEQUENCE: &str = "#EXT-X-MEDIA-SEQUENCE:";
+const PLAYLIST_TYPE: &str = "#EXT-X-PLAYLIST-TYPE:";
+const ENDLIST: &str = "#EXT-X-ENDLIST";
+
+/// A variant of the stream, listed in a master playlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant<const LEN: usize> {
+    /// The peak bit rate in bits per second
+    pub bandwidth: u32,
+    /// The URI of the media playlist, which is normally relative to the master playlist
+    pub uri: String<LEN>,
+}
+
+/// A segment of the stream, listed in a media playlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment<const LEN: usize> {
+    /// The media sequence number, which identifies the segment in all versions of the playlist
+    pub sequence: u64,
+    pub duration_ms: u32,
+    /// The URI of the segment, which is normally relative to the playlist
+    pub uri: String<LEN>,
+}
+
+/// A variant or a segment, depending on the type of playlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HlsItem<const LEN: usize> {
+    Variant(Variant<LEN>),
+    Segment(Segment<LEN>),
+}
+
+// The tag that applies to the next URI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    None,
+    Segment { duration_ms: u32 },
+    Variant { bandwidth: u32 },
+}
+
+/// Parses an HLS master or media playlist as it arrives, returning the variants or segments
+/// one at a time.
+///
+/// The URIs are limited to `LEN` bytes.
+pub struct HlsPlaylist<const LEN: usize> {
+    line: LineBuffer<MAX_LINE_LEN>,
+    pending: Pending,
+    target_duration: Option<u32>,
+    next_sequence: u64,
+    is_vod: bool,
+    is_end: bool,
+}
+
+impl<const LEN: usize> HlsPlaylist<LEN> {
+    pub fn new() -> HlsPlaylist<LEN> {
+        HlsPlaylist {
+            line: LineBuffer::new(),
+            pending: Pending::None,
+            target_duration: None,
+            next_sequence: 0,
+            is_vod: false,
+            is_end: false,
+        }
+    }
+
+    /// The maximum duration of a segment in seconds. The playlist should not be read in again
+    /// more often than this.
+    pub fn target_duration(&self) -> Option<u32> {
+        self.target_duration
+    }
+
+    /// Returns true if the playlist will not change (`#EXT-X-PLAYLIST-TYPE:VOD`), so is not
+    /// a live stream. This is given before the segments.
+    pub fn is_vod(&self) -> bool {
+        self.is_vod
+    }
+
+    /// Returns true if no more segments will be added (`#EXT-X-ENDLIST`). This is given
+    /// after the segments.
+    pub fn is_end(&self) -> bool {
+        self.is_end
+    }
+
+    // Give the bytes of the playlist one at a time. Returns a variant or a segment when the end
+    // of its URI has been reached, otherwise None.
+    // After an error the parser continues with the next line, so that the variant or segment
+    // with the error can be skipped.
+    pub fn parse(&mut self, byte: u8) -> Result<Option<HlsItem<LEN>>, M3UError> {
+        match byte {
+            b'\n' | b'\r' => self.end_line(),
+            _ => {
+                self.line.push(byte);
+                Ok(None)
+            }
+        }
+    }
+
+    // Not all playlists end with a new line. Once the end of the playlist has been reached,
+    // use this function to return the last variant or segment if there is one.
+    pub fn terminate(&mut self) -> Result<Option<HlsItem<LEN>>, M3UError> {
+        self.end_line()
+    }
+
+    fn end_line(&mut self) -> Result<Option<HlsItem<LEN>>, M3UError> {
+        let result = self.parse_line();
+        self.line.clear();
+        result
+    }
+
+    fn parse_line(&mut self) -> Result<Option<HlsItem<LEN>>, M3UError> {
+        // Taken, so that the tags can change the state of the parser
+        let line = core::mem::take(&mut self.line);
+        let is_too_long = line.is_too_long();
+        let line = line.line().trim_ascii();
+
+        if line.first() == Some(&b'#') {
+            // Tags that are too long or not UTF-8 are not needed
+            if let (false, Ok(tag)) = (is_too_long, core::str::from_utf8(line)) {
+                self.parse_tag(tag);
+            }
+            return Ok(None);
+        }
+
+        if line.is_empty() {
+            return Ok(None);
+        }
+
+        let pending = core::mem::replace(&mut self.pending, Pending::None);
+
+        // The sequence number is counted for a segment, even if its URI is not valid
+        let sequence = self.next_sequence;
+        if !matches!(pending, Pending::Variant { .. }) {
+            self.next_sequence += 1;
+        }
+
+        if is_too_long {
+            return Err(M3UError::UrlTooLong);
+        }
+        let uri = core::str::from_utf8(line)?;
+        if uri.contains(|c: char| c.is_ascii_whitespace()) {
+            return Err(M3UError::MalformedUrl);
+        }
+        let uri = String::try_from(uri).map_err(|_| M3UError::UrlTooLong)?;
+
+        let item = match pending {
+            Pending::Variant { bandwidth } => HlsItem::Variant(Variant { bandwidth, uri }),
+            Pending::Segment { duration_ms } => HlsItem::Segment(Segment {
+                sequence,
+                duration_ms,
+                uri,
+            }),
+            Pending::None => HlsItem::Segment(Segment {
+                sequence,
+                duration_ms: 0,
+                uri,
+            }),
+        };
+        Ok(Some(item))
+    }
+
+    fn parse_tag(&mut self, tag: &str) {
+        if let Some(info) = tag.strip_prefix(EXTINF) {
+            let duration = info.split(',').next().unwrap_or_default().trim();
+            let duration_ms = duration
+                .parse::<f32>()
+                .map(|duration| (duration * 1000.0) as u32)
+                .unwrap_or(0);
+            self.pending = Pending::Segment { duration_ms };
+        } else if let Some(attributes) = tag.strip_prefix(STREAM_INF) {
+            let bandwidth = attribute(attributes, "BANDWIDTH")
+                .and_then(|bandwidth| bandwidth.parse().ok())
+                .unwrap_or(0);
+            self.pending = Pending::Variant { bandwidth };
+        } else if let Some(duration) = tag.strip_prefix(TARGET_DURATION) {
+            self.target_duration = duration.trim().parse().ok();
+        } else if let Some(sequence) = tag.strip_prefix(MEDIA_SEQUENCE) {
+            if let Ok(sequence) = sequence.trim().parse() {
+                self.next_sequence = sequence;
+            }
+        } else if let Some(playlist_type) = tag.strip_prefix(PLAYLIST_TYPE) {
+            self.is_vod = playlist_type.trim() == "VOD";
+        } else if tag == ENDLIST {
+            self.is_end = true;
+        }
+    }
+}
+
+impl<const LEN: usize> Default for HlsPlaylist<LEN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Returns the value of an attribute in a list such as BANDWIDTH=128000,CODECS="mp4a.40.2"
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+
+    while let Some((key, after)) = rest.split_once('=') {
+        let (value, next) = match after.strip_prefix('"') {
+            // Quoted values can contain commas
+            Some(quoted) => {
+                let (value, next) = quoted.split_once('"').unwrap_or((quoted, ""));
+                (value, next.strip_prefix(',').unwrap_or(next))
+            }
+            None => after.split_once(',').unwrap_or((after, "")),
+        };
+
+        if key.trim() == name {
+            return Some(value);
+        }
+        rest = next;
+    }
+
+    None
+}
+
+/// Selects a variant of a master playlist as they are read: the variant with the highest
+/// bandwidth that is not more than the maximum or, if there is none, the one with the lowest.
+pub struct VariantSelection<const LEN: usize> {
+    max_bandwidth: u32,
+    selected: Option<Variant<LEN>>,
+}
+
+impl<const LEN: usize> VariantSelection<LEN> {
+    pub fn new(max_bandwidth: u32) -> VariantSelection<LEN> {
+        VariantSelection {
+            max_bandwidth,
+            selected: None,
+        }
+    }
+
+    pub fn offer(&mut self, variant: Variant<LEN>) {
+        let is_better = match &self.selected {
+            None => true,
+            Some(selected) => {
+                let fits = |variant: &Variant<LEN>| variant.bandwidth <= self.max_bandwidth;
+                match (fits(&variant), fits(selected)) {
+                    (true, true) => variant.bandwidth > selected.bandwidth,
+                    (true, false) => true,
+                    (false, true) => false,
+                    (false, false) => variant.bandwidth < selected.bandwidth,
+                }
+            }
+        };
+
+        if is_better {
+            self.selected = Some(variant);
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Variant<LEN>> {
+        self.selected.as_ref()
+    }
+
+    /// Returns the selected variant, so that the selection can be used again.
+    pub fn take(&mut self) -> Option<Variant<LEN>> {
+        self.selected.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.5\"\nlow/playlist.m3u8\n#EXT-X-STREAM-INF:CODECS=\"mp4a.40.2,mp3\",BANDWIDTH=128000\nhigh/playlist.m3u8\n#EXT-X-STREAM-INF:AVERAGE-BANDWIDTH=300000,BANDWIDTH=320000\nhttps://cdn.example.com/best/playlist.m3u8\n";
+
+    const MEDIA: &str = "#EXTM3U\r\n#EXT-X-VERSION:3\r\n#EXT-X-TARGETDURATION:10\r\n#EXT-X-MEDIA-SEQUENCE:2680\r\n#EXTINF:9.984,\r\nsegment2680.ts\r\n#EXTINF:10,Title\r\nsegment2681.ts\r\n#EXT-X-PROGRAM-DATE-TIME:2025-06-03T10:15:00Z\r\nsegment2682.ts";
+
+    fn parse_all(
+        playlist: &str,
+    ) -> (
+        HlsPlaylist<64>,
+        std::vec::Vec<Result<HlsItem<64>, M3UError>>,
+    ) {
+        let mut hls = HlsPlaylist::<64>::new();
+        let mut items: std::vec::Vec<_> = playlist
+            .bytes()
+            .filter_map(|b| hls.parse(b).transpose())
+            .collect();
+        items.extend(hls.terminate().transpose());
+        (hls, items)
+    }
+
+    fn variant(bandwidth: u32, uri: &str) -> Variant<64> {
+        Variant {
+            bandwidth,
+            uri: String::try_from(uri).unwrap(),
+        }
+    }
+
+    fn segment(sequence: u64, duration_ms: u32, uri: &str) -> HlsItem<64> {
+        HlsItem::Segment(Segment {
+            sequence,
+            duration_ms,
+            uri: String::try_from(uri).unwrap(),
+        })
+    }
+
+    #[test]
+    fn test_parse_master() {
+        let (_, items) = parse_all(MASTER);
+
+        assert_eq!(
+            items,
+            [
+                Ok(HlsItem::Variant(variant(64000, "low/playlist.m3u8"))),
+                Ok(HlsItem::Variant(variant(128000, "high/playlist.m3u8"))),
+                Ok(HlsItem::Variant(variant(
+                    320000,
+                    "https://cdn.example.com/best/playlist.m3u8"
+                ))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_media() {
+        let (hls, items) = parse_all(MEDIA);
+
+        assert_eq!(hls.target_duration(), Some(10));
+        assert!(!hls.is_vod());
+        assert!(!hls.is_end());
+        assert_eq!(
+            items,
+            [
+                Ok(segment(2680, 9984, "segment2680.ts")),
+                Ok(segment(2681, 10000, "segment2681.ts")),
+                // A segment without a duration
+                Ok(segment(2682, 0, "segment2682.ts")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_vod() {
+        let (hls, items) = parse_all(
+            "#EXTM3U\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:6\n#EXTINF:6.0,\na.aac\n#EXTINF:4.5,\nb.aac\n#EXT-X-ENDLIST\n",
+        );
+
+        assert!(hls.is_vod());
+        assert!(hls.is_end());
+        assert_eq!(
+            items,
+            [Ok(segment(0, 6000, "a.aac")), Ok(segment(1, 4500, "b.aac"))]
+        );
+    }
+
+    #[test]
+    fn test_invalid_segment_keeps_sequence() {
+        let (_, items) = parse_all(&std::format!(
+            "#EXT-X-MEDIA-SEQUENCE:7\n#EXTINF:10,\n{}\n#EXTINF:10,\nbad uri.ts\n#EXTINF:10,\ngood.ts\n",
+            "a".repeat(65)
+        ));
+
+        assert_eq!(
+            items,
+            [
+                Err(M3UError::UrlTooLong),
+                Err(M3UError::MalformedUrl),
+                Ok(segment(9, 10000, "good.ts")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_attribute() {
+        let attributes = "CODECS=\"mp4a.40.2,mp3\",BANDWIDTH=128000,NAME=x";
+
+        assert_eq!(attribute(attributes, "BANDWIDTH"), Some("128000"));
+        assert_eq!(attribute(attributes, "CODECS"), Some("mp4a.40.2,mp3"));
+        assert_eq!(attribute(attributes, "NAME"), Some("x"));
+        assert_eq!(attribute(attributes, "RESOLUTION"), None);
+        assert_eq!(attribute("", "BANDWIDTH"), None);
+    }
+
+    #[test]
+    fn test_variant_selection() {
+        let variants = [
+            variant(64000, "low"),
+            variant(320000, "best"),
+            variant(128000, "high"),
+        ];
+
+        let mut selection = VariantSelection::new(192000);
+        variants.iter().cloned().for_each(|v| selection.offer(v));
+        assert_eq!(selection.selected().unwrap().uri, "high");
+
+        // None are low enough, so the lowest is taken
+        let mut selection = VariantSelection::new(32000);
+        variants.iter().cloned().for_each(|v| selection.offer(v));
+        assert_eq!(selection.take().unwrap().uri, "low");
+        assert_eq!(selection.take(), None);
+    }
+}
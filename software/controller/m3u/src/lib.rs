@@ -7,125 +7,133 @@ use core::str::Utf8Error;
 
 use heapless::String;
 
+mod asx;
+mod extended;
+mod hls;
+mod line;
+mod playlist;
+mod writer;
+mod xml;
+mod xspf;
+
+pub use asx::Asx;
+pub use extended::{
+    Attribute, Entry, ExtendedM3U, MAX_ATTRIBUTE_NAME_LEN, MAX_ATTRIBUTES, MAX_LINE_LEN,
+};
+pub use hls::{HlsItem, HlsPlaylist, Segment, Variant, VariantSelection};
+pub use line::{LineBuffer, non_empty};
+pub use playlist::{PlaylistEntry, PlaylistParser};
+pub use writer::{M3UWriter, MAX_GROUP_TITLE_LEN};
+pub use xspf::Xspf;
+
 pub struct M3U<const MAX_URL_LEN: usize> {
     // contents: &'a [u8],
     url_buffer: [u8; MAX_URL_LEN],
     state: State,
     pos: usize,
+    // How much of the byte order mark at the start of the playlist has been skipped
+    bom_pos: usize,
 }
 
 #[derive(Clone, Debug)]
 enum State {
-    Initial,
-    H,
-    T1,
-    T2,
-    P,
-    Colon,
-    Slash1,
-    Slash2,
-    Rest,
+    // At the start of a line, before any non white space
+    LineStart,
+    // In a comment, or a line with an error, which is skipped
+    SkipLine,
+    Url,
+    // After the URL, where only white space may follow
+    UrlEnd,
 }
 
+// Some editors put a UTF-8 byte order mark at the start of the file
+pub(crate) const BOM: &[u8] = b"\xEF\xBB\xBF";
+
 impl<const MAX_URL_LEN: usize> M3U<MAX_URL_LEN> {
     pub fn new() -> M3U<MAX_URL_LEN> {
         M3U {
             url_buffer: [0; MAX_URL_LEN],
-            state: State::Initial,
+            state: State::LineStart,
             pos: 0,
+            bom_pos: 0,
         }
     }
 
-    // Extracts the first URL it finds in an extended M3U file and uses that as the next location
+    // Extracts the first URL it finds in a simple or extended M3U file and uses that as the next location.
     // This functions expects individual characters from a stream of data to be given.If no url currently
     // found in the parsing process then it returns None. This means it should be goven more characters
     // until the URL is found and Some is returned.
+    // Any line that is not empty or a comment is taken as the URL. After an error the parser continues
+    // with the next line, and after a URL is returned it can be given more characters to find the next one.
+    // Relative URLs, e.g. stream.mp3, are returned as they are, for the caller to resolve against the
+    // URL of the playlist.
     // It is designed to be sparing with memory.
-    // TODO  can we make this a "stream"  function
-    // TODO can we , should we, extend this so that it returns all the urls?
+    // Use ExtendedM3U to get all the entries, with their titles.
     pub fn parse_m3u(&mut self, char: u8) -> Result<Option<String<MAX_URL_LEN>>, M3UError> {
         // Assuming the first url found is the location and that it points to an audio stream and
         // not another m3u file.
 
-        // Look for http://
-
-        // let state = self.state.clone();
-        match (self.state.clone(), char) {
-            (State::Initial, b'h') => {
-                self.url_buffer[self.pos] = char;
-                self.pos += 1;
-                self.state = State::H;
-                Ok(None)
+        if self.bom_pos < BOM.len() {
+            if char == BOM[self.bom_pos] {
+                self.bom_pos += 1;
+                return Ok(None);
             }
-            (State::Initial, _) => Ok(None),
-
-            (State::H, b't') => {
-                self.url_buffer[self.pos] = char;
-                self.pos += 1;
-                self.state = State::T1;
-                Ok(None)
+            // Not a byte order mark after all, so the bytes taken for one belong to the line.
+            // They are all parsed with this one before an error from any of them is returned.
+            let taken = self.bom_pos;
+            self.bom_pos = BOM.len();
+            let mut error = None;
+            for &byte in &BOM[..taken] {
+                if let Err(e) = self.parse_char(byte) {
+                    error.get_or_insert(e);
+                }
             }
-            (State::T1, b't') => {
-                self.url_buffer[self.pos] = char;
-                self.pos += 1;
-                self.state = State::T2;
+            let result = self.parse_char(char);
+            return match error {
+                Some(error) => Err(error),
+                None => result,
+            };
+        }
+
+        self.parse_char(char)
+    }
+
+    fn parse_char(&mut self, char: u8) -> Result<Option<String<MAX_URL_LEN>>, M3UError> {
+        let is_line_end = char == b'\n' || char == b'\r';
+
+        match self.state {
+            State::LineStart if char == b'#' => {
+                self.state = State::SkipLine;
                 Ok(None)
             }
-            (State::T2, b'p') => {
-                self.url_buffer[self.pos] = char;
-                self.pos += 1;
-                self.state = State::P;
+            State::LineStart if char.is_ascii_whitespace() => Ok(None),
+            State::SkipLine if is_line_end => {
+                self.state = State::LineStart;
                 Ok(None)
             }
-            (State::P, b':') => {
-                self.url_buffer[self.pos] = char;
-                self.pos += 1;
-                self.state = State::Colon;
-                Ok(None)
+            State::SkipLine => Ok(None),
+            State::Url | State::UrlEnd if is_line_end => {
+                // End state
+                self.state = State::LineStart;
+                self.url().map(Some)
             }
-            (State::Colon, b'/') => {
-                self.url_buffer[self.pos] = char;
-                self.pos += 1;
-                self.state = State::Slash1;
+            State::Url | State::UrlEnd if char.is_ascii_whitespace() => {
+                self.state = State::UrlEnd;
                 Ok(None)
             }
-            (State::Slash1, b'/') => {
+            State::UrlEnd => self.skip_line(M3UError::MalformedUrl),
+            State::LineStart | State::Url => {
+                if matches!(self.state, State::LineStart) {
+                    self.pos = 0;
+                    self.state = State::Url;
+                }
+                if self.pos == MAX_URL_LEN {
+                    return self.skip_line(M3UError::UrlTooLong);
+                }
                 self.url_buffer[self.pos] = char;
                 self.pos += 1;
-                self.state = State::Slash2;
                 Ok(None)
             }
-            (State::Slash2, b'\n') => Err(M3UError::MalformedUrl),
-            (State::Slash2, _) => {
-                if char.is_ascii_whitespace() {
-                    Err(M3UError::MalformedUrl)
-                } else {
-                    self.url_buffer[self.pos] = char;
-                    self.pos += 1;
-                    self.state = State::Rest;
-                    Ok(None)
-                }
-            }
-            (State::Rest, b'\n') | (State::Rest, b'\r') => {
-                // End state
-                let url_str = core::str::from_utf8(&self.url_buffer[0..self.pos])?;
-                let mut url = String::<MAX_URL_LEN>::new();
-
-                url.push_str(url_str).map_err(|_| M3UError::UrlTooLong)?;
-                Ok(Some(url))
-            }
-            (State::Rest, _) => {
-                // Process rest of the url
-                if char.is_ascii_whitespace() {
-                    Err(M3UError::MalformedUrl)
-                } else {
-                    self.url_buffer[self.pos] = char;
-                    self.pos += 1;
-                    self.state = State::Rest;
-                    Ok(None)
-                }
-            }
-            (_, _) => Err(M3UError::InvalidInternalState),
         }
     }
 
@@ -133,16 +141,25 @@ impl<const MAX_URL_LEN: usize> M3U<MAX_URL_LEN> {
     // and no URL found, use this function to return the URL if it exists
     pub fn terminate(&mut self) -> Result<String<MAX_URL_LEN>, M3UError> {
         match self.state.clone() {
-            State::Rest => {
-                let url_str = core::str::from_utf8(&self.url_buffer[0..self.pos])?;
-                let mut url = String::<MAX_URL_LEN>::new();
-
-                url.push_str(url_str).map_err(|_| M3UError::UrlTooLong)?;
-                Ok(url)
+            State::Url | State::UrlEnd => {
+                self.state = State::LineStart;
+                self.url()
             }
             _ => Err(M3UError::MalformedUrl),
         }
     }
+
+    // The rest of the line with the error is skipped
+    fn skip_line(&mut self, error: M3UError) -> Result<Option<String<MAX_URL_LEN>>, M3UError> {
+        self.state = State::SkipLine;
+        Err(error)
+    }
+
+    fn url(&self) -> Result<String<MAX_URL_LEN>, M3UError> {
+        let url_str = core::str::from_utf8(&self.url_buffer[0..self.pos])?;
+        // The buffer is as long as the string, so this always fits
+        String::try_from(url_str).map_err(|_| M3UError::UrlTooLong)
+    }
 }
 
 impl<const MAX_URL_LEN: usize> Default for M3U<MAX_URL_LEN> {
@@ -221,6 +238,23 @@ mod tests {
         assert_eq!(expected_url, url);
     }
 
+    #[test]
+    fn test_parse_https_m3u() {
+        let https_m3u =
+            "#EXTM3U\n#EXTINF:-1,DLF\nhttps://st01.sslstream.dlf.de/dlf/01/128/mp3/stream.mp3\n";
+
+        let mut m3u = M3U::<1024>::new();
+        let url = https_m3u
+            .bytes()
+            .find_map(|b| m3u.parse_m3u(b).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            url,
+            "https://st01.sslstream.dlf.de/dlf/01/128/mp3/stream.mp3"
+        );
+    }
+
     #[test]
     fn test_parse_simple_m3u_unterminated() {
         let simple_m3u_unterminated = "http://listen.181fm.com/181-classical_128k.mp3";
@@ -231,7 +265,7 @@ mod tests {
             let partial_url = m3u.parse_m3u(b).unwrap();
             match partial_url {
                 Some(_) => {
-                    assert!(false, "Should not happen as the url is unterminated")
+                    panic!("Should not happen as the url is unterminated")
                 }
                 None => continue,
             }
@@ -256,7 +290,7 @@ mod tests {
             let r = m3u.parse_m3u(b);
             match r {
                 Ok(None) => continue,
-                Ok(Some(_)) => assert!(false, "Fully parsed malformed URL!"),
+                Ok(Some(_)) => panic!("Fully parsed malformed URL!"),
                 Err(err) => {
                     assert_eq!(err, M3UError::MalformedUrl);
                     break;
@@ -264,4 +298,132 @@ mod tests {
             };
         }
     }
+
+    fn parse_all(
+        m3u: &mut M3U<1024>,
+        playlist: &[u8],
+    ) -> std::vec::Vec<Result<String<1024>, M3UError>> {
+        let mut urls: std::vec::Vec<_> = playlist
+            .iter()
+            .filter_map(|&b| m3u.parse_m3u(b).transpose())
+            .collect();
+        if let Ok(url) = m3u.terminate() {
+            urls.push(Ok(url));
+        }
+        urls
+    }
+
+    #[test]
+    fn test_parse_bom() {
+        let mut m3u = M3U::new();
+        let urls = parse_all(&mut m3u, include_bytes!("../test_resources/bom.m3u"));
+
+        assert_eq!(
+            urls,
+            [Ok(String::try_from(
+                "http://listen.181fm.com/181-classical_128k.mp3"
+            )
+            .unwrap())]
+        );
+    }
+
+    #[test]
+    fn test_parse_uppercase_scheme() {
+        let mut m3u = M3U::new();
+        let urls = parse_all(
+            &mut m3u,
+            include_bytes!("../test_resources/uppercase_scheme.m3u"),
+        );
+
+        assert_eq!(
+            urls,
+            [Ok(String::try_from(
+                "HTTP://listen.181fm.com/181-classical_128k.mp3"
+            )
+            .unwrap())]
+        );
+    }
+
+    #[test]
+    fn test_parse_relative_urls() {
+        // They are resolved by the caller, which knows the URL of the playlist
+        let mut m3u = M3U::new();
+        let urls = parse_all(&mut m3u, include_bytes!("../test_resources/relative.m3u"));
+        assert_eq!(
+            urls,
+            [
+                Ok(String::try_from("181-classical_128k.mp3").unwrap()),
+                Ok(String::try_from("/181-classical_64k.aac").unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_partial_bom() {
+        // Starts like a byte order mark, but is the character U+FEC0
+        let mut m3u = M3U::new();
+        let urls = parse_all(&mut m3u, "\u{FEC0}.mp3\n".as_bytes());
+        assert_eq!(urls, [Ok(String::try_from("\u{FEC0}.mp3").unwrap())]);
+
+        // Only the start of a byte order mark
+        let mut m3u = M3U::new();
+        let urls = parse_all(&mut m3u, b"\xEFhttp://radio.com/hits.mp3\n");
+        assert!(matches!(
+            urls.as_slice(),
+            [Err(M3UError::Utf8ConversionError(_))]
+        ));
+    }
+
+    #[test]
+    fn test_parse_partial_bom_error() {
+        // The URL is already too long with the bytes taken for a byte order mark. The line end
+        // after them still has to be parsed, or the next line would be skipped as well.
+        let mut m3u = M3U::<1>::new();
+        let urls: std::vec::Vec<_> = b"\xEF\xBB\nb\n"
+            .iter()
+            .filter_map(|&b| m3u.parse_m3u(b).transpose())
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                Err(M3UError::UrlTooLong),
+                Ok(String::try_from("b").unwrap())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_titles_and_white_space() {
+        // The titles used to be mistaken for the start of a URL
+        let playlist = b"#EXTM3U\n#EXTINF:-1,The Hits\n  http://radio.com/hits.mp3 \t\n";
+
+        let mut m3u = M3U::new();
+        let urls = parse_all(&mut m3u, playlist);
+        assert_eq!(
+            urls,
+            [Ok(String::try_from("http://radio.com/hits.mp3").unwrap())]
+        );
+    }
+
+    #[test]
+    fn test_parse_url_too_long() {
+        let playlist = std::format!(
+            "http://radio.com/{}\nhttp://radio.com/stream.mp3\n",
+            "a".repeat(20)
+        );
+
+        let mut m3u = M3U::<32>::new();
+        let urls: std::vec::Vec<_> = playlist
+            .bytes()
+            .filter_map(|b| m3u.parse_m3u(b).transpose())
+            .collect();
+        // The rest of the line that is too long is skipped
+        assert_eq!(
+            urls,
+            [
+                Err(M3UError::UrlTooLong),
+                Ok(String::try_from("http://radio.com/stream.mp3").unwrap()),
+            ]
+        );
+    }
 }
@@ -0,0 +1,103 @@
+// Helpers for the parsers of the line based playlist formats, i.e. M3U, HLS and PLS.
+
+use heapless::{String, Vec};
+
+/// A line of a playlist, read in a byte at a time.
+///
+/// Only the first `MAX_LEN` bytes of a longer line are kept, and the line is marked as too
+/// long, so that the parser can ignore it or give an error.
+#[derive(Debug, Default)]
+pub struct LineBuffer<const MAX_LEN: usize> {
+    line: Vec<u8, MAX_LEN>,
+    is_too_long: bool,
+}
+
+impl<const MAX_LEN: usize> LineBuffer<MAX_LEN> {
+    pub fn new() -> LineBuffer<MAX_LEN> {
+        LineBuffer {
+            line: Vec::new(),
+            is_too_long: false,
+        }
+    }
+
+    /// Adds a byte, other than the new line at the end, to the line.
+    pub fn push(&mut self, byte: u8) {
+        if self.line.push(byte).is_err() {
+            self.is_too_long = true;
+        }
+    }
+
+    /// The line, or its start if it is too long.
+    pub fn line(&self) -> &[u8] {
+        &self.line
+    }
+
+    pub fn is_too_long(&self) -> bool {
+        self.is_too_long
+    }
+
+    /// Returns true if nothing has been added since the line was cleared.
+    pub fn is_empty(&self) -> bool {
+        self.line.is_empty() && !self.is_too_long
+    }
+
+    /// Clears the line for the next one.
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.is_too_long = false;
+    }
+}
+
+/// Trims the text and returns it if there is any left, truncated if it is too long.
+pub fn non_empty<const LEN: usize>(text: &str) -> Option<String<LEN>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let mut s = String::new();
+    for c in text.chars() {
+        if s.push(c).is_err() {
+            break;
+        }
+    }
+    Some(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_buffer() {
+        let mut line = LineBuffer::<4>::new();
+        assert!(line.is_empty());
+
+        b"abc".iter().for_each(|&b| line.push(b));
+        assert_eq!(line.line(), b"abc");
+        assert!(!line.is_too_long());
+
+        b"def".iter().for_each(|&b| line.push(b));
+        assert_eq!(line.line(), b"abcd");
+        assert!(line.is_too_long());
+        assert!(!line.is_empty());
+
+        line.clear();
+        assert!(line.is_empty());
+        assert!(!line.is_too_long());
+    }
+
+    #[test]
+    fn test_non_empty() {
+        assert_eq!(non_empty::<8>(" \t"), None);
+        assert_eq!(
+            non_empty::<8>(" Radio "),
+            Some(String::try_from("Radio").unwrap())
+        );
+        // Truncated at a character boundary
+        assert_eq!(
+            non_empty::<4>("Rädio"),
+            Some(String::try_from("Räd").unwrap())
+        );
+    }
+}
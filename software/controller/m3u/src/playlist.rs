@@ -0,0 +1,141 @@
+// The common interface of the playlist parsers, so that playlists of any format can be
+// followed in the same way.
+
+use crate::{Asx, Entry, ExtendedM3U, M3UError, Xspf};
+
+/// An entry of a playlist, whatever the format of the playlist.
+pub trait PlaylistEntry {
+    fn url(&self) -> &str;
+
+    fn title(&self) -> Option<&str>;
+}
+
+/// A parser that is given a playlist a byte at a time and returns its entries.
+pub trait PlaylistParser {
+    type Entry: PlaylistEntry;
+    type Error;
+
+    /// Parses the next byte of the playlist. Returns an entry once all of it has been read.
+    ///
+    /// After an error the parser continues with the next entry, so that the entry with the
+    /// error can be skipped.
+    fn parse(&mut self, byte: u8) -> Result<Option<Self::Entry>, Self::Error>;
+
+    /// Returns the entries left once the end of the playlist has been reached. Call it until
+    /// it returns `Ok(None)`.
+    fn terminate(&mut self) -> Result<Option<Self::Entry>, Self::Error>;
+
+    /// The title of the whole playlist, if the format has one and it has been read yet.
+    fn playlist_title(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl<const LEN: usize> PlaylistEntry for Entry<LEN> {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+}
+
+impl<const LEN: usize> PlaylistParser for ExtendedM3U<LEN> {
+    type Entry = Entry<LEN>;
+    type Error = M3UError;
+
+    fn parse(&mut self, byte: u8) -> Result<Option<Entry<LEN>>, M3UError> {
+        ExtendedM3U::parse(self, byte)
+    }
+
+    fn terminate(&mut self) -> Result<Option<Entry<LEN>>, M3UError> {
+        ExtendedM3U::terminate(self)
+    }
+
+    fn playlist_title(&self) -> Option<&str> {
+        ExtendedM3U::playlist_title(self)
+    }
+}
+
+impl<const LEN: usize> PlaylistParser for Asx<LEN> {
+    type Entry = Entry<LEN>;
+    type Error = M3UError;
+
+    fn parse(&mut self, byte: u8) -> Result<Option<Entry<LEN>>, M3UError> {
+        Asx::parse(self, byte)
+    }
+
+    fn terminate(&mut self) -> Result<Option<Entry<LEN>>, M3UError> {
+        Asx::terminate(self)
+    }
+
+    fn playlist_title(&self) -> Option<&str> {
+        Asx::playlist_title(self)
+    }
+}
+
+impl<const LEN: usize> PlaylistParser for Xspf<LEN> {
+    type Entry = Entry<LEN>;
+    type Error = M3UError;
+
+    fn parse(&mut self, byte: u8) -> Result<Option<Entry<LEN>>, M3UError> {
+        Xspf::parse(self, byte)
+    }
+
+    fn terminate(&mut self) -> Result<Option<Entry<LEN>>, M3UError> {
+        Xspf::terminate(self)
+    }
+
+    fn playlist_title(&self) -> Option<&str> {
+        Xspf::playlist_title(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads the URLs of a whole playlist through the trait
+    fn urls<P: PlaylistParser>(
+        mut parser: P,
+        playlist: &str,
+    ) -> std::vec::Vec<std::string::String> {
+        let mut urls: std::vec::Vec<std::string::String> = playlist
+            .bytes()
+            .filter_map(|b| parser.parse(b).ok().flatten())
+            .map(|entry| entry.url().into())
+            .collect();
+        while let Ok(Some(entry)) = parser.terminate() {
+            urls.push(entry.url().into());
+        }
+        urls
+    }
+
+    #[test]
+    fn test_all_formats() {
+        let expected = ["http://a.de/1", "http://a.de/2"];
+
+        assert_eq!(
+            urls(
+                ExtendedM3U::<64>::new(),
+                "#EXTM3U\nhttp://a.de/1\nhttp://a.de/2"
+            ),
+            expected
+        );
+        assert_eq!(
+            urls(
+                Asx::<64>::new(),
+                "<asx><entry><ref href=\"http://a.de/1\"/></entry><entry><ref href=\"http://a.de/2\"/>"
+            ),
+            expected
+        );
+        assert_eq!(
+            urls(
+                Xspf::<64>::new(),
+                "<playlist><trackList><track><location>http://a.de/1</location></track><track><location>http://a.de/2</location></track></trackList></playlist>"
+            ),
+            expected
+        );
+    }
+}
@@ -0,0 +1,440 @@
+// Streaming parser for the XML playlist formats, ASX and XSPF.
+//
+// This is not a full XML parser. It only scans for the start and end tags, their attributes
+// and the text between them, which is enough to find the entries of a playlist. Comments,
+// declarations and processing instructions are skipped.
+
+use heapless::{String, Vec};
+
+use crate::{Entry, M3UError, MAX_LINE_LEN, non_empty};
+
+// What the scanner has found once a tag or the text before it is complete
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    // A start tag, e.g. <ref href="...">, with the attributes after the name
+    Start {
+        name: &'a [u8],
+        attributes: &'a [u8],
+        // An empty element, e.g. <ref href="..."/>, which has no end tag
+        is_empty: bool,
+    },
+    End {
+        name: &'a [u8],
+    },
+    // The text before a tag, with the entities (e.g. &amp;) still in it
+    Text(&'a [u8]),
+}
+
+// Scans the tags and text of the playlist a byte at a time
+struct TagScanner {
+    buffer: Vec<u8, MAX_LINE_LEN>,
+    is_truncated: bool,
+    in_tag: bool,
+    // The quote around an attribute value, which can contain '>'
+    quote: Option<u8>,
+    // The last two bytes of the tag, to find the end of a comment that is too long for the
+    // buffer
+    last_two: [u8; 2],
+    // The buffer has been returned in a token, so is cleared with the next byte
+    is_complete: bool,
+}
+
+impl TagScanner {
+    fn new() -> TagScanner {
+        TagScanner {
+            buffer: Vec::new(),
+            is_truncated: false,
+            in_tag: false,
+            quote: None,
+            last_two: [0; 2],
+            is_complete: false,
+        }
+    }
+
+    // Returns a token once it is complete, with whether it was too long and so truncated
+    fn scan(&mut self, byte: u8) -> Option<(Token<'_>, bool)> {
+        if self.is_complete {
+            self.buffer.clear();
+            self.is_truncated = false;
+            self.last_two = [0; 2];
+            self.is_complete = false;
+        }
+
+        if !self.in_tag {
+            if byte == b'<' {
+                self.in_tag = true;
+                self.is_complete = true;
+                return Some((Token::Text(&self.buffer), self.is_truncated));
+            }
+        } else if self.quote == Some(byte) {
+            self.quote = None;
+        } else if self.quote.is_none() {
+            let is_comment = self.buffer.starts_with(b"!--");
+            match byte {
+                b'"' | b'\'' if !is_comment => self.quote = Some(byte),
+                // A comment only ends with -->
+                b'>' if !is_comment || self.last_two == *b"--" => {
+                    self.in_tag = false;
+                    self.is_complete = true;
+                    return tag_token(&self.buffer).map(|token| (token, self.is_truncated));
+                }
+                _ => (),
+            }
+        }
+
+        if self.buffer.push(byte).is_err() {
+            self.is_truncated = true;
+        }
+        self.last_two = [self.last_two[1], byte];
+        None
+    }
+}
+
+// Returns the token for what is between < and >, if it is a start or end tag
+fn tag_token(tag: &[u8]) -> Option<Token<'_>> {
+    match tag.first()? {
+        // Comments, declarations and processing instructions
+        b'!' | b'?' => None,
+        b'/' => Some(Token::End {
+            name: tag[1..].trim_ascii(),
+        }),
+        _ => {
+            let tag = tag.trim_ascii_end();
+            let (tag, is_empty) = match tag.strip_suffix(b"/") {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let name_len = tag
+                .iter()
+                .position(u8::is_ascii_whitespace)
+                .unwrap_or(tag.len());
+            let (name, attributes) = tag.split_at(name_len);
+            Some(Token::Start {
+                name,
+                attributes,
+                is_empty,
+            })
+        }
+    }
+}
+
+// Returns the value of an attribute in a list such as href="http://..." title='x'
+fn attribute_value<'a>(attributes: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let mut rest = attributes;
+
+    loop {
+        let separator = rest.iter().position(|&b| b == b'=')?;
+        let key = rest[..separator].trim_ascii();
+        let after = rest[separator + 1..].trim_ascii_start();
+
+        let (value, next) = match after.first() {
+            Some(&quote @ (b'"' | b'\'')) => {
+                let quoted = &after[1..];
+                let end = quoted
+                    .iter()
+                    .position(|&b| b == quote)
+                    .unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+            }
+            _ => {
+                let end = after
+                    .iter()
+                    .position(u8::is_ascii_whitespace)
+                    .unwrap_or(after.len());
+                after.split_at(end)
+            }
+        };
+
+        if key.eq_ignore_ascii_case(name.as_bytes()) {
+            return Some(value);
+        }
+        rest = next;
+    }
+}
+
+// Replaces the XML entities in the text, e.g. &amp; with &. Returns false if it did not all
+// fit in the string.
+fn decode<const LEN: usize>(text: &str, decoded: &mut String<LEN>) -> bool {
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        if decoded.push_str(&rest[..start]).is_err() {
+            return false;
+        }
+        rest = &rest[start..];
+
+        let known_entity = rest
+            .find(';')
+            .and_then(|end| Some((entity(&rest[1..end])?, end)));
+        let c = match known_entity {
+            Some((c, end)) => {
+                rest = &rest[end + 1..];
+                c
+            }
+            // Not an entity, so keep the &
+            None => {
+                rest = &rest[1..];
+                '&'
+            }
+        };
+        if decoded.push(c).is_err() {
+            return false;
+        }
+    }
+
+    decoded.push_str(rest).is_ok()
+}
+
+// Returns the character for the name of an entity, e.g. amp or #38
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+fn parse_url<const LEN: usize>(url: &[u8], is_truncated: bool) -> Result<String<LEN>, M3UError> {
+    if is_truncated {
+        return Err(M3UError::UrlTooLong);
+    }
+    let url = core::str::from_utf8(url)?.trim();
+    if url.is_empty() || url.contains(|c: char| c.is_ascii_whitespace()) {
+        return Err(M3UError::MalformedUrl);
+    }
+
+    let mut decoded = String::new();
+    if !decode(url, &mut decoded) {
+        return Err(M3UError::UrlTooLong);
+    }
+    Ok(decoded)
+}
+
+// Returns the title if there is one, truncated if it is too long
+fn parse_title<const LEN: usize>(title: &[u8]) -> Option<String<LEN>> {
+    let title = core::str::from_utf8(title).ok()?;
+    let mut decoded = String::<MAX_LINE_LEN>::new();
+    decode(title, &mut decoded);
+    non_empty(&decoded)
+}
+
+// Where the URL of an entry is given
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum UrlSource {
+    // In an attribute of an element, e.g. <ref href="...">
+    Attribute {
+        element: &'static str,
+        attribute: &'static str,
+    },
+    // In the text of an element, e.g. <location>...</location>
+    Text {
+        element: &'static str,
+    },
+}
+
+// The elements used by a format
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Format {
+    // The element of an entry
+    pub(crate) entry: &'static str,
+    pub(crate) url: UrlSource,
+}
+
+const TITLE: &str = "title";
+
+// The element whose text is wanted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextFor {
+    Title,
+    Url,
+}
+
+// An entry of which not all the elements have been read yet
+#[derive(Default)]
+struct PartialEntry<const LEN: usize> {
+    // An invalid URL is only reported when the entry is complete
+    url: Option<Result<String<LEN>, M3UError>>,
+    title: Option<String<LEN>>,
+}
+
+// Parses an XML playlist in the format as it arrives, returning the entries one at a time
+pub(crate) struct XmlPlaylist<const LEN: usize> {
+    format: Format,
+    scanner: TagScanner,
+    entry: Option<PartialEntry<LEN>>,
+    text_for: Option<TextFor>,
+    playlist_title: Option<String<LEN>>,
+}
+
+impl<const LEN: usize> XmlPlaylist<LEN> {
+    pub(crate) fn new(format: Format) -> XmlPlaylist<LEN> {
+        XmlPlaylist {
+            format,
+            scanner: TagScanner::new(),
+            entry: None,
+            text_for: None,
+            playlist_title: None,
+        }
+    }
+
+    pub(crate) fn playlist_title(&self) -> Option<&str> {
+        self.playlist_title.as_deref()
+    }
+
+    pub(crate) fn parse(&mut self, byte: u8) -> Result<Option<Entry<LEN>>, M3UError> {
+        let Some((token, is_truncated)) = self.scanner.scan(byte) else {
+            return Ok(None);
+        };
+        let is = |name: &[u8], element: &str| name.eq_ignore_ascii_case(element.as_bytes());
+
+        match token {
+            Token::Text(text) => match (self.text_for.take(), self.entry.as_mut()) {
+                (Some(TextFor::Title), Some(entry)) => entry.title = parse_title(text),
+                (Some(TextFor::Title), None) => self.playlist_title = parse_title(text),
+                (Some(TextFor::Url), Some(entry)) => {
+                    entry.url = Some(parse_url(text, is_truncated));
+                }
+                _ => (),
+            },
+            Token::Start {
+                name,
+                attributes,
+                is_empty,
+            } => {
+                self.text_for = None;
+                if is(name, self.format.entry) {
+                    self.entry = Some(PartialEntry::default());
+                } else if is(name, TITLE) && !is_empty {
+                    self.text_for = Some(TextFor::Title);
+                } else if let Some(entry) = self.entry.as_mut()
+                    && entry.url.is_none()
+                {
+                    // Only the first URL of an entry is used
+                    match self.format.url {
+                        UrlSource::Attribute { element, attribute } if is(name, element) => {
+                            entry.url = Some(match attribute_value(attributes, attribute) {
+                                Some(url) => parse_url(url, is_truncated),
+                                None if is_truncated => Err(M3UError::UrlTooLong),
+                                None => Err(M3UError::MalformedUrl),
+                            });
+                        }
+                        UrlSource::Text { element } if is(name, element) && !is_empty => {
+                            self.text_for = Some(TextFor::Url);
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            Token::End { name } => {
+                self.text_for = None;
+                if is(name, self.format.entry) {
+                    return self.entry.take().map_or(Ok(None), complete);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub(crate) fn terminate(&mut self) -> Result<Option<Entry<LEN>>, M3UError> {
+        self.text_for = None;
+        self.entry.take().map_or(Ok(None), complete)
+    }
+}
+
+// Returns the entry, unless it has no URL
+fn complete<const LEN: usize>(entry: PartialEntry<LEN>) -> Result<Option<Entry<LEN>>, M3UError> {
+    match entry.url {
+        Some(url) => Ok(Some(Entry {
+            url: url?,
+            title: entry.title,
+            ..Entry::default()
+        })),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan_all(xml: &str) -> std::vec::Vec<std::string::String> {
+        let mut scanner = TagScanner::new();
+        xml.bytes()
+            .filter_map(|b| {
+                scanner.scan(b).map(|(token, _)| match token {
+                    Token::Start {
+                        name,
+                        attributes,
+                        is_empty,
+                    } => std::format!(
+                        "<{}|{}|{}>",
+                        std::str::from_utf8(name).unwrap(),
+                        std::str::from_utf8(attributes).unwrap().trim(),
+                        is_empty
+                    ),
+                    Token::End { name } => {
+                        std::format!("</{}>", std::str::from_utf8(name).unwrap())
+                    }
+                    Token::Text(text) => std::str::from_utf8(text).unwrap().into(),
+                })
+            })
+            .filter(|token| !token.trim().is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn test_scan() {
+        let tokens =
+            scan_all("<?xml version=\"1.0\"?><!-- a > b --><a x='1>2'>text<b y=\"2\" /></a >");
+
+        assert_eq!(
+            tokens,
+            ["<a|x='1>2'|false>", "text", "<b|y=\"2\"|true>", "</a>"]
+        );
+    }
+
+    #[test]
+    fn test_scan_long_comment() {
+        let comment = "x".repeat(MAX_LINE_LEN + 10);
+        let tokens = scan_all(&std::format!("<!-- {comment} --><a>text</a>"));
+
+        assert_eq!(tokens, ["<a||false>", "text", "</a>"]);
+    }
+
+    #[test]
+    fn test_attribute_value() {
+        let attributes = b" href = \"http://a.de/s?x=1&amp;y=2\" title='A B' id=3";
+
+        assert_eq!(
+            attribute_value(attributes, "HREF"),
+            Some(&b"http://a.de/s?x=1&amp;y=2"[..])
+        );
+        assert_eq!(attribute_value(attributes, "title"), Some(&b"A B"[..]));
+        assert_eq!(attribute_value(attributes, "id"), Some(&b"3"[..]));
+        assert_eq!(attribute_value(attributes, "src"), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut decoded = String::<64>::new();
+        assert!(decode(
+            "Rock &amp; Roll &lt;3 &#38;&#x26; &unknown; &",
+            &mut decoded
+        ));
+        assert_eq!(decoded, "Rock & Roll <3 && &unknown; &");
+
+        let mut decoded = String::<4>::new();
+        assert!(!decode("a&amp;bcd", &mut decoded));
+    }
+}
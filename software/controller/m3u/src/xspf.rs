@@ -0,0 +1,110 @@
+// Streaming parser for XSPF ("spiff") playlists, see https://www.xspf.org/spec
+//
+// An XSPF playlist looks like this:
+//
+// <?xml version="1.0" encoding="UTF-8"?>
+// <playlist version="1" xmlns="http://xspf.org/ns/0/">
+//   <title>Radio</title>
+//   <trackList>
+//     <track>
+//       <location>http://example.com/stream.mp3</location>
+//       <title>Station name</title>
+//     </track>
+//   </trackList>
+// </playlist>
+//
+// A track can have more than one location, of which only the first is used.
+
+use crate::xml::{Format, UrlSource, XmlPlaylist};
+use crate::{Entry, M3UError};
+
+const XSPF: Format = Format {
+    entry: "track",
+    url: UrlSource::Text {
+        element: "location",
+    },
+};
+
+/// Parses an XSPF playlist as it arrives, returning the entries (tracks) one at a time.
+///
+/// The URLs are limited to `LEN` bytes.
+pub struct Xspf<const LEN: usize> {
+    playlist: XmlPlaylist<LEN>,
+}
+
+impl<const LEN: usize> Xspf<LEN> {
+    pub fn new() -> Xspf<LEN> {
+        Xspf {
+            playlist: XmlPlaylist::new(XSPF),
+        }
+    }
+
+    /// The title of the whole playlist, if it has been read yet.
+    pub fn playlist_title(&self) -> Option<&str> {
+        self.playlist.playlist_title()
+    }
+
+    // Give the bytes of the playlist one at a time. Returns an entry at the end of its
+    // track element, otherwise None.
+    // An entry with an invalid URL is returned as an error. The parser then continues with the
+    // next entry, so that the entry with the error can be skipped.
+    pub fn parse(&mut self, byte: u8) -> Result<Option<Entry<LEN>>, M3UError> {
+        self.playlist.parse(byte)
+    }
+
+    // Once the end of the playlist has been reached, use this function to return the last
+    // entry if it was not closed.
+    pub fn terminate(&mut self) -> Result<Option<Entry<LEN>>, M3UError> {
+        self.playlist.terminate()
+    }
+}
+
+impl<const LEN: usize> Default for Xspf<LEN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XSPF_PLAYLIST: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <title>Radio</title>\n  <trackList>\n    <track>\n      <location>\n        http://example.com/stream.mp3\n      </location>\n      <location>http://backup.example.com/stream.mp3</location>\n      <title>Rock &amp; Roll</title>\n    </track>\n    <track><title/><location>http://example.com/second.aac</location></track>\n  </trackList>\n</playlist>\n";
+
+    fn parse_all(playlist: &str) -> (Xspf<64>, std::vec::Vec<Result<Entry<64>, M3UError>>) {
+        let mut xspf = Xspf::<64>::new();
+        let mut entries: std::vec::Vec<_> = playlist
+            .bytes()
+            .filter_map(|b| xspf.parse(b).transpose())
+            .collect();
+        entries.extend(xspf.terminate().transpose());
+        (xspf, entries)
+    }
+
+    #[test]
+    fn test_parse() {
+        let (xspf, entries) = parse_all(XSPF_PLAYLIST);
+
+        assert_eq!(xspf.playlist_title(), Some("Radio"));
+        assert_eq!(entries.len(), 2);
+
+        let entry = entries[0].as_ref().unwrap();
+        assert_eq!(entry.title.as_deref(), Some("Rock & Roll"));
+        assert_eq!(entry.url, "http://example.com/stream.mp3");
+
+        let entry = entries[1].as_ref().unwrap();
+        assert_eq!(entry.title, None);
+        assert_eq!(entry.url, "http://example.com/second.aac");
+    }
+
+    #[test]
+    fn test_skip_invalid_entry() {
+        let (_, entries) = parse_all(
+            "<playlist><trackList><track><location>http://a.de/ s</location></track><track><location>http://a.de/s</location></track></trackList></playlist>",
+        );
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], Err(M3UError::MalformedUrl));
+        assert_eq!(entries[1].as_ref().unwrap().url, "http://a.de/s");
+    }
+}
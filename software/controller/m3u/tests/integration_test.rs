@@ -0,0 +1,56 @@
+use http::join_url;
+use m3u::{ExtendedM3U, M3U, PlaylistEntry, PlaylistParser};
+
+const PLAYLIST_URL: &str = "http://listen.181fm.com/m3u/181-classical.m3u";
+
+// Resolves the entries against the URL of the playlist, as the stream task does
+fn resolve_all<P: PlaylistParser>(parser: &mut P, playlist: &[u8]) -> Vec<String> {
+    let mut entries: Vec<_> = playlist
+        .iter()
+        .filter_map(|&b| parser.parse(b).ok().flatten())
+        .collect();
+    while let Ok(Some(entry)) = parser.terminate() {
+        entries.push(entry);
+    }
+    entries
+        .iter()
+        .map(|entry| join_url(PLAYLIST_URL, entry.url()).unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_resolve_relative_entries() {
+    let playlist = include_bytes!("../test_resources/relative.m3u");
+
+    let urls = resolve_all(&mut ExtendedM3U::<256>::new(), playlist);
+    assert_eq!(
+        urls,
+        [
+            "http://listen.181fm.com/m3u/181-classical_128k.mp3",
+            "http://listen.181fm.com/181-classical_64k.aac"
+        ]
+    );
+}
+
+#[test]
+fn test_resolve_relative_url() {
+    let playlist = include_bytes!("../test_resources/relative.m3u");
+
+    let mut m3u = M3U::<256>::new();
+    let url = playlist
+        .iter()
+        .find_map(|&b| m3u.parse_m3u(b).unwrap())
+        .unwrap();
+    assert_eq!(
+        join_url(PLAYLIST_URL, &url).unwrap(),
+        "http://listen.181fm.com/m3u/181-classical_128k.mp3"
+    );
+}
+
+#[test]
+fn test_absolute_entries_unchanged() {
+    let playlist = include_bytes!("../test_resources/bom.m3u");
+
+    let urls = resolve_all(&mut ExtendedM3U::<256>::new(), playlist);
+    assert_eq!(urls, ["http://listen.181fm.com/181-classical_128k.mp3"]);
+}
@@ -0,0 +1,389 @@
+#![cfg_attr(not(test), no_std)]
+
+// See the description of the format on Wikipedia at https://en.wikipedia.org/wiki/PLS_(file_format)
+//
+// A PLS playlist looks like this:
+//
+// [playlist]
+// File1=http://listen.181fm.com/181-classical_128k.mp3
+// Title1=181.FM Classical
+// Length1=-1
+// NumberOfEntries=1
+// Version=2
+//
+// The keys of an entry end with its number. The entries are returned once all the keys for them
+// have been read, which is when the key of another entry or the end of the playlist is reached.
+
+use core::str::Utf8Error;
+
+use heapless::String;
+use m3u::{LineBuffer, PlaylistEntry, PlaylistParser, non_empty};
+
+/// The maximum length of a line. The values of longer lines are ignored, except for URLs
+/// which give an error.
+pub const MAX_LINE_LEN: usize = 512;
+
+/// An entry of a playlist.
+///
+/// The title is limited to `LEN` bytes and is truncated if longer.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Entry<const LEN: usize> {
+    /// The number of the entry in the playlist, starting at 1
+    pub number: u32,
+    pub url: String<LEN>,
+    pub title: Option<String<LEN>>,
+    /// The duration in seconds, -1 for a stream. `None` if not given.
+    pub duration: Option<i32>,
+}
+
+// An entry of which not all the keys have been read yet
+struct PartialEntry<const LEN: usize> {
+    number: u32,
+    // An invalid URL is only reported when the entry is complete
+    url: Option<Result<String<LEN>, PlsError>>,
+    title: Option<String<LEN>>,
+    duration: Option<i32>,
+}
+
+/// Parses a PLS playlist as it arrives, returning the entries one at a time.
+///
+/// The URLs are limited to `LEN` bytes.
+pub struct Pls<const LEN: usize> {
+    line: LineBuffer<MAX_LINE_LEN>,
+    entry: Option<PartialEntry<LEN>>,
+    number_of_entries: Option<usize>,
+}
+
+impl<const LEN: usize> Pls<LEN> {
+    pub fn new() -> Pls<LEN> {
+        Pls {
+            line: LineBuffer::new(),
+            entry: None,
+            number_of_entries: None,
+        }
+    }
+
+    /// Returns an iterator over the entries of a whole playlist.
+    pub fn entries(data: &[u8]) -> Entries<'_, LEN> {
+        Entries {
+            data: data.iter(),
+            pls: Pls::new(),
+        }
+    }
+
+    /// The number of entries given in the playlist, if it has been read yet.
+    pub fn number_of_entries(&self) -> Option<usize> {
+        self.number_of_entries
+    }
+
+    // Give the bytes of the playlist one at a time. Returns an entry once all of it has been
+    // read, otherwise None.
+    // An entry with an invalid URL is returned as an error. The parser then continues with the
+    // next entry, so that the entry with the error can be skipped.
+    pub fn parse(&mut self, byte: u8) -> Result<Option<Entry<LEN>>, PlsError> {
+        match byte {
+            b'\n' | b'\r' => self.end_line(),
+            _ => {
+                self.line.push(byte);
+                Ok(None)
+            }
+        }
+    }
+
+    // Once the end of the playlist has been reached, use this function to return the last
+    // entries. Unlike with M3U, this is needed even if the playlist ends with a new line.
+    // Call it until it returns Ok(None), as a last line without a new line can complete one
+    // entry and start another.
+    pub fn terminate(&mut self) -> Result<Option<Entry<LEN>>, PlsError> {
+        if !self.line.is_empty() {
+            let result = self.end_line();
+            if !matches!(result, Ok(None)) {
+                return result;
+            }
+        }
+
+        match self.entry.take() {
+            Some(entry) => complete(entry),
+            None => Ok(None),
+        }
+    }
+
+    fn end_line(&mut self) -> Result<Option<Entry<LEN>>, PlsError> {
+        let result = self.parse_line();
+        self.line.clear();
+        result
+    }
+
+    fn parse_line(&mut self) -> Result<Option<Entry<LEN>>, PlsError> {
+        let line = self.line.line().trim_ascii();
+        let Some(separator) = line.iter().position(|&b| b == b'=') else {
+            // Empty lines and the [playlist] header
+            return Ok(None);
+        };
+        let (key, value) = (line[..separator].trim_ascii(), &line[separator + 1..]);
+
+        // The key is the name and the number of the entry, e.g. File1
+        let digits = key.iter().position(u8::is_ascii_digit).unwrap_or(key.len());
+        let (name, number) = key.split_at(digits);
+        let number = core::str::from_utf8(number)
+            .ok()
+            .and_then(|number| number.parse::<u32>().ok());
+
+        let Some(number) = number else {
+            if name.eq_ignore_ascii_case(b"NumberOfEntries") {
+                self.number_of_entries = core::str::from_utf8(value)
+                    .ok()
+                    .and_then(|value| value.trim().parse().ok());
+            }
+            // Other keys, e.g. Version, are not needed
+            return Ok(None);
+        };
+
+        // The key is for another entry, so the current entry is complete
+        let completed = match self.entry.take() {
+            Some(entry) if entry.number != number => Some(entry),
+            entry => {
+                self.entry = entry;
+                None
+            }
+        };
+        let entry = self.entry.get_or_insert(PartialEntry {
+            number,
+            url: None,
+            title: None,
+            duration: None,
+        });
+
+        let value = if self.line.is_too_long() {
+            Err(PlsError::UrlTooLong)
+        } else {
+            core::str::from_utf8(value)
+                .map(str::trim)
+                .map_err(PlsError::from)
+        };
+
+        if name.eq_ignore_ascii_case(b"File") {
+            entry.url = Some(value.and_then(parse_url));
+        } else if name.eq_ignore_ascii_case(b"Title") {
+            entry.title = value.ok().and_then(non_empty);
+        } else if name.eq_ignore_ascii_case(b"Length") {
+            entry.duration = value.ok().and_then(|value| value.parse().ok());
+        }
+
+        match completed {
+            Some(entry) => complete(entry),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<const LEN: usize> Default for Pls<LEN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const LEN: usize> PlaylistParser for Pls<LEN> {
+    type Entry = Entry<LEN>;
+    type Error = PlsError;
+
+    fn parse(&mut self, byte: u8) -> Result<Option<Entry<LEN>>, PlsError> {
+        Pls::parse(self, byte)
+    }
+
+    fn terminate(&mut self) -> Result<Option<Entry<LEN>>, PlsError> {
+        Pls::terminate(self)
+    }
+}
+
+impl<const LEN: usize> PlaylistEntry for Entry<LEN> {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+}
+
+/// An iterator over the entries of a whole playlist, see `Pls::entries`.
+pub struct Entries<'a, const LEN: usize> {
+    data: core::slice::Iter<'a, u8>,
+    pls: Pls<LEN>,
+}
+
+impl<const LEN: usize> Entries<'_, LEN> {
+    /// The number of entries given in the playlist, if it has been read yet.
+    pub fn number_of_entries(&self) -> Option<usize> {
+        self.pls.number_of_entries()
+    }
+}
+
+impl<const LEN: usize> Iterator for Entries<'_, LEN> {
+    type Item = Result<Entry<LEN>, PlsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for &b in self.data.by_ref() {
+            if let Some(result) = self.pls.parse(b).transpose() {
+                return Some(result);
+            }
+        }
+
+        self.pls.terminate().transpose()
+    }
+}
+
+// Returns the entry if it has a URL. Entries without one are ignored.
+fn complete<const LEN: usize>(entry: PartialEntry<LEN>) -> Result<Option<Entry<LEN>>, PlsError> {
+    let Some(url) = entry.url else {
+        return Ok(None);
+    };
+
+    Ok(Some(Entry {
+        number: entry.number,
+        url: url?,
+        title: entry.title,
+        duration: entry.duration,
+    }))
+}
+
+fn parse_url<const LEN: usize>(url: &str) -> Result<String<LEN>, PlsError> {
+    if url.is_empty() || url.contains(|c: char| c.is_ascii_whitespace()) {
+        return Err(PlsError::MalformedUrl);
+    }
+    String::try_from(url).map_err(|_| PlsError::UrlTooLong)
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum PlsError {
+    Utf8ConversionError(Utf8Error),
+    UrlTooLong,
+    MalformedUrl,
+}
+
+impl From<Utf8Error> for PlsError {
+    fn from(e: Utf8Error) -> Self {
+        Self::Utf8ConversionError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLS: &str = "[playlist]\nFile1=http://listen.181fm.com/181-classical_128k.mp3\nTitle1=181.FM Classical\nLength1=-1\n\nFile2=https://listen.181fm.com/181-classical_128k.mp3\r\nLength2=-1\r\nNumberOfEntries=2\nVersion=2\n";
+
+    #[test]
+    fn test_parse() {
+        let mut pls = Pls::<256>::new();
+        let mut entries = std::vec::Vec::new();
+        for b in PLS.bytes() {
+            if let Some(entry) = pls.parse(b).unwrap() {
+                entries.push(entry);
+            }
+        }
+        // The second entry is only known to be complete at the end
+        assert_eq!(entries.len(), 1);
+        entries.extend(pls.terminate().unwrap());
+        assert_eq!(pls.terminate(), Ok(None));
+
+        assert_eq!(pls.number_of_entries(), Some(2));
+        assert_eq!(
+            entries,
+            [
+                Entry {
+                    number: 1,
+                    url: String::try_from("http://listen.181fm.com/181-classical_128k.mp3")
+                        .unwrap(),
+                    title: Some(String::try_from("181.FM Classical").unwrap()),
+                    duration: Some(-1),
+                },
+                Entry {
+                    number: 2,
+                    url: String::try_from("https://listen.181fm.com/181-classical_128k.mp3")
+                        .unwrap(),
+                    title: None,
+                    duration: Some(-1),
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entries() {
+        let urls: std::vec::Vec<_> = Pls::<256>::entries(PLS.as_bytes())
+            .map(|entry| entry.unwrap().url)
+            .collect();
+
+        assert_eq!(
+            urls,
+            [
+                "http://listen.181fm.com/181-classical_128k.mp3",
+                "https://listen.181fm.com/181-classical_128k.mp3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_and_lower_case() {
+        let mut entries = Pls::<256>::entries(
+            b"[playlist]\nnumberofentries=1\ntitle1=Radio\nfile1=http://radio.com/stream",
+        );
+
+        let entry = entries.next().unwrap().unwrap();
+        assert_eq!(entry.url, "http://radio.com/stream");
+        assert_eq!(entry.title.unwrap(), "Radio");
+        assert_eq!(entries.next(), None);
+        assert_eq!(entries.number_of_entries(), Some(1));
+    }
+
+    #[test]
+    fn test_terminate_completes_two_entries() {
+        let mut pls = Pls::<256>::new();
+        for b in b"File1=http://radio.com/1\nFile2=http://radio.com/2" {
+            assert_eq!(pls.parse(*b), Ok(None));
+        }
+
+        assert_eq!(pls.terminate().unwrap().unwrap().number, 1);
+        assert_eq!(pls.terminate().unwrap().unwrap().number, 2);
+        assert_eq!(pls.terminate(), Ok(None));
+    }
+
+    #[test]
+    fn test_skip_invalid_entry() {
+        let entries: std::vec::Vec<_> = Pls::<32>::entries(
+            b"[playlist]\nFile1=http://radio.com/ stream\nTitle1=Broken\nFile2=http://radio.com/this/url/is/too/long\nFile3=http://radio.com/stream\nFile4=\n",
+        )
+        .collect();
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0], Err(PlsError::MalformedUrl));
+        assert_eq!(entries[1], Err(PlsError::UrlTooLong));
+        assert_eq!(entries[2].as_ref().unwrap().url, "http://radio.com/stream");
+        assert_eq!(entries[3], Err(PlsError::MalformedUrl));
+    }
+
+    #[test]
+    fn test_line_too_long() {
+        let pls = std::format!(
+            "[playlist]\nFile1=http://radio.com/stream\nTitle1={}\nFile2=http://radio.com/{}\n",
+            "a".repeat(MAX_LINE_LEN),
+            "b".repeat(MAX_LINE_LEN)
+        );
+
+        let entries: std::vec::Vec<_> = Pls::<1024>::entries(pls.as_bytes()).collect();
+
+        // The title that is too long is left out
+        assert_eq!(entries[0].as_ref().unwrap().title, None);
+        assert_eq!(entries[1], Err(PlsError::UrlTooLong));
+    }
+
+    #[test]
+    fn test_no_entries() {
+        assert_eq!(
+            Pls::<256>::entries(b"[playlist]\nNumberOfEntries=0\n").count(),
+            0
+        );
+        assert_eq!(Pls::<256>::entries(b"").count(), 0);
+    }
+}
@@ -5,9 +5,12 @@
 //!
 //! ## Features
 //!
-//! - Stores station names and URLs in a compact string pool to minimize memory usage.
+//! - Stores station names, URLs and tags (e.g. genres) in a compact string pool to minimize memory usage.
 //! - Supports a configurable maximum number of stations, name length, URL length, and preset slots via const generics.
 //! - Allows adding, retrieving, and assigning stations to preset slots.
+//! - Allows removing, updating and moving stations and clearing presets. The space in the pool is reused.
+//! - Allows browsing the stations by tag.
+//! - Loads the stations from a CSV file, also in parts as it is read, and writes them back out.
 //! - Designed for embedded and resource-constrained environments (uses `heapless`).
 //!
 //! ## Const Generics
@@ -62,24 +65,102 @@
 //! let stations = Stations::<32, 256, 4>::load(csv).unwrap();
 //! ```
 //!
+//! The fields after the URL are the tags of the station, e.g. its genres:
+//!
+//! ```rust
+//! # use stations::Stations;
+//! let csv = b"RPR1,http://streams.rpr1.de/rpr-kaiserslautern-128-mp3,Favorites,Pop\n\
+//!             Classical,http://listen.181fm.com/181-classical_128k.mp3,Classical";
+//! let stations = Stations::<32, 256, 4>::load(csv).unwrap();
+//!
+//! assert!(stations.tags().eq(["Favorites", "Pop", "Classical"]));
+//! assert!(stations.stations_with_tag("pop").eq([0]));
+//! assert_eq!(stations.number_stations_with_tag("Classical"), 1);
+//! ```
+//!
+//! A file that is read in parts, e.g. from a connection, can be loaded one part at a time
+//! with a `StationsLoader`, without keeping the whole file in memory:
+//!
+//! ```rust
+//! # use stations::StationsLoader;
+//! let mut loader = StationsLoader::<32, 256, 4>::new();
+//! loader.feed(b"Radio1,http://radio1.exa").unwrap();
+//! loader.feed(b"mple/stream\nRadio2,http://radio2.example/stream,PRESET:0").unwrap();
+//! let (stations, _warnings) = loader.finish().unwrap();
+//!
+//! assert_eq!(stations.number_stations(), 2);
+//! assert_eq!(stations.get_station(0).unwrap().url(), "http://radio1.example/stream");
+//! ```
+//!
+//! ## Saving as CSV
+//!
+//! The stations can be written back out in the same format, e.g. to keep changes made on the
+//! device. Anything that implements `embedded_io::Write` can be written to, including a byte
+//! buffer:
+//!
+//! ```rust
+//! # use stations::Stations;
+//! let csv = b"\"Radio, 1\",http://radio1.example/stream,Pop,PRESET:0\n";
+//! let stations = Stations::<32, 256, 4>::load(csv).unwrap();
+//!
+//! let mut buffer = [0u8; 128];
+//! let mut remaining = &mut buffer[..];
+//! stations.write_csv(&mut remaining).unwrap();
+//! let len = 128 - remaining.len();
+//!
+//! assert_eq!(&buffer[..len], csv);
+//! ```
+//!
 //! ## Error Handling
 //!
 //! Most operations return a `Result` with a `StationError` describing the failure reason (e.g., field too long, invalid UTF-8, out-of-bounds).
 //!
+//! Loading a CSV file returns a `LoadError` with the `StationError` of the first invalid record and
+//! where it is. `Stations::load_lenient` skips the invalid records instead, with a warning for each:
+//!
+//! ```rust
+//! # use stations::{StationError, Stations};
+//! let csv = b"Radio 1,http://radio1.example/stream\n\
+//!             A name that is far too long,http://radio2.example/stream\n\
+//!             Radio 3,http://radio3.example/stream";
+//!
+//! let error = Stations::<16, 256, 4>::load(csv).err().unwrap();
+//! assert_eq!((error.error, error.line, error.field), (StationError::NameTooLong, 2, 0));
+//!
+//! let (stations, warnings) = Stations::<16, 256, 4>::load_lenient(csv);
+//! assert_eq!(stations.number_stations(), 2);
+//! assert_eq!(warnings.number_skipped(), 1);
+//! ```
+//!
 //! ## Crate Features
 //!
 //! - No-std compatible (when `std` is disabled).
 //! - Suitable for embedded and microcontroller projects.
 
+use core::fmt::Write as _;
 use core::str::Utf8Error;
 
 use heapless::{String, Vec};
 
-use csv_core::{ReadFieldResult, Reader};
+use csv_core::{ReadFieldResult, Reader, WriteResult, Writer};
+use embedded_io::Write;
 
 const POOL_SIZE: usize = 4096;
 const MAX_NUM_STATIONS: usize = 64;
 
+// The longest field of a CSV file that can be read
+const CSV_FIELD_LEN: usize = 1024;
+
+// Separates the tags of a station in the pool. It is not expected in a tag.
+const TAG_SEPARATOR: char = '\x1f';
+
+/// The maximum number of warnings kept by [`Stations::load_lenient`]. Records are still
+/// skipped after that, but only counted.
+pub const MAX_LOAD_WARNINGS: usize = 8;
+
+// The start of the field that assigns a station to a preset slot
+const PRESET_PREFIX: &str = "PRESET:";
+
 /// A station.
 /// This struct is in a form that can be easily used in an application.
 ///
@@ -122,16 +203,20 @@ struct StationPositions {
     name: (usize, usize),
     // Start and end index of the station url string
     url: (usize, usize),
+    // Start and end index of the tags of the station, separated by TAG_SEPARATOR
+    tags: (usize, usize),
 }
 
-/// A list of stations with name and url.
+/// A list of stations with name, url and tags.
 ///
 ///  Assuming that all station data is UTF8.
 pub struct Stations<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize> {
-    // To save storage, the station names and urls are stored in a long string pool.
+    // To save storage, the station names, urls and tags are stored in a long string pool.
     pool: String<POOL_SIZE>,
 
-    // The positions of each station name and url in the string pool.
+    // The positions of each station name, url and tags in the string pool.
+    // Each station takes up one part of the pool with its name, url and tags in that order.
+    // The parts are not always in the order of the stations, e.g. after a station is moved.
     positions: Vec<StationPositions, MAX_NUM_STATIONS>,
 
     // The preset stations
@@ -162,9 +247,9 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
     /// The CSV file must have at least two fields per record:
     /// `{station name},{station_url},...`
     ///
-    /// Only the first two fields of each record are used; additional fields are ignored
-    /// unless the last field has the form PREFIX:n (n is the preset slot number).
-    /// In this case the station is assigned to a preset slot.
+    /// The fields after the first two are the tags of the station, e.g. its genres,
+    /// unless a field has the form PRESET:n (n is the preset slot number).
+    /// In this case the station is assigned to a preset slot. Empty fields are ignored.
     ///
     /// # Arguments
     ///
@@ -173,77 +258,79 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
     /// # Returns
     ///
     /// Returns `Ok(Stations)` containing all successfully parsed stations.
-    /// Returns `Err(StationError)` if any record is invalid or a field is too long.
+    /// Returns `Err(LoadError)` for the first record that is invalid, with the
+    /// [`StationError`] and where it is in the file.
     ///
     /// # Errors
     ///
     /// * [`StationError::CsvFieldTooLong`] - If a CSV field exceeds the allowed buffer size.
+    /// * [`StationError::CsvFieldNotUtf8`] - If a tag or preset field is not valid UTF-8.
     /// * [`StationError::NameTooLong`] - If a station name exceeds the maximum allowed length.
     /// * [`StationError::UrlTooLong`] - If a station URL exceeds the maximum allowed length.
     /// * [`StationError::NameNotUtf8`] - If a station name is not valid UTF-8.
     /// * [`StationError::UrlNotUtf8`] - If a station URL is not valid UTF-8.
+    /// * [`StationError::InvalidPreset`] - If a preset slot does not exist.
+    /// * [`StationError::InvalidTag`] - If a tag contains a control character that separates tags.
     /// * [`StationError::TooManyStations`] - If the station pool or list is full.
     ///
-    pub fn load(data: &[u8]) -> Result<Stations<NAME_LEN, URL_LEN, NUM_PRESETS>, StationError> {
-        let mut reader = Reader::new();
-
-        let mut stations = Stations::new();
-
-        let mut out = [0u8; 1024];
-
-        let mut in_bytes = data;
-        let mut name = [0u8; NAME_LEN];
-        let mut url = [0u8; URL_LEN];
-        let mut field_index: usize = 0;
-        let mut name_len = 0;
-        let mut station_id = 0;
-        loop {
-            // let (result, nin, nout) = reader.read_field(&in_bytes, &mut out);
-            let (result, nin, nout) = reader.read_field(in_bytes, &mut out);
-
-            match result {
-                ReadFieldResult::InputEmpty => {}
-                ReadFieldResult::OutputFull => Err(StationError::CsvFieldTooLong)?,
-                ReadFieldResult::Field { record_end } => match field_index {
-                    0 => {
-                        if nout > NAME_LEN {
-                            Err(StationError::NameTooLong)?;
-                        };
-                        name[0..nout].copy_from_slice(&out[0..nout]);
-                        name_len = nout;
-                        field_index += 1;
-                    }
-                    1 => {
-                        if nout > URL_LEN {
-                            Err(StationError::UrlTooLong)?;
-                        };
-                        url[0..nout].copy_from_slice(&out[0..nout]);
-                        station_id = stations.add_station(&name[0..name_len], &url[0..nout])?;
-                        field_index += 1;
-                    }
-                    _ => {
-                        // Check if this is a preset field
-                        let value = str::from_utf8(&out[0..nout])?.trim();
-                        if value.starts_with("PRESET:") {
-                            let preset_slot = Self::extract_prefix_slot(value)?;
-                            stations.set_preset(station_id, preset_slot)?;
-                        }
-                        if !record_end {
-                            field_index += 1;
-                        } else {
-                            field_index = 0;
-                        }
-                        in_bytes = &in_bytes[nin..];
-                        continue;
-                    }
-                },
-                ReadFieldResult::End => break,
+    pub fn load(data: &[u8]) -> Result<Stations<NAME_LEN, URL_LEN, NUM_PRESETS>, LoadError> {
+        let mut loader = StationsLoader::new();
+        loader.feed(data)?;
+        let (stations, _) = loader.finish()?;
+        Ok(stations)
+    }
+
+    /// Loads a set of stations from a CSV file like [`Stations::load`], but skips the
+    /// records that are invalid instead of failing.
+    ///
+    /// This keeps a list with one bad record, e.g. a name that is too long, usable.
+    ///
+    /// # Returns
+    ///
+    /// Returns the stations of all the valid records, and the warnings for the records
+    /// that were skipped.
+    pub fn load_lenient(data: &[u8]) -> (Stations<NAME_LEN, URL_LEN, NUM_PRESETS>, LoadWarnings) {
+        let mut loader = StationsLoader::lenient();
+        // A lenient loader does not return errors
+        loader
+            .feed(data)
+            .and_then(|()| loader.finish())
+            .unwrap_or_default()
+    }
+
+    // Loads a field of a record of a CSV file. The station is added with the URL, i.e. the
+    // second field, and the later fields are added to it. The presets are only noted, to be
+    // set once the whole record has been loaded.
+    fn load_field(
+        &mut self,
+        field: &[u8],
+        field_index: usize,
+        name: &mut Vec<u8, NAME_LEN>,
+        station_id: &mut usize,
+        presets: &mut [bool; NUM_PRESETS],
+    ) -> Result<(), StationError> {
+        match field_index {
+            0 => {
+                str::from_utf8(field).map_err(|_| StationError::NameNotUtf8)?;
+                name.clear();
+                name.extend_from_slice(field)
+                    .map_err(|_| StationError::NameTooLong)?;
+            }
+            1 => {
+                *station_id = self.add_station(name, field)?;
+            }
+            _ => {
+                // Check if this is a preset field, otherwise it is a tag
+                let value = str::from_utf8(field)?.trim();
+                if value.starts_with(PRESET_PREFIX) {
+                    let preset_slot = Self::extract_prefix_slot(value)?;
+                    presets[preset_slot] = true;
+                } else if !value.is_empty() {
+                    self.push_tag(*station_id, value)?;
+                }
             }
-            in_bytes = &in_bytes[nin..];
         }
-
-        //stations.set_current_station(0)?;
-        Ok(stations)
+        Ok(())
     }
 
     /// Adds a station to the list.
@@ -276,6 +363,243 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
         station_name: &[u8],
         station_url: &[u8],
     ) -> Result<usize, StationError> {
+        self.add_station_with_tags(station_name, station_url, &[])
+    }
+
+    /// Adds a station with tags, e.g. its genres, to the list.
+    ///
+    /// Empty tags are left out. Otherwise this is the same as [`Stations::add_station`],
+    /// and the station is not added at all if there is no space for it and its tags.
+    ///
+    /// # Errors
+    ///
+    /// * [`StationError::InvalidTag`] - If a tag would be read back as a preset.
+    ///
+    pub fn add_station_with_tags(
+        &mut self,
+        station_name: &[u8],
+        station_url: &[u8],
+        tags: &[&str],
+    ) -> Result<usize, StationError> {
+        let (name, url) = Self::validate(station_name, station_url)?;
+
+        let tags_len = tags_len(tags)?;
+        if self.positions.is_full() || name.len() + url.len() + tags_len > self.free_space() {
+            Err(StationError::TooManyStations)?;
+        }
+
+        // There is space for all of the station
+        let station_id = self.positions.len();
+        let positions = self.push_name_and_url(name, url)?;
+        self.positions
+            .push(positions)
+            .map_err(|_| StationError::TooManyStations)?;
+        self.push_tags(station_id, tags)?;
+
+        Ok(station_id)
+    }
+
+    /// Removes a station from the list. The stations after it move up one place,
+    /// so their ids are one less, and the space it took up can be used again.
+    ///
+    /// A preset with the station is cleared.
+    ///
+    /// # Errors
+    ///
+    /// * [`StationError::StationNonExistent`] - If the station index does not exist.
+    ///
+    pub fn remove_station(&mut self, station_id: usize) -> Result<(), StationError> {
+        if station_id >= self.number_stations() {
+            Err(StationError::StationNonExistent)?;
+        }
+
+        self.move_to_pool_end(station_id);
+        let positions = self.positions.remove(station_id);
+        self.pool.truncate(positions.name.0);
+
+        for slot in self.preset_slots.iter_mut() {
+            *slot = match *slot {
+                Some(id) if id == station_id => None,
+                Some(id) if id > station_id => Some(id - 1),
+                slot => slot,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Changes the name and URL of a station. Its id, tags and presets stay the same.
+    ///
+    /// The station is left as it is if the name or URL is invalid or there is no space
+    /// for them.
+    ///
+    /// # Errors
+    ///
+    /// * [`StationError::StationNonExistent`] - If the station index does not exist.
+    /// * Otherwise the same as [`Stations::add_station`].
+    ///
+    pub fn update_station(
+        &mut self,
+        station_id: usize,
+        station_name: &[u8],
+        station_url: &[u8],
+    ) -> Result<(), StationError> {
+        let (name, url) = Self::validate(station_name, station_url)?;
+        let Some(positions) = self.positions.get(station_id) else {
+            return Err(StationError::StationNonExistent);
+        };
+
+        let old_len = positions.tags.0 - positions.name.0;
+        if name.len() + url.len() > self.free_space() + old_len {
+            Err(StationError::TooManyStations)?;
+        }
+
+        // The station is moved to the end of the pool, where the name and URL are replaced
+        // by putting the tags in front of them, then the new ones after the tags, and then
+        // the tags back at the front
+        self.move_to_pool_end(station_id);
+        let StationPositions {
+            name: (start, _),
+            tags: (tags_start, end),
+            ..
+        } = self.positions[station_id];
+        let tags_len = end - tags_start;
+        self.rotate_pool_left(start, old_len);
+        self.pool.truncate(start + tags_len);
+
+        self.push_name_and_url(name, url)?;
+        self.rotate_pool_left(start, tags_len);
+
+        let name_end = start + name.len();
+        let url_end = name_end + url.len();
+        self.positions[station_id] = StationPositions {
+            name: (start, name_end),
+            url: (name_end, url_end),
+            tags: (url_end, url_end + tags_len),
+        };
+
+        Ok(())
+    }
+
+    /// Replaces the tags of a station. Empty tags are left out.
+    ///
+    /// The tags are left as they are if there is no space for the new ones.
+    ///
+    /// # Errors
+    ///
+    /// * [`StationError::StationNonExistent`] - If the station index does not exist.
+    /// * [`StationError::TooManyStations`] - If the station pool is full.
+    /// * [`StationError::InvalidTag`] - If a tag would be read back as a preset.
+    ///
+    pub fn set_station_tags(
+        &mut self,
+        station_id: usize,
+        tags: &[&str],
+    ) -> Result<(), StationError> {
+        let Some(positions) = self.positions.get(station_id) else {
+            return Err(StationError::StationNonExistent);
+        };
+
+        let old_len = positions.tags.1 - positions.tags.0;
+        if tags_len(tags)? > self.free_space() + old_len {
+            Err(StationError::TooManyStations)?;
+        }
+
+        self.move_to_pool_end(station_id);
+        let positions = &mut self.positions[station_id];
+        self.pool.truncate(positions.tags.0);
+        positions.tags.1 = positions.tags.0;
+        self.push_tags(station_id, tags)
+    }
+
+    /// Moves a station to another place in the list, i.e. gives it another id. The stations
+    /// in between move up or down one place to make room. Presets stay with their stations.
+    ///
+    /// # Errors
+    ///
+    /// * [`StationError::StationNonExistent`] - If either index does not exist.
+    ///
+    pub fn move_station(&mut self, from_id: usize, to_id: usize) -> Result<(), StationError> {
+        if from_id >= self.number_stations() || to_id >= self.number_stations() {
+            Err(StationError::StationNonExistent)?;
+        }
+
+        // Only the positions move, the pool stays as it is
+        if from_id < to_id {
+            self.positions[from_id..=to_id].rotate_left(1);
+        } else {
+            self.positions[to_id..=from_id].rotate_right(1);
+        }
+
+        for id in self.preset_slots.iter_mut().flatten() {
+            *id = match *id {
+                id if id == from_id => to_id,
+                id if from_id < id && id <= to_id => id - 1,
+                id if to_id <= id && id < from_id => id + 1,
+                id => id,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Writes the stations as CSV, in the same format that [`Stations::load`] reads, so that
+    /// loading the CSV gives the same stations, tags and presets.
+    ///
+    /// Each station is a record with the name, the URL, the tags and a `PRESET:n` field for
+    /// each preset slot it is assigned to. Fields are only quoted if needed, e.g. a name with
+    /// a comma.
+    ///
+    /// # Errors
+    ///
+    /// Any error writing to `out`, e.g. `embedded_io::SliceWriteError::Full` if a byte buffer
+    /// is too small.
+    ///
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> Result<(), W::Error> {
+        let mut writer = Writer::new();
+        // Only needs to be large enough for the quotes, delimiter and terminator
+        let mut buffer = [0u8; 64];
+
+        for (id, positions) in self.positions.iter().enumerate() {
+            let name = &self.pool[positions.name.0..positions.name.1];
+            let url = &self.pool[positions.url.0..positions.url.1];
+            write_field(&mut writer, out, &mut buffer, true, name.as_bytes())?;
+            write_field(&mut writer, out, &mut buffer, false, url.as_bytes())?;
+
+            for tag in self.tags_at(positions) {
+                write_field(&mut writer, out, &mut buffer, false, tag.as_bytes())?;
+            }
+
+            for (preset_id, _) in self
+                .preset_slots
+                .iter()
+                .enumerate()
+                .filter(|(_, slot)| **slot == Some(id))
+            {
+                let mut preset = String::<16>::new();
+                // The preset number always fits
+                let _ = write!(preset, "{PRESET_PREFIX}{preset_id}");
+                write_field(&mut writer, out, &mut buffer, false, preset.as_bytes())?;
+            }
+
+            let (_, nout) = writer.terminator(&mut buffer);
+            out.write_all(&buffer[..nout])?;
+        }
+
+        let (_, nout) = writer.finish(&mut buffer);
+        out.write_all(&buffer[..nout])
+    }
+
+    /// The number of bytes left in the pool for the names, URLs and tags of more stations.
+    pub fn free_space(&self) -> usize {
+        self.pool.capacity() - self.pool.len()
+    }
+
+    // Checks that the name and URL can be used for a station
+    fn validate<'a>(
+        station_name: &'a [u8],
+        station_url: &'a [u8],
+    ) -> Result<(&'a str, &'a str), StationError> {
         let name = str::from_utf8(station_name).map_err(|_| StationError::NameNotUtf8)?;
         let url = str::from_utf8(station_url).map_err(|_| StationError::UrlNotUtf8)?;
 
@@ -286,6 +610,15 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
             Err(StationError::UrlTooLong)?;
         }
 
+        Ok((name, url))
+    }
+
+    // Adds the name and URL to the end of the pool, followed by no tags
+    fn push_name_and_url(
+        &mut self,
+        name: &str,
+        url: &str,
+    ) -> Result<StationPositions, StationError> {
         let name_positions = (self.pool.len(), self.pool.len() + name.len());
         self.pool
             .push_str(name)
@@ -296,17 +629,74 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
             .push_str(url)
             .map_err(|_| StationError::TooManyStations)?;
 
-        let station_positions = StationPositions {
+        Ok(StationPositions {
             name: name_positions,
             url: url_positions,
-        };
+            tags: (self.pool.len(), self.pool.len()),
+        })
+    }
 
-        self.positions
-            .push(station_positions)
+    fn push_tags(&mut self, station_id: usize, tags: &[&str]) -> Result<(), StationError> {
+        tags.iter()
+            .try_for_each(|tag| self.push_tag(station_id, tag))
+    }
+
+    // Adds a tag to a station at the end of the pool, e.g. the one that was added last.
+    // Empty tags are left out.
+    fn push_tag(&mut self, station_id: usize, tag: &str) -> Result<(), StationError> {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Ok(());
+        }
+        if !is_valid_tag(tag) {
+            Err(StationError::InvalidTag)?;
+        }
+
+        let positions = self
+            .positions
+            .get_mut(station_id)
+            .ok_or(StationError::StationNonExistent)?;
+
+        if positions.tags.1 > positions.tags.0 {
+            self.pool
+                .push(TAG_SEPARATOR)
+                .map_err(|_| StationError::TooManyStations)?;
+        }
+        self.pool
+            .push_str(tag)
             .map_err(|_| StationError::TooManyStations)?;
 
-        let added_station_id = self.positions.len() - 1;
-        Ok(added_station_id)
+        positions.tags.1 = self.pool.len();
+        Ok(())
+    }
+
+    // Moves the name, URL and tags of a station to the end of the pool. The stations after
+    // it in the pool move up to take its place.
+    fn move_to_pool_end(&mut self, station_id: usize) {
+        let StationPositions {
+            name: (start, _),
+            tags: (_, end),
+            ..
+        } = self.positions[station_id];
+        let len = end - start;
+        self.rotate_pool_left(start, len);
+
+        let offset = (self.pool.len() - end) as isize;
+        for (id, positions) in self.positions.iter_mut().enumerate() {
+            if id == station_id {
+                positions.move_by(offset);
+            } else if positions.name.0 > start {
+                positions.move_by(-(len as isize));
+            }
+        }
+    }
+
+    // Rotates the pool after `start` to the left, so that the `len` bytes at the start
+    // go to the end
+    fn rotate_pool_left(&mut self, start: usize, len: usize) {
+        // SAFETY: Only whole names, URLs and tags are moved, so the pool stays valid UTF-8
+        let pool = unsafe { self.pool.as_mut_vec() };
+        pool[start..].rotate_left(len);
     }
 
     /// Sets a station as a preset at the specified preset index.
@@ -364,6 +754,21 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
         }
     }
 
+    /// Clears the preset at the specified preset index, so it has no station.
+    ///
+    /// # Errors
+    ///
+    /// * [`StationError::TooManyPresets`] - If the preset index is out of range.
+    ///
+    pub fn clear_preset(&mut self, preset_id: usize) -> Result<(), StationError> {
+        let slot = self
+            .preset_slots
+            .get_mut(preset_id)
+            .ok_or(StationError::TooManyPresets)?;
+        *slot = None;
+        Ok(())
+    }
+
     // /// Retrieves the id of the  station assigned to the specified preset index
     // /// or `None` if the preset is empty or the preset index is out of bounds.
     // pub fn preset_station_id(&self, preset_id: usize) -> Option<usize> {
@@ -414,6 +819,62 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
         }
     }
 
+    /// Returns the tags of the station, in the order they were given, or `None` if the
+    /// station does not exist.
+    pub fn station_tags(&self, id: usize) -> Option<impl Iterator<Item = &str> + '_> {
+        let positions = self.positions.get(id)?;
+        Some(self.tags_at(positions))
+    }
+
+    /// Returns all the tags of the stations, each only once, in the order they first appear.
+    ///
+    /// Tags are compared ignoring ASCII case, so `Pop` and `pop` are the same tag.
+    pub fn tags(&self) -> impl Iterator<Item = &str> + '_ {
+        self.all_tags()
+            .enumerate()
+            .filter(|(index, tag)| {
+                !self
+                    .all_tags()
+                    .take(*index)
+                    .any(|earlier| earlier.eq_ignore_ascii_case(tag))
+            })
+            .map(|(_, tag)| tag)
+    }
+
+    /// Returns the ids of the stations that have the tag, in order. The tag is compared
+    /// ignoring ASCII case.
+    pub fn stations_with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.positions
+            .iter()
+            .enumerate()
+            .filter(move |(_, positions)| {
+                self.tags_at(positions)
+                    .any(|station_tag| station_tag.eq_ignore_ascii_case(tag))
+            })
+            .map(|(id, _)| id)
+    }
+
+    /// Returns the number of stations that have the tag. The tag is compared ignoring
+    /// ASCII case.
+    pub fn number_stations_with_tag(&self, tag: &str) -> usize {
+        self.stations_with_tag(tag).count()
+    }
+
+    // The tags of all the stations, in order
+    fn all_tags(&self) -> impl Iterator<Item = &str> + '_ {
+        self.positions
+            .iter()
+            .flat_map(|positions| self.tags_at(positions))
+    }
+
+    fn tags_at(&self, positions: &StationPositions) -> impl Iterator<Item = &str> + '_ {
+        let (start, end) = positions.tags;
+        // Without any tags there is still one empty string to split
+        self.pool[start..end]
+            .split(TAG_SEPARATOR)
+            .filter(|tag| !tag.is_empty())
+    }
+
     // / Sets the current station by index.
     // /
     // / # Arguments
@@ -444,8 +905,8 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
     //     }
     // }
 
-    /// Set the current station to the next one in the stations list. If the range is exceeded
-    /// the current stations is clamped to the last one in the list.
+    // / Set the current station to the next one in the stations list. If the range is exceeded
+    // / the current stations is clamped to the last one in the list.
     // pub fn increment_current_station(&mut self) {
     //     if let Some(station_id) = self.current_station {
     //         let inc_station_id = (station_id + 1).clamp(0, self.positions.len() - 1);
@@ -453,8 +914,8 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
     //         self.current_station = Some(inc_station_id);
     //     }
     // }
-    /// Set the current station to the previous one in the stations list. If the range is exceeded
-    /// the current stations is clamped to the first one in the list.
+    // / Set the current station to the previous one in the stations list. If the range is exceeded
+    // / the current stations is clamped to the first one in the list.
     // pub fn decrement_current_station(&mut self) {
     //     if let Some(station_id) = self.current_station {
     //         let dec_station_id = station_id.saturating_sub(1);
@@ -464,9 +925,9 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
     //     }
     // }
 
-    /// Reset the current station to 0.
-    ///
-    /// Equivalent to `set_current_station(0)`
+    // / Reset the current station to 0.
+    // /
+    // / Equivalent to `set_current_station(0)`
     // pub fn reset_current_station(&mut self) {
     //     self.current_station = Some(0);
     // }
@@ -489,6 +950,64 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
     }
 }
 
+impl StationPositions {
+    // Moves the positions towards the end of the pool, or the start if the offset is negative
+    fn move_by(&mut self, offset: isize) {
+        for (start, end) in [&mut self.name, &mut self.url, &mut self.tags] {
+            *start = start.wrapping_add_signed(offset);
+            *end = end.wrapping_add_signed(offset);
+        }
+    }
+}
+
+// The space taken up in the pool by the tags, without the empty ones
+fn tags_len(tags: &[&str]) -> Result<usize, StationError> {
+    let mut count: usize = 0;
+    let mut len = 0;
+    for tag in tags
+        .iter()
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+    {
+        if !is_valid_tag(tag) {
+            Err(StationError::InvalidTag)?;
+        }
+        count += 1;
+        len += tag.len();
+    }
+    // The tags are separated by a character that takes up one byte
+    Ok(len + count.saturating_sub(1))
+}
+
+// Whether the tag is read back as the same tag from a CSV file, so that it is not a preset
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.starts_with(PRESET_PREFIX) && !tag.contains(TAG_SEPARATOR)
+}
+
+// Writes a field of a CSV record, in quotes if needed, e.g. for a name with a comma
+fn write_field<W: Write>(
+    writer: &mut Writer,
+    out: &mut W,
+    buffer: &mut [u8],
+    is_first: bool,
+    mut field: &[u8],
+) -> Result<(), W::Error> {
+    if !is_first {
+        // The buffer is large enough for the closing quote of the last field and the delimiter
+        let (_, nout) = writer.delimiter(buffer);
+        out.write_all(&buffer[..nout])?;
+    }
+
+    loop {
+        let (result, nin, nout) = writer.field(field, buffer);
+        out.write_all(&buffer[..nout])?;
+        field = &field[nin..];
+        if result == WriteResult::InputEmpty {
+            return Ok(());
+        }
+    }
+}
+
 impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize> Default
     for Stations<NAME_LEN, URL_LEN, NUM_PRESETS>
 {
@@ -517,6 +1036,188 @@ impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize> core
     }
 }
 
+/// Loads a set of stations from a CSV file that is given in parts, e.g. as it is read from a
+/// connection, so that the whole file does not have to be kept in memory.
+///
+/// The parts can be of any size and split the file anywhere, also within a field. The
+/// format is the one read by [`Stations::load`].
+pub struct StationsLoader<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize> {
+    stations: Stations<NAME_LEN, URL_LEN, NUM_PRESETS>,
+    reader: Reader,
+
+    // The field being read, which can be in more than one part
+    field: [u8; CSV_FIELD_LEN],
+    field_len: usize,
+    field_index: usize,
+
+    // The name of the station of the record, which is added once its URL has been read
+    name: Vec<u8, NAME_LEN>,
+    station_id: usize,
+
+    // The presets of the record, which are set at its end so that a record that turns out
+    // to be invalid does not take the preset of another station
+    presets: [bool; NUM_PRESETS],
+
+    // Where the reader is in the file
+    record: usize,
+    line: usize,
+
+    // After an error the rest of the record is skipped
+    skip_record: bool,
+
+    // The records that were skipped, only when lenient
+    warnings: Option<LoadWarnings>,
+}
+
+impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize>
+    StationsLoader<NAME_LEN, URL_LEN, NUM_PRESETS>
+{
+    /// Creates a loader that fails at the first invalid record, like [`Stations::load`].
+    pub fn new() -> StationsLoader<NAME_LEN, URL_LEN, NUM_PRESETS> {
+        StationsLoader {
+            stations: Stations::new(),
+            reader: Reader::new(),
+            field: [0; CSV_FIELD_LEN],
+            field_len: 0,
+            field_index: 0,
+            name: Vec::new(),
+            station_id: 0,
+            presets: [false; NUM_PRESETS],
+            record: 1,
+            line: 1,
+            skip_record: false,
+            warnings: None,
+        }
+    }
+
+    /// Creates a loader that skips the invalid records, like [`Stations::load_lenient`].
+    pub fn lenient() -> StationsLoader<NAME_LEN, URL_LEN, NUM_PRESETS> {
+        StationsLoader {
+            warnings: Some(LoadWarnings::default()),
+            ..Self::new()
+        }
+    }
+
+    /// Loads the next part of the CSV file.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`LoadError`] for an invalid record, unless the loader is lenient. The
+    /// errors are the ones of [`Stations::load`].
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), LoadError> {
+        self.read(data, false)
+    }
+
+    /// Ends the file and returns the stations, and the warnings for the records that were
+    /// skipped if the loader is lenient.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`LoadError`] if the last record is invalid, unless the loader is lenient.
+    pub fn finish(
+        mut self,
+    ) -> Result<(Stations<NAME_LEN, URL_LEN, NUM_PRESETS>, LoadWarnings), LoadError> {
+        // The last field is only complete once the reader is given the empty input at the
+        // end of the file
+        self.read(&[], true)?;
+        Ok((self.stations, self.warnings.unwrap_or_default()))
+    }
+
+    // Reads the data, which is all read unless there is an error. At the end of the file,
+    // the data is empty.
+    fn read(&mut self, mut data: &[u8], at_end: bool) -> Result<(), LoadError> {
+        while at_end || !data.is_empty() {
+            let (result, nin, nout) = self
+                .reader
+                .read_field(data, &mut self.field[self.field_len..]);
+            self.field_len += nout;
+
+            // The new line at the end of a record is read with its last field, but the
+            // field is on the line before
+            let mut consumed = &data[..nin];
+            let mut record_new_line = 0;
+            if let (ReadFieldResult::Field { record_end: true }, Some((b'\n', rest))) =
+                (&result, consumed.split_last())
+            {
+                consumed = rest;
+                record_new_line = 1;
+            }
+            self.line += consumed.iter().filter(|&&b| b == b'\n').count();
+            data = &data[nin..];
+
+            let field_result = match result {
+                // More data is needed
+                ReadFieldResult::InputEmpty => continue,
+                // The rest of the field is dropped with the rest of the record
+                ReadFieldResult::OutputFull => Err(StationError::CsvFieldTooLong),
+                ReadFieldResult::Field { .. } if self.skip_record => Ok(()),
+                ReadFieldResult::Field { .. } => self.stations.load_field(
+                    &self.field[..self.field_len],
+                    self.field_index,
+                    &mut self.name,
+                    &mut self.station_id,
+                    &mut self.presets,
+                ),
+                ReadFieldResult::End => break,
+            };
+            self.field_len = 0;
+
+            if let Err(error) = field_result {
+                if !self.skip_record {
+                    self.skip_record = true;
+                    // The station is added once its URL has been read
+                    if self.field_index > 1 {
+                        self.stations.remove_station(self.station_id).ok();
+                    }
+                    let error = LoadError {
+                        error,
+                        record: self.record,
+                        line: self.line,
+                        field: self.field_index,
+                    };
+                    match self.warnings.as_mut() {
+                        Some(warnings) => warnings.push(error),
+                        None => return Err(error),
+                    }
+                }
+            }
+
+            if let ReadFieldResult::Field { record_end } = result {
+                // A record with only a name has no station
+                if record_end {
+                    if !self.skip_record {
+                        self.set_presets();
+                    }
+                    self.presets = [false; NUM_PRESETS];
+                    self.field_index = 0;
+                    self.record += 1;
+                    self.skip_record = false;
+                } else {
+                    self.field_index += 1;
+                }
+            }
+            self.line += record_new_line;
+        }
+        Ok(())
+    }
+
+    // Sets the presets of the record that has just been loaded
+    fn set_presets(&mut self) {
+        for (preset_id, _) in self.presets.iter().enumerate().filter(|(_, set)| **set) {
+            // The preset exists, as does the station as it can only have presets with a URL
+            self.stations.set_preset(self.station_id, preset_id).ok();
+        }
+    }
+}
+
+impl<const NAME_LEN: usize, const URL_LEN: usize, const NUM_PRESETS: usize> Default
+    for StationsLoader<NAME_LEN, URL_LEN, NUM_PRESETS>
+{
+    fn default() -> StationsLoader<NAME_LEN, URL_LEN, NUM_PRESETS> {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StationError {
     /// The station URL added is not in UTF8
@@ -549,6 +1250,10 @@ pub enum StationError {
     /// Attempt to access a preset that cannot exist or the preset is
     /// incorrectly specified.
     InvalidPreset,
+
+    /// A tag that cannot be written to a CSV file and read back, as it would be
+    /// taken as a preset
+    InvalidTag,
 }
 
 impl From<Utf8Error> for StationError {
@@ -556,3 +1261,60 @@ impl From<Utf8Error> for StationError {
         StationError::CsvFieldNotUtf8
     }
 }
+
+/// An invalid record in a CSV file of stations, with where it is in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadError {
+    /// What is wrong with the record
+    pub error: StationError,
+
+    /// The number of the record, starting at 1. Empty lines are not records.
+    pub record: usize,
+
+    /// The line of the field, starting at 1. A quoted field can go over more than one line,
+    /// then it is the line the field ends on.
+    pub line: usize,
+
+    /// The index of the field in the record, i.e. 0 for the name and 1 for the URL
+    pub field: usize,
+}
+
+impl From<LoadError> for StationError {
+    fn from(err: LoadError) -> Self {
+        err.error
+    }
+}
+
+/// The records that were skipped by [`Stations::load_lenient`], as they are invalid.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LoadWarnings {
+    // The first records that were skipped
+    warnings: Vec<LoadError, MAX_LOAD_WARNINGS>,
+
+    // The number of records that were skipped, including the ones without a warning
+    number_skipped: usize,
+}
+
+impl LoadWarnings {
+    /// The errors of the first [`MAX_LOAD_WARNINGS`] records that were skipped.
+    pub fn iter(&self) -> impl Iterator<Item = &LoadError> {
+        self.warnings.iter()
+    }
+
+    /// The number of records that were skipped.
+    pub fn number_skipped(&self) -> usize {
+        self.number_skipped
+    }
+
+    /// Whether all the records were loaded.
+    pub fn is_empty(&self) -> bool {
+        self.number_skipped == 0
+    }
+
+    // Adds the error of a record that was skipped
+    fn push(&mut self, warning: LoadError) {
+        // Only the first warnings are kept when there is no space
+        self.warnings.push(warning).ok();
+        self.number_skipped += 1;
+    }
+}
@@ -1,4 +1,4 @@
-use stations::Stations;
+use stations::{LoadError, StationError, Stations, StationsLoader, MAX_LOAD_WARNINGS};
 
 const MAX_STATION_NAME_LEN: usize = 32;
 const MAX_STATION_URL_LEN: usize = 256;
@@ -77,3 +77,774 @@ fn test_load_with_presets() {
 
     assert!(stations.preset(3).is_none());
 }
+
+#[test]
+fn test_load_tags() {
+    let data = include_bytes!("resources/stations.txt");
+
+    let stations =
+        Stations::<MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN, NUMBER_PRESETS>::load(data).unwrap();
+
+    // BBC Radio 4 FM,http://stream.live.vc.bbcmedia.co.uk/bbc_radio_fourfm,UK,Favorites,Culture
+    assert!(stations
+        .station_tags(6)
+        .unwrap()
+        .eq(["UK", "Favorites", "Culture"]));
+    assert!(stations.station_tags(stations.number_stations()).is_none());
+
+    assert!(stations.tags().eq([
+        "Favorites",
+        "Pop",
+        "Oldies",
+        "UK",
+        "Classical",
+        "Culture",
+        "Lounge",
+        "Info",
+        "Classic"
+    ]));
+
+    assert!(stations.stations_with_tag("Favorites").eq([0, 1, 2, 6, 28]));
+    assert!(stations.stations_with_tag("favorites").eq([0, 1, 2, 6, 28]));
+    assert!(stations.stations_with_tag("Jazz").eq([]));
+
+    assert_eq!(stations.number_stations_with_tag("Pop"), 13);
+    assert_eq!(stations.number_stations_with_tag("UK"), 6);
+    assert_eq!(stations.number_stations_with_tag("Jazz"), 0);
+}
+
+#[test]
+fn test_presets_are_not_tags() {
+    let data = include_bytes!("resources/stations_with_presets.txt");
+
+    let stations =
+        Stations::<MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN, NUMBER_PRESETS>::load(data).unwrap();
+
+    assert!(stations.station_tags(0).unwrap().eq(["Favorites", "Pop"]));
+    assert!(stations.tags().all(|tag| !tag.starts_with("PRESET")));
+}
+
+#[test]
+fn test_load_records_without_tags() {
+    let data = b"Radio1,http://radio1.example/stream\nRadio2,http://radio2.example/stream,,PRESET:0\nNo URL\nRadio3,http://radio3.example/stream, Pop \n";
+
+    let stations =
+        Stations::<MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN, NUMBER_PRESETS>::load(data).unwrap();
+
+    assert_eq!(stations.number_stations(), 3);
+    assert_eq!(stations.get_station(1).unwrap().name(), "Radio2");
+    assert_eq!(stations.preset(0).unwrap().0, 1);
+    assert_eq!(stations.get_station(2).unwrap().name(), "Radio3");
+
+    assert!(stations.station_tags(0).unwrap().eq([] as [&str; 0]));
+    assert!(stations.station_tags(1).unwrap().eq([] as [&str; 0]));
+    assert!(stations.station_tags(2).unwrap().eq(["Pop"]));
+}
+
+#[test]
+fn test_add_station_with_tags() {
+    let mut stations = Stations::<MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN, NUMBER_PRESETS>::new();
+
+    let id = stations
+        .add_station_with_tags(
+            b"Radio 1",
+            b"http://radio1.example/stream",
+            &["Pop", "", "Rock"],
+        )
+        .unwrap();
+    assert_eq!(id, 0);
+    assert!(stations.station_tags(id).unwrap().eq(["Pop", "Rock"]));
+
+    // A station that does not fit is not added at all
+    let long_tag = "t".repeat(5000);
+    assert_eq!(
+        stations.add_station_with_tags(b"Radio 2", b"http://radio2.example/stream", &[&long_tag]),
+        Err(StationError::TooManyStations)
+    );
+    assert_eq!(stations.number_stations(), 1);
+
+    let id = stations
+        .add_station_with_tags(b"Radio 3", b"http://radio3.example/stream", &["Jazz"])
+        .unwrap();
+    assert_eq!(id, 1);
+    let station = stations.get_station(id).unwrap();
+    assert_eq!(station.name(), "Radio 3");
+    assert_eq!(station.url(), "http://radio3.example/stream");
+    assert!(stations.station_tags(id).unwrap().eq(["Jazz"]));
+    assert!(stations.tags().eq(["Pop", "Rock", "Jazz"]));
+}
+
+type TestStations = Stations<MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN, NUMBER_PRESETS>;
+type TestStationsLoader = StationsLoader<MAX_STATION_NAME_LEN, MAX_STATION_URL_LEN, NUMBER_PRESETS>;
+
+// The name, URL and tags of a station
+type Contents = (String, String, Vec<String>);
+
+fn contents(stations: &TestStations) -> Vec<Contents> {
+    (0..stations.number_stations())
+        .map(|id| {
+            let station = stations.get_station(id).unwrap();
+            let tags = stations.station_tags(id).unwrap().map(String::from);
+            (
+                station.name().to_string(),
+                station.url().to_string(),
+                tags.collect(),
+            )
+        })
+        .collect()
+}
+
+fn presets(stations: &TestStations) -> Vec<Option<usize>> {
+    (0..NUMBER_PRESETS)
+        .map(|preset_id| stations.preset(preset_id).map(|(id, _)| id))
+        .collect()
+}
+
+// The space in the pool used by the stations
+fn used_space(contents: &[Contents]) -> usize {
+    contents
+        .iter()
+        .map(|(name, url, tags)| {
+            let tags_len: usize = tags.iter().map(|tag| tag.len()).sum();
+            name.len() + url.len() + tags_len + tags.len().saturating_sub(1)
+        })
+        .sum()
+}
+
+fn load_with_presets() -> TestStations {
+    TestStations::load(include_bytes!("resources/stations_with_presets.txt")).unwrap()
+}
+
+#[test]
+fn test_remove_station() {
+    let mut stations = load_with_presets();
+    let mut expected = contents(&stations);
+    let free_space = stations.free_space();
+    assert_eq!(presets(&stations), [Some(0), Some(1), Some(10), None]);
+
+    // The middle, the first and the last station
+    for id in [5, 0, expected.len() - 3] {
+        stations.remove_station(id).unwrap();
+        expected.remove(id);
+        assert_eq!(contents(&stations), expected);
+    }
+
+    // The preset of the first station is cleared, the others follow their stations
+    assert_eq!(presets(&stations), [None, Some(0), Some(8), None]);
+    assert_eq!(stations.preset(2).unwrap().1.name(), "Rockland");
+
+    assert!(stations.free_space() > free_space);
+    assert_eq!(
+        stations.free_space() + used_space(&expected),
+        TestStations::new().free_space()
+    );
+
+    assert_eq!(
+        stations.remove_station(expected.len()),
+        Err(StationError::StationNonExistent)
+    );
+    assert_eq!(contents(&stations), expected);
+}
+
+#[test]
+fn test_remove_reclaims_space() {
+    let mut stations = TestStations::new();
+    let url = format!("http://example.com/{}", "s".repeat(200));
+
+    // Fill the pool
+    let mut number_added = 0;
+    while stations
+        .add_station(format!("Radio {number_added}").as_bytes(), url.as_bytes())
+        .is_ok()
+    {
+        number_added += 1;
+    }
+    assert_eq!(stations.number_stations(), number_added);
+    assert!(stations.free_space() < url.len());
+
+    stations.remove_station(3).unwrap();
+    let id = stations
+        .add_station(b"Another radio", url.as_bytes())
+        .unwrap();
+    assert_eq!(id, number_added - 1);
+    assert_eq!(stations.get_station(3).unwrap().name(), "Radio 4");
+    assert_eq!(stations.get_station(id).unwrap().name(), "Another radio");
+}
+
+#[test]
+fn test_update_station() {
+    let mut stations = load_with_presets();
+    let mut expected = contents(&stations);
+
+    // Longer, then shorter
+    stations
+        .update_station(1, b"RPR1 80er", b"http://streams.rpr1.de/rpr-80er-256-mp3")
+        .unwrap();
+    expected[1].0 = "RPR1 80er".to_string();
+    expected[1].1 = "http://streams.rpr1.de/rpr-80er-256-mp3".to_string();
+    assert_eq!(contents(&stations), expected);
+
+    stations
+        .update_station(6, b"Radio 4", b"http://bbc.co.uk/4")
+        .unwrap();
+    expected[6].0 = "Radio 4".to_string();
+    expected[6].1 = "http://bbc.co.uk/4".to_string();
+    assert_eq!(contents(&stations), expected);
+
+    // The tags and presets stay
+    assert!(stations
+        .station_tags(6)
+        .unwrap()
+        .eq(["UK", "Favorites", "Culture"]));
+    assert_eq!(stations.preset(1).unwrap().1.name(), "RPR1 80er");
+
+    // The station is left as it is after an error
+    let long_name = "n".repeat(MAX_STATION_NAME_LEN + 1);
+    assert_eq!(
+        stations.update_station(2, long_name.as_bytes(), b"http://swr.de/swr3"),
+        Err(StationError::NameTooLong)
+    );
+    assert_eq!(
+        stations.update_station(2, b"SWR3", b"\xff"),
+        Err(StationError::UrlNotUtf8)
+    );
+    assert_eq!(
+        stations.update_station(expected.len(), b"SWR3", b"http://swr.de/swr3"),
+        Err(StationError::StationNonExistent)
+    );
+    assert_eq!(contents(&stations), expected);
+}
+
+#[test]
+fn test_update_station_without_space() {
+    let mut stations = TestStations::new();
+    let url = format!("http://example.com/{}", "s".repeat(100));
+    while stations.add_station(b"Radio", url.as_bytes()).is_ok() {}
+    let expected = contents(&stations);
+
+    // Just fits
+    let longer_url = format!("{url}{}", "t".repeat(stations.free_space()));
+    stations
+        .update_station(0, b"Radio", longer_url.as_bytes())
+        .unwrap();
+    assert_eq!(stations.free_space(), 0);
+    stations
+        .update_station(0, b"Radio", url.as_bytes())
+        .unwrap();
+    assert_eq!(contents(&stations), expected);
+
+    // Does not fit
+    let too_long_url = format!("{longer_url}t");
+    assert_eq!(
+        stations.update_station(0, b"Radio", too_long_url.as_bytes()),
+        Err(StationError::TooManyStations)
+    );
+    assert_eq!(
+        stations.set_station_tags(1, &[&"t".repeat(stations.free_space() + 1)]),
+        Err(StationError::TooManyStations)
+    );
+    assert_eq!(contents(&stations), expected);
+}
+
+#[test]
+fn test_set_station_tags() {
+    let mut stations = load_with_presets();
+    let mut expected = contents(&stations);
+
+    stations
+        .set_station_tags(3, &["Pop", " ", "Charts", "UK"])
+        .unwrap();
+    expected[3].2 = vec!["Pop".to_string(), "Charts".to_string(), "UK".to_string()];
+    assert_eq!(contents(&stations), expected);
+
+    stations.set_station_tags(0, &[]).unwrap();
+    expected[0].2.clear();
+    assert_eq!(contents(&stations), expected);
+    assert_eq!(stations.number_stations_with_tag("Charts"), 1);
+
+    assert_eq!(
+        stations.set_station_tags(expected.len(), &["Pop"]),
+        Err(StationError::StationNonExistent)
+    );
+}
+
+#[test]
+fn test_move_station() {
+    let mut stations = load_with_presets();
+    let mut expected = contents(&stations);
+
+    // Forward, past the preset station 1
+    stations.move_station(0, 4).unwrap();
+    let station = expected.remove(0);
+    expected.insert(4, station);
+    assert_eq!(contents(&stations), expected);
+    assert_eq!(presets(&stations), [Some(4), Some(0), Some(10), None]);
+
+    // Backward, past the preset station 4
+    stations.move_station(10, 2).unwrap();
+    let station = expected.remove(10);
+    expected.insert(2, station);
+    assert_eq!(contents(&stations), expected);
+    assert_eq!(presets(&stations), [Some(5), Some(0), Some(2), None]);
+    assert_eq!(stations.preset(2).unwrap().1.name(), "Rockland");
+
+    // Nowhere
+    stations.move_station(3, 3).unwrap();
+    assert_eq!(contents(&stations), expected);
+
+    assert_eq!(
+        stations.move_station(0, expected.len()),
+        Err(StationError::StationNonExistent)
+    );
+
+    // A moved station can still be updated and removed
+    stations
+        .update_station(
+            2,
+            b"Rockland Radio",
+            b"https://stream.rockland.de/rockland.mp3",
+        )
+        .unwrap();
+    expected[2].0 = "Rockland Radio".to_string();
+    stations.remove_station(5).unwrap();
+    expected.remove(5);
+    assert_eq!(contents(&stations), expected);
+    assert_eq!(presets(&stations), [None, Some(0), Some(2), None]);
+}
+
+#[test]
+fn test_clear_preset() {
+    let mut stations = load_with_presets();
+
+    stations.clear_preset(1).unwrap();
+    stations.clear_preset(3).unwrap();
+    assert_eq!(presets(&stations), [Some(0), None, Some(10), None]);
+
+    assert_eq!(
+        stations.clear_preset(NUMBER_PRESETS),
+        Err(StationError::TooManyPresets)
+    );
+}
+
+// Makes many edits, checking the stations against a simple model after each one
+#[test]
+fn test_random_edits() {
+    let mut stations = load_with_presets();
+    let mut expected = contents(&stations);
+    let mut expected_presets = presets(&stations);
+    let capacity = TestStations::new().free_space();
+
+    // A simple linear congruential generator, so the test is repeatable
+    let mut seed: u64 = 12345;
+    let mut random = |below: usize| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((seed >> 33) as usize) % below.max(1)
+    };
+
+    for step in 0..2000 {
+        let len = expected.len();
+        match random(6) {
+            0 if len > 0 => {
+                let id = random(len);
+                stations.remove_station(id).unwrap();
+                expected.remove(id);
+                for preset in expected_presets.iter_mut() {
+                    *preset = match *preset {
+                        Some(p) if p == id => None,
+                        Some(p) if p > id => Some(p - 1),
+                        p => p,
+                    };
+                }
+            }
+            1 => {
+                let name = format!("Station {step}");
+                let url = format!("http://example.com/{}", "u".repeat(random(100)));
+                let tags = ["Pop", "Rock", "News"][..random(4)].to_vec();
+                if let Ok(id) =
+                    stations.add_station_with_tags(name.as_bytes(), url.as_bytes(), &tags)
+                {
+                    assert_eq!(id, len);
+                    expected.push((name, url, tags.iter().map(|t| t.to_string()).collect()));
+                }
+            }
+            2 if len > 0 => {
+                let id = random(len);
+                let name = format!("Updated {step}");
+                let url = format!("http://example.org/{}", "v".repeat(random(150)));
+                if stations
+                    .update_station(id, name.as_bytes(), url.as_bytes())
+                    .is_ok()
+                {
+                    expected[id].0 = name;
+                    expected[id].1 = url;
+                }
+            }
+            3 if len > 0 => {
+                let id = random(len);
+                let tags = ["Jazz", "Culture", "Info", "UK"][..random(5)].to_vec();
+                if stations.set_station_tags(id, &tags).is_ok() {
+                    expected[id].2 = tags.iter().map(|t| t.to_string()).collect();
+                }
+            }
+            4 if len > 0 => {
+                let (from, to) = (random(len), random(len));
+                stations.move_station(from, to).unwrap();
+                let station = expected.remove(from);
+                expected.insert(to, station);
+                for preset in expected_presets.iter_mut().flatten() {
+                    *preset = match *preset {
+                        p if p == from => to,
+                        p if from < p && p <= to => p - 1,
+                        p if to <= p && p < from => p + 1,
+                        p => p,
+                    };
+                }
+            }
+            5 if len > 0 => {
+                let (id, preset_id) = (random(len), random(NUMBER_PRESETS));
+                stations.set_preset(id, preset_id).unwrap();
+                expected_presets[preset_id] = Some(id);
+            }
+            _ => {
+                let preset_id = random(NUMBER_PRESETS);
+                stations.clear_preset(preset_id).unwrap();
+                expected_presets[preset_id] = None;
+            }
+        }
+
+        assert_eq!(contents(&stations), expected, "step {step}");
+        assert_eq!(presets(&stations), expected_presets, "step {step}");
+        assert_eq!(
+            stations.free_space() + used_space(&expected),
+            capacity,
+            "step {step}"
+        );
+    }
+}
+
+// Writes the stations as CSV into a buffer, returning what was written
+fn write_csv(stations: &TestStations) -> Vec<u8> {
+    let mut buffer = vec![0u8; 8192];
+    let mut remaining = &mut buffer[..];
+    stations.write_csv(&mut remaining).unwrap();
+    let len = 8192 - remaining.len();
+    buffer.truncate(len);
+    buffer
+}
+
+#[test]
+fn test_write_csv() {
+    let data = include_bytes!("resources/stations_with_presets.txt");
+    let stations = TestStations::load(data).unwrap();
+
+    // The file only lacks the new line at the end
+    let csv = write_csv(&stations);
+    assert_eq!(csv, [&data[..], b"\n"].concat());
+
+    let reloaded = TestStations::load(&csv).unwrap();
+    assert_eq!(contents(&reloaded), contents(&stations));
+    assert_eq!(presets(&reloaded), presets(&stations));
+    assert_eq!(write_csv(&reloaded), csv);
+}
+
+#[test]
+fn test_write_csv_round_trip() {
+    let mut stations = TestStations::new();
+    stations
+        .add_station_with_tags(
+            b"Radio \"One\", the best",
+            b"http://radio1.example/stream?a=1,2",
+            &["Pop, Rock", "News"],
+        )
+        .unwrap();
+    stations
+        .add_station(b"Two\nlines", b"http://radio2.example/stream")
+        .unwrap();
+    stations.add_station(b"", b"").unwrap();
+    stations
+        .add_station_with_tags(b" Spaced ", b"http://radio4.example/", &["PRESET"])
+        .unwrap();
+    // A station can be in more than one preset slot
+    stations.set_preset(0, 3).unwrap();
+    stations.set_preset(0, 1).unwrap();
+    stations.set_preset(3, 0).unwrap();
+
+    let csv = write_csv(&stations);
+    assert_eq!(
+        std::str::from_utf8(&csv).unwrap(),
+        "\"Radio \"\"One\"\", the best\",\"http://radio1.example/stream?a=1,2\",\"Pop, Rock\",News,PRESET:1,PRESET:3\n\
+         \"Two\nlines\",http://radio2.example/stream\n\
+         ,\n\
+         \x20Spaced ,http://radio4.example/,PRESET,PRESET:0\n"
+    );
+
+    let reloaded = TestStations::load(&csv).unwrap();
+    assert_eq!(contents(&reloaded), contents(&stations));
+    assert_eq!(presets(&reloaded), presets(&stations));
+    assert_eq!(write_csv(&reloaded), csv);
+
+    // After edits
+    stations.remove_station(1).unwrap();
+    stations.move_station(2, 0).unwrap();
+    stations.set_station_tags(1, &["Jazz"]).unwrap();
+    let reloaded = TestStations::load(&write_csv(&stations)).unwrap();
+    assert_eq!(contents(&reloaded), contents(&stations));
+    assert_eq!(presets(&reloaded), presets(&stations));
+
+    // Nothing at all
+    assert!(write_csv(&TestStations::new()).is_empty());
+}
+
+#[test]
+fn test_write_csv_buffer_too_small() {
+    let stations = load_with_presets();
+
+    let mut buffer = [0u8; 100];
+    assert_eq!(
+        stations.write_csv(&mut &mut buffer[..]),
+        Err(embedded_io::SliceWriteError::Full)
+    );
+}
+
+#[test]
+fn test_invalid_tags() {
+    let mut stations = TestStations::new();
+
+    // They would be read back as presets
+    assert_eq!(
+        stations.add_station_with_tags(b"Radio", b"http://radio.example/", &["PRESET:1"]),
+        Err(StationError::InvalidTag)
+    );
+    assert_eq!(stations.number_stations(), 0);
+
+    stations
+        .add_station_with_tags(b"Radio", b"http://radio.example/", &["Pop"])
+        .unwrap();
+    assert_eq!(
+        stations.set_station_tags(0, &["Rock", " PRESET:2"]),
+        Err(StationError::InvalidTag)
+    );
+    assert!(stations.station_tags(0).unwrap().eq(["Pop"]));
+}
+
+#[test]
+fn test_load_error_position() {
+    let data = b"Radio 1,http://radio1.example/stream\n\
+                 \n\
+                 \"Radio\n2\",http://radio2.example/stream,Pop,PRESET:9\n\
+                 Radio 3,http://radio3.example/stream";
+
+    assert_eq!(
+        TestStations::load(data).err(),
+        Some(LoadError {
+            error: StationError::InvalidPreset,
+            record: 2,
+            line: 4,
+            field: 3,
+        })
+    );
+
+    // Carriage returns are not new lines
+    let data = b"Radio 1,http://radio1.example/stream\r\n\r\nRadio 2,\xff\r\n";
+    assert_eq!(
+        TestStations::load(data).err(),
+        Some(LoadError {
+            error: StationError::UrlNotUtf8,
+            record: 2,
+            line: 3,
+            field: 1,
+        })
+    );
+
+    let data = b"Radio 1,http://radio1.example/stream\n\xff,http://radio2.example/stream";
+    assert_eq!(
+        TestStations::load(data).err(),
+        Some(LoadError {
+            error: StationError::NameNotUtf8,
+            record: 2,
+            line: 2,
+            field: 0,
+        })
+    );
+    assert_eq!(
+        StationError::from(TestStations::load(data).err().unwrap()),
+        StationError::NameNotUtf8
+    );
+}
+
+#[test]
+fn test_load_lenient() {
+    let long_field = "x".repeat(2000);
+    let data = format!(
+        "Radio 1,http://radio1.example/stream,PRESET:1\n\
+         A name that is much too long for a station,http://radio2.example/stream\n\
+         Radio 3,http://radio3.example/stream,Pop,PRESET:0\n\
+         Radio 4,http://radio4.example/stream,Rock,PRESET:7,PRESET:2\n\
+         Radio 5,{long_field},Jazz\n\
+         Radio 6,http://radio6.example/stream,{long_field}\n\
+         Radio 7,http://radio7.example/stream,Rock,PRESET:3"
+    );
+
+    let (stations, warnings) = TestStations::load_lenient(data.as_bytes());
+
+    let mut expected = TestStations::new();
+    expected
+        .add_station(b"Radio 1", b"http://radio1.example/stream")
+        .unwrap();
+    expected
+        .add_station_with_tags(b"Radio 3", b"http://radio3.example/stream", &["Pop"])
+        .unwrap();
+    expected
+        .add_station_with_tags(b"Radio 7", b"http://radio7.example/stream", &["Rock"])
+        .unwrap();
+    assert_eq!(contents(&stations), contents(&expected));
+    // The station that was skipped is not left in a preset
+    assert_eq!(presets(&stations), [Some(1), Some(0), None, Some(2)]);
+    assert!(stations.tags().eq(["Pop", "Rock"]));
+
+    assert_eq!(warnings.number_skipped(), 4);
+    assert!(warnings
+        .iter()
+        .map(|warning| (warning.error, warning.record, warning.field))
+        .eq([
+            (StationError::NameTooLong, 2, 0),
+            (StationError::InvalidPreset, 4, 3),
+            (StationError::CsvFieldTooLong, 5, 1),
+            (StationError::CsvFieldTooLong, 6, 2),
+        ]));
+
+    // The space of the skipped stations is free again
+    assert_eq!(stations.free_space(), expected.free_space());
+
+    let (_, warnings) = TestStations::load_lenient(&write_csv(&stations));
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn test_load_lenient_keeps_earlier_presets() {
+    // The second record takes preset 0 before its invalid preset is found
+    let data = b"Radio 1,http://radio1.example/stream,PRESET:0\n\
+                 Radio 2,http://radio2.example/stream,PRESET:0,PRESET:9\n\
+                 Radio 3,http://radio3.example/stream,PRESET:1,PRESET:3\n";
+
+    let (stations, warnings) = TestStations::load_lenient(data);
+
+    assert_eq!(stations.number_stations(), 2);
+    assert_eq!(presets(&stations), [Some(0), Some(1), None, Some(1)]);
+    assert!(warnings
+        .iter()
+        .map(|warning| (warning.error, warning.record, warning.field))
+        .eq([(StationError::InvalidPreset, 2, 3)]));
+}
+
+#[test]
+fn test_load_lenient_too_many_warnings() {
+    let data =
+        "A name that is much too long for a station,http://radio.example/stream\n".repeat(20);
+
+    let (stations, warnings) = TestStations::load_lenient(data.as_bytes());
+
+    assert_eq!(stations.number_stations(), 0);
+    assert_eq!(warnings.number_skipped(), 20);
+    assert_eq!(warnings.iter().count(), MAX_LOAD_WARNINGS);
+    assert!(warnings
+        .iter()
+        .map(|warning| warning.line)
+        .eq(1..=MAX_LOAD_WARNINGS));
+}
+
+// Loads the data in parts of the length
+fn load_in_parts(
+    loader: &mut TestStationsLoader,
+    data: &[u8],
+    part_len: usize,
+) -> Result<(), LoadError> {
+    data.chunks(part_len).try_for_each(|part| loader.feed(part))
+}
+
+#[test]
+fn test_load_in_parts() {
+    let data = include_bytes!("resources/stations_with_presets.txt");
+    let expected = load_with_presets();
+
+    for part_len in 1..100 {
+        let mut loader = TestStationsLoader::new();
+        load_in_parts(&mut loader, data, part_len).unwrap();
+        let (stations, warnings) = loader.finish().unwrap();
+
+        assert_eq!(contents(&stations), contents(&expected));
+        assert_eq!(presets(&stations), presets(&expected));
+        assert!(warnings.is_empty());
+    }
+
+    // Empty parts are allowed
+    let mut loader = TestStationsLoader::default();
+    loader.feed(b"Radio 1,http://radio1").unwrap();
+    loader.feed(b"").unwrap();
+    loader.feed(b".example/stream").unwrap();
+    let (stations, _) = loader.finish().unwrap();
+    assert_eq!(
+        stations.get_station(0).unwrap().url(),
+        "http://radio1.example/stream"
+    );
+}
+
+#[test]
+fn test_load_in_parts_awkward() {
+    let long_field = "x".repeat(1500);
+    let data = format!(
+        "\"Radio, \"\"1\"\"\",http://radio1.example/stream,PRESET:1\r\n\
+         \r\n\
+         \"Radio\n2\",http://radio2.example/stream,Pop,PRESET:9\r\n\
+         Radio 3,{long_field},Jazz\r\n\
+         Radio 4,http://radio4.example/stream,\"Rock, Pop\",PRESET:0\r\n"
+    );
+    let data = data.as_bytes();
+    let (expected, expected_warnings) = TestStations::load_lenient(data);
+    assert_eq!(expected.number_stations(), 2);
+    assert_eq!(expected_warnings.number_skipped(), 2);
+
+    for part_len in [1, 2, 3, 7, 64, 1000, 2000] {
+        let mut loader = TestStationsLoader::lenient();
+        load_in_parts(&mut loader, data, part_len).unwrap();
+        let (stations, warnings) = loader.finish().unwrap();
+
+        assert_eq!(contents(&stations), contents(&expected));
+        assert_eq!(presets(&stations), presets(&expected));
+        assert_eq!(warnings, expected_warnings);
+
+        // The strict loader stops at the first invalid record
+        let mut loader = TestStationsLoader::new();
+        assert_eq!(
+            load_in_parts(&mut loader, data, part_len),
+            Err(LoadError {
+                error: StationError::InvalidPreset,
+                record: 2,
+                line: 4,
+                field: 3,
+            })
+        );
+    }
+}
+
+#[test]
+fn test_load_in_parts_last_record_invalid() {
+    // The last field is only known to be complete at the end
+    let mut loader = TestStationsLoader::new();
+    loader
+        .feed(
+            b"Radio 1,http://radio1.example/stream\nRadio 2,http://radio2.example/stream,PRESET:4",
+        )
+        .unwrap();
+    assert_eq!(
+        loader.finish().err(),
+        Some(LoadError {
+            error: StationError::InvalidPreset,
+            record: 2,
+            line: 2,
+            field: 2,
+        })
+    );
+}